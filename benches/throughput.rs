@@ -0,0 +1,71 @@
+extern crate tiny_machine;
+
+use std::time::Instant;
+use tiny_machine::prelude::*;
+
+const RUNS: usize = 20_000;
+const LONG_BUDGET: usize = 2_000_000;
+
+// images that only stop at the budget
+const IMAGES: [MachineInner; 4] = [
+  [0x00, 0x00, 0xD1, 0x00, 0, 0, 0, 0, 0, 0], // del; jmp 0
+  [0x00, 0x00, 0x46, 0xA7, 0x10, 0x35, 0, 0, 0, 0], // lda 6; adc 7; jmp 0
+  [0x00, 0x00, 0x8F, 0x9C, 0x10, 0x00, 0, 0, 0, 0], // rol; fla; ror; scf; jmp 0
+  [0x00, 0x00, 0x02, 0x03, 0x10, 0x00, 0, 0, 0, 0], // push; pop; jmp 0, extended
+];
+
+fn report(name: &str, calls: usize, start: Instant) {
+  let secs = start.elapsed().as_secs_f64();
+  println!{"{:<18} {:>10} instructions in {:>7.3}s  {:>7.2}M/s",
+    name, calls, secs, calls as f64 / secs / 1e6};
+}
+
+// many short runs, what solving and the server do
+fn short_runs(name: &str, setup: fn(Machine) -> Machine) {
+  let start = Instant::now();
+  let mut calls = 0;
+  for i in 0..RUNS {
+    let image = IMAGES[i % IMAGES.len()];
+    let mut machine = setup(Machine::from(image).set_isa(Isa::Extended));
+    assert_eq!{machine.run(), HaltReason::Budget};
+    calls += machine.get_cc();
+  }
+  report(name, calls, start);
+}
+
+// a few long runs, the interpreter loop alone
+fn long_runs(engine: Engine) {
+  let start = Instant::now();
+  let mut calls = 0;
+  for image in IMAGES.iter() {
+    let mut machine = Machine::from(*image).set_isa(Isa::Extended)
+      .set_budget(LONG_BUDGET).set_engine(engine);
+    assert_eq!{machine.run(), HaltReason::Budget};
+    calls += machine.get_cc();
+  }
+  report(&format!{"long {:?}", engine}, calls, start);
+}
+
+// the short runs again through Batch, on one thread and on every core
+fn batch_runs() {
+  let ruleset = Ruleset { isa: Isa::Extended, ..Ruleset::default() };
+  let images: Vec<MachineInner> = (0..RUNS).map(|i| IMAGES[i % IMAGES.len()]).collect();
+  for threads in [1, 0].iter() {
+    let batch = Batch::new(ruleset).set_threads(*threads);
+    let start = Instant::now();
+    let results = batch.run(&images);
+    assert!{results.iter().all(|r| r.reason == HaltReason::Budget)};
+    let calls = results.iter().map(|r| r.calls).sum();
+    report(&format!{"batch x{}", batch.get_threads()}, calls, start);
+  }
+}
+
+fn main() {
+  short_runs("short", |machine| machine);
+  // a log call per step, as every step made before the interpreter stopped logging
+  short_runs("short hook", |machine| machine.set_hook(Some(log_step)));
+  short_runs("short recorded", |machine| machine.set_recording(true));
+  long_runs(Engine::Interpreter);
+  long_runs(Engine::Cached);
+  batch_runs();
+}
@@ -0,0 +1,16 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tokio::runtime::current_thread::block_on_all;
+use tiny_machine::prelude::*;
+
+// the whole session, client bytes sent at once and the stream closed
+fuzz_target!(|data: &[u8]| {
+  let (stream, peer) = duplex();
+  peer.send(data);
+  peer.close();
+  let id = block_on_all(Game::start(stream, 0, TIMEOUT)).unwrap();
+  assert_eq!{id, 0};
+  let reply = String::from_utf8(peer.received()).unwrap();
+  assert!{reply.starts_with(GREETING)};
+  assert_ne!{reply.contains("Flag: "), reply.contains("Failed to complete TinyMachine")};
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tiny_machine::prelude::*;
+
+// the text formats stand in for an assembler, anything parsed renders back the same
+fuzz_target!(|data: &[u8]| {
+  for format in [Format::Raw, Format::Hex, Format::IntelHex, Format::Annotated].iter() {
+    if let Ok(image) = format.parse(data) {
+      assert_eq!{format.parse(&format.render(&image)), Ok(image)};
+    }
+  }
+  let _ = load_image(data);
+});
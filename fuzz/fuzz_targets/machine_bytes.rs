@@ -0,0 +1,22 @@
+#![no_main]
+use std::convert::TryFrom;
+use libfuzzer_sys::fuzz_target;
+use tiny_machine::prelude::*;
+
+// untrusted bytes as the server loads them, on both engines
+fuzz_target!(|data: &[u8]| {
+  let mut machine = match Level::current().try_load(data, 0) {
+    Ok(machine) => machine,
+    Err(_) => {
+      assert!{Machine::try_from(data).is_err()};
+      return
+    },
+  };
+  let mut cached = machine.clone().set_engine(Engine::Cached);
+  let reason = machine.run();
+  assert!{machine.get_cc() <= machine.get_budget()};
+  assert_eq!{machine.get_halt_reason(), Some(reason.clone())};
+  assert_eq!{cached.run(), reason};
+  assert_eq!{cached.snapshot(), machine.snapshot()};
+  let _ = Level::current().is_valid(&machine);
+});
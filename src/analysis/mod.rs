@@ -0,0 +1,130 @@
+#[cfg(test)] mod test;
+
+use std::collections::HashMap;
+use crate::machine::{Machine, MachineError};
+use crate::memory::MemoryInner;
+use crate::registers::RegisterInner;
+
+#[derive(Clone,PartialEq,Debug)]
+pub enum Prediction {
+  Halt,                // HLT, or an invalid opcode on level 1
+  Fault(MachineError),
+  Budget,              // still running at the call limit
+}
+
+// a loop in the state trace, steps entry.. repeat every length steps
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct Cycle {
+  pub entry: usize,
+  pub length: usize,
+  pub ip: u8,  // IP at the entry state
+}
+
+// Everything that decides the next step, call and cycle counts aside
+fn state_key(machine: &Machine) -> Vec<u8> {
+  let reg: RegisterInner = machine.get_reg().clone().into();
+  let mem: MemoryInner = machine.get_mem().clone().into();
+  let irq = machine.get_irq();
+  let (inp, outp) = (machine.get_inp(), machine.get_outp());
+  let mut key = Vec::with_capacity(32);
+  key.extend_from_slice(&reg);
+  key.extend_from_slice(&mem);
+  key.push(machine.get_stack().len() as u8);
+  key.extend_from_slice(machine.get_stack().get_all());
+  key.extend_from_slice(&[
+    irq.is_enabled() as u8, irq.get_mask(), irq.get_pending(),
+    irq.get_timer(), irq.get_reload(), irq.get_vector(),
+  ]);
+  key.push(inp.len() as u8);
+  key.extend(inp.iter());
+  key.push(inp.underflowed() as u8);
+  key.push(outp.len() as u8);
+  key.extend(outp.iter());
+  key.push(outp.overflowed() as u8);
+  key
+}
+
+// Deterministic trace of a machine up to a step limit. Once a state repeats
+// every later step is known, so exec's outcome is predicted without running
+// to the limit.
+#[derive(Clone)]
+pub struct Analysis {
+  trace: Vec<Machine>, // state before each step, plus the final state
+  cycle: Option<Cycle>,
+  prediction: Prediction,
+  limit: usize,
+}
+impl Analysis {
+  pub fn new(machine: &Machine) -> Self {
+    Analysis::with_limit(machine, machine.get_budget())
+  }
+  // limit counts calls like Machine::get_cc, past the machine's budget it
+  // only matters for finding cycles
+  pub fn with_limit(machine: &Machine, limit: usize) -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Analysis::with_limit()"};
+    let mut machine = machine.clone();
+    let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+    // device state is opaque, so a repeat is only a cycle without devices
+    let detect = machine.get_mem().get_bus().is_empty();
+    let mut trace = Vec::new();
+    let mut cycle = None;
+    let prediction = loop {
+      if machine.get_reg().get_hf() {
+        break Prediction::Halt;
+      }
+      if limit <= machine.get_cc() {
+        break Prediction::Budget;
+      }
+      if detect {
+        let step = trace.len();
+        if let Some(entry) = seen.insert(state_key(&machine), step) {
+          let ip = machine.get_reg().get_ip();
+          cycle = Some(Cycle { entry, length: step - entry, ip });
+          break Prediction::Budget;
+        }
+      }
+      trace.push(machine.clone());
+      if let Err(e) = machine.step() {
+        break Prediction::Fault(e);
+      }
+    };
+    trace.push(machine);
+    Analysis { trace, cycle, prediction, limit }
+  }
+  pub fn get_trace(&self) -> &[Machine] {
+    &self.trace
+  }
+  pub fn get_cycle(&self) -> Option<Cycle> {
+    self.cycle
+  }
+  pub fn get_prediction(&self) -> &Prediction {
+    &self.prediction
+  }
+  pub fn halts(&self) -> bool {
+    self.prediction != Prediction::Budget
+  }
+  // calls exec would report
+  pub fn get_cc(&self) -> usize {
+    match self.cycle {
+      Some(_) => self.limit,
+      None => self.trace.last().map(|m| m.get_cc()).unwrap_or(0),
+    }
+  }
+  // state exec would stop in, unrolling the cycle when there is one
+  pub fn final_state(&self) -> Machine {
+    let last = &self.trace[self.trace.len() - 1];
+    let c = match self.cycle {
+      Some(c) => c,
+      None => return last.clone(),
+    };
+    // every step in a cycle is a call, so calls map straight to steps
+    let entry = &self.trace[c.entry];
+    let remaining = self.limit - entry.get_cc();
+    let mut machine = self.trace[c.entry + remaining % c.length].clone();
+    let per_loop = last.get_cycles() - entry.get_cycles();
+    *machine.get_mut_cycles() += (remaining / c.length) * per_loop;
+    *machine.get_mut_cc() = self.limit;
+    machine
+  }
+}
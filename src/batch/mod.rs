@@ -0,0 +1,96 @@
+#[cfg(test)] mod test;
+
+use std::thread;
+use crate::machine::{MachineInner, HaltReason};
+use crate::level::Ruleset;
+use crate::instructions::DecodeTable;
+
+// What one image did, small enough to keep millions of
+#[derive(Clone,PartialEq,Debug)]
+pub struct BatchResult {
+  pub reason: HaltReason,
+  pub calls: usize,
+  pub state: MachineInner, // image once it stopped
+  pub valid: bool,         // the ruleset's level accepts the end state
+}
+
+// Runs images one after another under a ruleset, decoding through a table.
+// Nothing is allocated per image while the level maps no devices.
+pub struct Runner {
+  ruleset: Ruleset,
+  seed: u32,
+  table: DecodeTable,
+}
+impl Runner {
+  pub fn new(ruleset: Ruleset, seed: u32) -> Self {
+    Runner {
+      ruleset,
+      seed,
+      table: DecodeTable::from(ruleset.isa),
+    }
+  }
+  // same end state as loading the image and calling run
+  pub fn run(&self, image: MachineInner) -> BatchResult {
+    let mut machine = self.ruleset.load(image, self.seed);
+    let reason = loop {
+      if let Some(reason) = machine.get_halt_reason() {
+        break reason;
+      }
+      // a fault is kept by the machine and ends the loop above
+      let _ = machine.step_with(&self.table);
+    };
+    BatchResult {
+      reason,
+      calls: machine.get_cc(),
+      state: (&machine).into(),
+      valid: self.ruleset.level.is_valid(&machine),
+    }
+  }
+}
+
+// Many images split across threads, results in image order
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct Batch {
+  ruleset: Ruleset,
+  seed: u32,
+  threads: usize,
+}
+impl Batch {
+  pub fn new(ruleset: Ruleset) -> Self {
+    Batch {
+      ruleset,
+      seed: 0,
+      threads: 1,
+    }
+  }
+  pub fn set_seed(mut self, seed: u32) -> Self {
+    self.seed = seed;
+    self
+  }
+  // 0 uses every available core
+  pub fn set_threads(mut self, threads: usize) -> Self {
+    self.threads = threads;
+    self
+  }
+  pub fn get_threads(&self) -> usize {
+    match self.threads {
+      0 => thread::available_parallelism().map_or(1, |n| n.get()),
+      n => n,
+    }
+  }
+  pub fn run(&self, images: &[MachineInner]) -> Vec<BatchResult> {
+    let threads = self.get_threads().min(images.len());
+    if threads <= 1 {
+      let runner = Runner::new(self.ruleset, self.seed);
+      return images.iter().map(|image| runner.run(*image)).collect()
+    }
+    let chunk = images.len().div_ceil(threads);
+    thread::scope(|scope| {
+      let workers: Vec<_> = images.chunks(chunk).map(|part| scope.spawn(move || {
+        let runner = Runner::new(self.ruleset, self.seed);
+        part.iter().map(|image| runner.run(*image)).collect::<Vec<_>>()
+      })).collect();
+      workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+    })
+  }
+}
@@ -0,0 +1,78 @@
+use crate::batch::*;
+use crate::machine::*;
+use crate::level::{Level, Ruleset};
+use crate::instructions::{Instruction, Isa, DecodeTable};
+
+// deterministic images, HF cleared so none are refused
+fn images(count: usize) -> Vec<MachineInner> {
+  let mut x: u32 = 0x2545_F491;
+  (0..count).map(|_| {
+    let mut image: MachineInner = [0; MACHINE_SIZE];
+    for b in image.iter_mut() {
+      x ^= x << 13;
+      x ^= x >> 17;
+      x ^= x << 5;
+      *b = x as u8;
+    }
+    image[1] &= 0x7F;
+    image
+  }).collect()
+}
+
+#[test]
+fn decode_table() {
+  for isa in [Isa::Standard, Isa::Extended].iter() {
+    let table = DecodeTable::from(*isa);
+    assert_eq!{table.get_isa(), *isa};
+    for b in 0..=255u8 {
+      assert_eq!{*table.get(b), Instruction::decode(b, 0, *isa)};
+    }
+  }
+}
+#[test]
+fn matches_run() {
+  let rulesets = [
+    Ruleset::default(),
+    Ruleset { isa: Isa::Extended, ..Ruleset::default() },
+    Ruleset { level: Level::One, ..Ruleset::default() },
+    Ruleset { level: Level::Three, isa: Isa::Extended, ..Ruleset::default() },
+  ];
+  for ruleset in rulesets.iter() {
+    let runner = Runner::new(*ruleset, 0);
+    for image in images(500) {
+      let mut machine = ruleset.load(image, 0);
+      let reason = machine.run();
+      let expect = BatchResult {
+        reason,
+        calls: machine.get_cc(),
+        state: (&machine).into(),
+        valid: ruleset.level.is_valid(&machine),
+      };
+      assert_eq!{runner.run(image), expect, "{:02X?} {:?}", image, ruleset};
+    }
+  }
+}
+#[test]
+fn threads_keep_order() {
+  let ruleset = Ruleset { isa: Isa::Extended, ..Ruleset::default() };
+  let images = images(1000);
+  let single = Batch::new(ruleset).run(&images);
+  assert_eq!{single.len(), images.len()};
+  assert_eq!{Batch::new(ruleset).set_threads(4).run(&images), single};
+  assert_eq!{Batch::new(ruleset).set_threads(0).run(&images), single};
+  assert!{Batch::new(ruleset).set_threads(0).get_threads() >= 1};
+  assert!{Batch::new(ruleset).set_threads(8).run(&[]).is_empty()};
+}
+#[test]
+fn winning_image() {
+  let ruleset = Ruleset { level: Level::Two, ..Ruleset::default() };
+  // AC already 5, hlt at 0
+  let results = Batch::new(ruleset).run(&[[0x00, 0x05, 0, 0, 0, 0, 0, 0, 0, 0]]);
+  assert_eq!{results[0].reason, HaltReason::Halted};
+  assert_eq!{results[0].calls, 1};
+  assert!{results[0].valid};
+  // jmp 0 forever
+  let results = Batch::new(ruleset).run(&[[0x00, 0x05, 0x10, 0, 0, 0, 0, 0, 0, 0]]);
+  assert_eq!{(results[0].reason.clone(), results[0].calls), (HaltReason::Budget, Level::Two.budget())};
+  assert!{!results[0].valid};
+}
@@ -0,0 +1,223 @@
+#[cfg(test)] mod test;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use crate::instructions::{Instruction, Isa};
+use crate::machine::Machine;
+use crate::memory::{Memory, MEMORY_SIZE};
+
+const NIBBLES: u8 = (MEMORY_SIZE * 2) as u8;
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum EdgeKind {
+  Fallthrough,
+  Jump,      // JMP
+  Taken,     // JZE/JNZ with the condition met
+  NotTaken,
+  Call,      // CALL into the routine
+  Return,    // where RET lands after a CALL
+}
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct Edge {
+  pub from: u8, // block start
+  pub to: u8,
+  pub kind: EdgeKind,
+  pub wraps: bool, // sequential flow past nibble 15 back to 0
+}
+
+// straight line run of instructions, by address
+#[derive(Clone,PartialEq,Debug)]
+pub struct Block {
+  pub start: u8,
+  pub addrs: Vec<u8>,
+}
+
+// an STA whose target nibble is part of a reachable instruction
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct CodeWrite {
+  pub at: u8,     // address of the STA
+  pub target: u8, // nibble written
+  pub inst: u8,   // instruction covering the target
+}
+
+// Static control flow of the code reachable from an entry point. Jumps into
+// the middle of another instruction decode a second, overlapping instruction
+// at that address, so every address decodes on its own.
+#[derive(Clone,Debug)]
+pub struct Cfg {
+  entry: u8,
+  insts: BTreeMap<u8, Instruction>,
+  blocks: BTreeMap<u8, Block>,
+  edges: Vec<Edge>,
+  writes: Vec<CodeWrite>,
+}
+impl<'a> From<&'a Machine> for Cfg {
+  fn from(machine: &'a Machine) -> Self {
+    Cfg::new(machine.get_mem(), machine.get_reg().get_ip(), machine.get_isa())
+  }
+}
+impl Cfg {
+  pub fn new(mem: &Memory, entry: u8, isa: Isa) -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Cfg::new()"};
+    let entry = entry % NIBBLES;
+    // instruction level graph first
+    let mut insts = BTreeMap::new();
+    let mut succs: BTreeMap<u8, Vec<(u8, EdgeKind)>> = BTreeMap::new();
+    let mut work = vec![entry];
+    while let Some(addr) = work.pop() {
+      if insts.contains_key(&addr) {
+        continue;
+      }
+      let inst = decode(mem, addr, isa);
+      let next = |n: u8| (addr + n) % NIBBLES;
+      let out = match inst {
+        Instruction::HLT | Instruction::RET |
+        Instruction::RTI | Instruction::INVALID(_) => vec![],
+        Instruction::JMP(a) => vec![(a, EdgeKind::Jump)],
+        Instruction::JZE(a) | Instruction::JNZ(a) =>
+          vec![(a, EdgeKind::Taken), (next(inst.size()), EdgeKind::NotTaken)],
+        Instruction::CALL(a) =>
+          vec![(a, EdgeKind::Call), (next(inst.size()), EdgeKind::Return)],
+        _ => vec![(next(inst.size()), EdgeKind::Fallthrough)],
+      };
+      work.extend(out.iter().map(|e| e.0));
+      succs.insert(addr, out);
+      insts.insert(addr, inst);
+    }
+    // leaders start blocks: the entry, branch targets and successors,
+    // anything reached from more than one place, and wrap arounds so the
+    // wrap shows up as an edge
+    let mut preds: BTreeMap<u8, usize> = BTreeMap::new();
+    let mut leaders: BTreeSet<u8> = BTreeSet::new();
+    leaders.insert(entry);
+    for (&from, out) in succs.iter() {
+      for &(to, kind) in out.iter() {
+        *preds.entry(to).or_insert(0) += 1;
+        if kind != EdgeKind::Fallthrough || to < from {
+          leaders.insert(to);
+        }
+      }
+    }
+    leaders.extend(preds.iter().filter(|p| *p.1 > 1).map(|p| *p.0));
+    let mut blocks = BTreeMap::new();
+    let mut edges = Vec::new();
+    for &start in leaders.iter() {
+      let mut addrs = vec![start];
+      let mut addr = start;
+      loop {
+        let out = &succs[&addr];
+        let wraps = |to: u8, kind: EdgeKind| to < addr && matches!{kind,
+          EdgeKind::Fallthrough | EdgeKind::NotTaken | EdgeKind::Return};
+        match out.as_slice() {
+          [(to, EdgeKind::Fallthrough)] if !leaders.contains(to) => {
+            addrs.push(*to);
+            addr = *to;
+          },
+          _ => {
+            edges.extend(out.iter().map(|&(to, kind)| Edge {
+              from: start, to, kind, wraps: wraps(to, kind),
+            }));
+            break;
+          },
+        }
+      }
+      blocks.insert(start, Block { start, addrs });
+    }
+    let writes = code_writes(&insts);
+    Cfg { entry, insts, blocks, edges, writes }
+  }
+  pub fn get_entry(&self) -> u8 {
+    self.entry
+  }
+  pub fn get_inst(&self, addr: u8) -> Option<&Instruction> {
+    self.insts.get(&addr)
+  }
+  pub fn get_blocks(&self) -> Vec<&Block> {
+    self.blocks.values().collect()
+  }
+  pub fn get_block(&self, start: u8) -> Option<&Block> {
+    self.blocks.get(&start)
+  }
+  pub fn get_edges(&self) -> &[Edge] {
+    &self.edges
+  }
+  pub fn get_writes(&self) -> &[CodeWrite] {
+    &self.writes
+  }
+  // block holding the instruction at addr
+  pub fn block_of(&self, addr: u8) -> Option<&Block> {
+    self.blocks.values().find(|b| b.addrs.contains(&addr))
+  }
+  // Graphviz, blocks whose code is overwritten are red
+  pub fn to_dot(&self) -> String {
+    let mut dot = String::new();
+    let _ = writeln!{dot, "digraph cfg {{"};
+    let _ = writeln!{dot, "  node [shape=box fontname=monospace];"};
+    let _ = writeln!{dot, "  start [shape=point];"};
+    let _ = writeln!{dot, "  start -> b{:X};", self.entry};
+    for block in self.blocks.values() {
+      let label: String = block.addrs.iter().map(|a| {
+        let s: String = (&self.insts[a]).into();
+        format!{"{:X}: {}\\l", a, s}
+      }).collect();
+      let modified = self.writes.iter().any(|w| block.addrs.contains(&w.inst));
+      let color = if modified { " color=red" } else { "" };
+      let _ = writeln!{dot, "  b{:X} [label=\"{}\"{}];", block.start, label, color};
+    }
+    for edge in self.edges.iter() {
+      let label = match edge.kind {
+        EdgeKind::Fallthrough => "",
+        EdgeKind::Jump => "jump",
+        EdgeKind::Taken => "taken",
+        EdgeKind::NotTaken => "not taken",
+        EdgeKind::Call => "call",
+        EdgeKind::Return => "return",
+      };
+      let label = match edge.wraps {
+        true if label.is_empty() => "wrap".to_string(),
+        true => format!{"{}, wrap", label},
+        false => label.to_string(),
+      };
+      let style = if edge.wraps { " style=dashed" } else { "" };
+      let _ = writeln!{dot, "  b{:X} -> b{:X} [label=\"{}\"{}];",
+        edge.from, edge.to, label, style};
+    }
+    for write in self.writes.iter() {
+      if let (Some(from), Some(to)) = (self.block_of(write.at), self.block_of(write.inst)) {
+        let _ = writeln!{dot, "  b{:X} -> b{:X} [label=\"writes {:X}\" style=dotted color=red];",
+          from.start, to.start, write.target};
+      }
+    }
+    let _ = writeln!{dot, "}}"};
+    dot
+  }
+}
+
+// same fetch as Machine::current_instruction
+fn decode(mem: &Memory, addr: u8, isa: Isa) -> Instruction {
+  let byte = mem.get_loc_u8(addr as usize).unwrap_or(0);
+  let next = match isa {
+    Isa::Extended => mem.get_loc(((addr + 2) % NIBBLES) as usize).unwrap_or(0),
+    Isa::Standard => 0,
+  };
+  Instruction::decode(byte, next, isa)
+}
+
+fn code_writes(insts: &BTreeMap<u8, Instruction>) -> Vec<CodeWrite> {
+  let mut writes = Vec::new();
+  for (&at, inst) in insts.iter() {
+    let target = match *inst {
+      Instruction::STA(a) => a,
+      _ => continue,
+    };
+    for (&addr, covering) in insts.iter() {
+      let covers = (0..covering.size()).any(|n| (addr + n) % NIBBLES == target);
+      if covers {
+        writes.push(CodeWrite { at, target, inst: addr });
+      }
+    }
+  }
+  writes
+}
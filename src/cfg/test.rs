@@ -0,0 +1,71 @@
+use crate::cfg::*;
+use crate::machine::*;
+use crate::memory::Memory;
+use crate::instructions::{Instruction, Isa};
+
+fn memory(mem: [u8; 8]) -> Memory {
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  image[2..].copy_from_slice(&mem);
+  Machine::from(image).get_mem().clone()
+}
+
+#[test]
+fn branch_blocks() {
+  // lda 7; jze 5; hlt; scf; hlt
+  let cfg = Cfg::new(&memory([0x47, 0x25, 0x0C, 0x05, 0, 0, 0, 0]), 0, Isa::Standard);
+  let starts: Vec<u8> = cfg.get_blocks().iter().map(|b| b.start).collect();
+  assert_eq!{starts, vec![0, 4, 5]};
+  assert_eq!{cfg.get_block(0).unwrap().addrs, vec![0, 2]};
+  assert_eq!{cfg.get_block(5).unwrap().addrs, vec![5, 6]};
+  assert_eq!{cfg.get_inst(2), Some(&Instruction::JZE(5))};
+  assert_eq!{cfg.get_edges(), &[
+    Edge { from: 0, to: 5, kind: EdgeKind::Taken, wraps: false },
+    Edge { from: 0, to: 4, kind: EdgeKind::NotTaken, wraps: false },
+  ]};
+  assert!{cfg.get_writes().is_empty()};
+  let dot = cfg.to_dot();
+  assert!{dot.starts_with("digraph cfg {")};
+  assert!{dot.contains("b0 -> b5 [label=\"taken\"]")};
+  assert!{dot.contains("b0 -> b4 [label=\"not taken\"]")};
+}
+#[test]
+fn wrap_around() {
+  // hlt at 0, scf; scf at 14 and 15
+  let cfg = Cfg::new(&memory([0, 0, 0, 0, 0, 0, 0, 0xCC]), 14, Isa::Standard);
+  assert_eq!{cfg.get_block(14).unwrap().addrs, vec![14, 15]};
+  assert_eq!{cfg.get_edges(), &[
+    Edge { from: 14, to: 0, kind: EdgeKind::Fallthrough, wraps: true },
+  ]};
+  assert!{cfg.to_dot().contains("bE -> b0 [label=\"wrap\" style=dashed]")};
+}
+#[test]
+fn self_modifying() {
+  // sta 2; hlt -- the store overwrites the hlt
+  let cfg = Cfg::new(&memory([0x52, 0, 0, 0, 0, 0, 0, 0]), 0, Isa::Standard);
+  assert_eq!{cfg.get_writes(), &[CodeWrite { at: 0, target: 2, inst: 2 }]};
+  let dot = cfg.to_dot();
+  assert!{dot.contains("color=red")};
+  assert!{dot.contains("label=\"writes 2\"")};
+}
+#[test]
+fn loop_back() {
+  // del; jnz 0; hlt
+  let cfg = Cfg::new(&memory([0xD3, 0x00, 0, 0, 0, 0, 0, 0]), 0, Isa::Standard);
+  assert_eq!{cfg.get_block(0).unwrap().addrs, vec![0, 1]};
+  assert_eq!{cfg.get_edges(), &[
+    Edge { from: 0, to: 0, kind: EdgeKind::Taken, wraps: false },
+    Edge { from: 0, to: 3, kind: EdgeKind::NotTaken, wraps: false },
+  ]};
+}
+#[test]
+fn extended_call() {
+  // call 4; hlt; ret at 4
+  let mut machine = Machine::from([0, 0, 0x04, 0x40, 0x01, 0, 0, 0, 0, 0]);
+  machine = machine.set_isa(Isa::Extended);
+  let cfg = Cfg::from(&machine);
+  assert_eq!{cfg.get_inst(0), Some(&Instruction::CALL(4))};
+  assert_eq!{cfg.get_edges(), &[
+    Edge { from: 0, to: 4, kind: EdgeKind::Call, wraps: false },
+    Edge { from: 0, to: 3, kind: EdgeKind::Return, wraps: false },
+  ]};
+}
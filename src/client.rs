@@ -1,21 +1,18 @@
 #[macro_use] extern crate log;
 extern crate pretty_env_logger;
-extern crate tokio;
-extern crate tokio_core;
-extern crate futures;
+extern crate clap;
 
 extern crate tiny_machine;
 
-use std::sync::atomic::{AtomicUsize, Ordering};
-use tokio::net::TcpListener;
-use tokio::prelude::*;
-use tokio_core::reactor::Core;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::process;
+use clap::{App, Arg};
 
 use tiny_machine::prelude::*;
 
-#[cfg(feature="real_flag")]      const IP: &str = "0.0.0.0";
-#[cfg(not(feature="real_flag"))] const IP: &str = "127.0.0.1";
-
 #[cfg(feature = "lvl1")] const PORT: &str = "12346";
 #[cfg(feature = "lvl2")] const PORT: &str = "45678";
 #[cfg(feature = "lvl3")] const PORT: &str = "61830";
@@ -24,35 +21,58 @@ use tiny_machine::prelude::*;
 fn main() {
   pretty_env_logger::init();
 
+  let default_addr = format!{"127.0.0.1:{}", PORT};
+  let matches = App::new("tm_client")
+    .about("Submit an image file to a TinyMachine server")
+    .arg(Arg::with_name("image")
+      .help("Image file, raw, hex, Intel hex or annotated text")
+      .required(true))
+    .arg(Arg::with_name("format")
+      .long("format")
+      .help("Skip detection and read the file as this format")
+      .takes_value(true)
+      .possible_values(&["raw", "hex", "ihex", "text"]))
+    .arg(Arg::with_name("addr")
+      .long("addr")
+      .takes_value(true)
+      .default_value(&default_addr))
+    .get_matches();
 
-  #[cfg(not(feature="lvl3"))] debug!{"Starting Tokio::Core"}
-  let mut core = Core::new().unwrap();
-  let handle = core.handle();
-
-
-  #[cfg(not(feature="lvl3"))] trace!{"Preparing server configuration"}
-  let addr = format!{"{}:{}", IP, PORT};
-
-  debug!{"Binding to: {}", addr};
-  let addr = addr.parse().unwrap();
-  let listener = TcpListener::bind(&addr).unwrap();
-  let client_count = AtomicUsize::new(0);
-
-  let server = listener.incoming().for_each(move |socket| { // TcpStream
-
-      #[cfg(not(feature="lvl3"))] debug!{"Accepted socket; addr={:?}", socket.peer_addr().unwrap()};
-      let id = client_count.fetch_add(1, Ordering::SeqCst);
-      let game = Game::start(socket, id)
-        .map_err(|err| error!{"Client error = {:?}", err})
-        .map(|p| {
-          //println!{"game.map()"}
-          info!{"Player finished {}", p}
-        });
+  let res = load(matches.value_of("image").unwrap(), matches.value_of("format"))
+    .and_then(|image| submit(matches.value_of("addr").unwrap(), &image));
+  match res {
+    Ok(reply) => print!{"{}", reply},
+    Err(e) => {
+      eprintln!{"{}", e};
+      process::exit(1);
+    },
+  };
+}
 
-      handle.spawn(game);
-      Ok(())
-  })
-  .map_err(|err| { error!("accept error = {:?}", err); });
+fn load(path: &str, format: Option<&str>) -> Result<MachineInner, String> {
+  let mut bytes = Vec::new();
+  File::open(path)
+    .and_then(|mut f| f.read_to_end(&mut bytes))
+    .map_err(|e| e.to_string())?;
+  let format = match format {
+    Some(name) => name.parse().map_err(|e| error_chain(&e))?,
+    None => Format::detect(&bytes),
+  };
+  debug!{"Reading {} as {:?}", path, format};
+  let image = format.parse(&bytes).map_err(|e| error_chain(&e))?;
+  // the server would refuse it anyway
+  Machine::try_from(&image[..]).map_err(|e| error_chain(&e))?;
+  Ok(image)
+}
 
-  core.run(server).unwrap();
+// the server greets, reads the raw image, then answers and closes
+fn submit(addr: &str, image: &MachineInner) -> Result<String, String> {
+  let mut stream = TcpStream::connect(addr).map_err(|e| e.to_string())?;
+  let mut greeting = [0; 64];
+  let size = stream.read(&mut greeting).map_err(|e| e.to_string())?;
+  debug!{"Server said: {}", String::from_utf8_lossy(&greeting[..size])};
+  stream.write_all(image).map_err(|e| e.to_string())?;
+  let mut reply = String::new();
+  stream.read_to_string(&mut reply).map_err(|e| e.to_string())?;
+  Ok(reply)
 }
@@ -0,0 +1,111 @@
+#[cfg(test)] mod test;
+
+use bit_field::*;
+use crate::machine::{Machine, MachineError, HaltReason};
+
+#[derive(Clone,PartialEq,Debug)]
+pub enum Stop {
+  Step,               // a single step finished
+  Breakpoint(u8),     // IP reached a breakpoint
+  Halted,             // HLT, or out of calls
+  Fault(MachineError),
+  Start,              // nothing left to step back over
+}
+
+// Steps a journaled machine in either direction, stopping at breakpoints.
+#[derive(Clone,Debug)]
+pub struct Debugger {
+  machine: Machine,
+  breakpoints: u16, // bitmask by IP
+}
+impl Debugger {
+  pub fn new(machine: Machine) -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Debugger::new()"};
+    Debugger {
+      machine: machine.set_journal(true),
+      breakpoints: 0,
+    }
+  }
+  pub fn get_machine(&self) -> &Machine {
+    &self.machine
+  }
+  // steps run since the start, where step_back can return to
+  pub fn get_position(&self) -> usize {
+    self.machine.get_journal().map_or(0, |j| j.len())
+  }
+  pub fn get_breakpoints(&self) -> Vec<u8> {
+    (0..16).filter(|ip| self.breakpoints.get_bit(*ip as usize)).collect()
+  }
+  pub fn is_breakpoint(&self, ip: u8) -> bool {
+    (ip as usize) < 16 && self.breakpoints.get_bit(ip as usize)
+  }
+  pub fn set_breakpoint(mut self, ip: u8) -> Self {
+    self.breakpoints.set_bit(ip as usize % 16, true);
+    self
+  }
+  pub fn clear_breakpoint(mut self, ip: u8) -> Self {
+    self.breakpoints.set_bit(ip as usize % 16, false);
+    self
+  }
+  pub fn step(&mut self) -> Stop {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Debugger::step()"};
+    if self.machine.is_stopped() {
+      return Stop::Halted
+    }
+    match self.machine.step() {
+      Err(e) => Stop::Fault(e),
+      Ok(()) if self.machine.is_stopped() => Stop::Halted,
+      Ok(()) => Stop::Step,
+    }
+  }
+  // runs until a breakpoint, halt or fault, leaving a breakpoint first
+  pub fn cont(&mut self) -> Stop {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Debugger::cont()"};
+    match self.step() {
+      Stop::Step => {},
+      stop => return stop,
+    };
+    let breakpoints = self.breakpoints;
+    let reason = self.machine.run_until(|m| {
+      let ip = m.get_reg().get_ip();
+      match breakpoints.get_bit(ip as usize % 16) {
+        true => Some(HaltReason::Breakpoint(ip)),
+        false => None,
+      }
+    });
+    match reason {
+      HaltReason::Breakpoint(ip) => Stop::Breakpoint(ip),
+      HaltReason::Fault(f) => Stop::Fault(MachineError::Fault(f)),
+      _ => Stop::Halted,
+    }
+  }
+  pub fn step_back(&mut self) -> Stop {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Debugger::step_back()"};
+    match self.machine.step_back() {
+      true => Stop::Step,
+      false => Stop::Start,
+    }
+  }
+  // steps back until IP is on a breakpoint or the start is reached
+  pub fn reverse_continue(&mut self) -> Stop {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Debugger::reverse_continue()"};
+    while self.machine.step_back() {
+      let ip = self.machine.get_reg().get_ip();
+      if self.is_breakpoint(ip) {
+        return Stop::Breakpoint(ip)
+      }
+    }
+    Stop::Start
+  }
+  // back to any earlier position, false if it is ahead
+  pub fn restore(&mut self, position: usize) -> bool {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Debugger::restore()"};
+    self.machine.rewind_to(position)
+  }
+}
@@ -0,0 +1,48 @@
+use crate::debugger::*;
+use crate::machine::*;
+use crate::instructions::Isa;
+
+// del; jnz 0; hlt counting AC down from 3
+fn countdown() -> Machine {
+  Machine::from([0x00, 0x03, 0xD3, 0x00, 0, 0, 0, 0, 0, 0])
+}
+
+#[test]
+fn breakpoints() {
+  let mut dbg = Debugger::new(countdown()).set_breakpoint(1).set_breakpoint(5);
+  assert_eq!{dbg.get_breakpoints(), vec![1, 5]};
+  assert_eq!{dbg.cont(), Stop::Breakpoint(1)};
+  assert_eq!{dbg.get_position(), 1};
+  assert_eq!{dbg.cont(), Stop::Breakpoint(1)};
+  assert_eq!{dbg.get_position(), 3};
+  let mut dbg = dbg.clear_breakpoint(1);
+  assert_eq!{dbg.cont(), Stop::Halted};
+  assert_eq!{dbg.step(), Stop::Halted};
+}
+#[test]
+fn reverse() {
+  let mut dbg = Debugger::new(countdown());
+  assert_eq!{dbg.cont(), Stop::Halted};
+  let end = dbg.get_position();
+  let mut dbg = dbg.set_breakpoint(1);
+  assert_eq!{dbg.reverse_continue(), Stop::Breakpoint(1)};
+  assert_eq!{dbg.get_position(), end - 2};
+  assert_eq!{dbg.step_back(), Stop::Step};
+  assert_eq!{dbg.cont(), Stop::Breakpoint(1)};
+  assert_eq!{dbg.get_position(), end - 2};
+  assert_eq!{dbg.reverse_continue(), Stop::Breakpoint(1)};
+  assert!{!dbg.restore(end)};
+  assert!{dbg.restore(0)};
+  assert_eq!{dbg.step_back(), Stop::Start};
+  assert_eq!{dbg.reverse_continue(), Stop::Start};
+  assert_eq!{MachineInner::from(dbg.get_machine()), MachineInner::from(&countdown())};
+}
+#[test]
+fn back_from_fault() {
+  // ret with an empty stack
+  let machine = Machine::from([0, 0, 0x01, 0, 0, 0, 0, 0, 0, 0]).set_isa(Isa::Extended);
+  let mut dbg = Debugger::new(machine);
+  assert!{matches!{dbg.cont(), Stop::Fault(_)}};
+  assert_eq!{dbg.step_back(), Stop::Step};
+  assert!{!dbg.get_machine().is_stopped()};
+}
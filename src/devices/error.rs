@@ -0,0 +1,25 @@
+use std::error::Error;
+use std::fmt;
+
+pub type DeviceResult<D> = Result<D, DeviceError>;
+#[derive(Clone,PartialEq)]
+pub enum DeviceError {
+  OutOfBounds(usize),
+  AddressInUse(usize),
+}
+impl fmt::Display for DeviceError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      DeviceError::OutOfBounds(a) =>
+        write!{f, "Address requested is out of bounds: {:X}", a},
+      DeviceError::AddressInUse(a) =>
+        write!{f, "Address already mapped to a device: {:X}", a},
+    }
+  }
+}
+impl fmt::Debug for DeviceError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Device Error: {}", self}
+  }
+}
+impl Error for DeviceError {}
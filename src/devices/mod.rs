@@ -0,0 +1,207 @@
+#[cfg(test)] mod test;
+mod error;
+
+use std::fmt;
+use std::cell::Cell;
+use bit_field::*;
+use crate::memory::MEMORY_BITS;
+use self::error::DeviceError as DevErr;
+use self::error::DeviceResult as DevRes;
+
+pub use self::error::DeviceError;
+pub use self::error::DeviceResult;
+pub const BUS_SIZE: usize = MEMORY_BITS / 4; // one slot per nibble address
+
+// A device answers every access to the nibble address it is attached at,
+// instruction fetch included.
+pub trait Device: fmt::Debug + Send {
+  fn read(&self) -> u8;
+  fn write(&mut self, value: u8);
+  // called once per executed instruction
+  fn tick(&mut self) {}
+  fn box_clone(&self) -> Box<dyn Device>;
+}
+impl Clone for Box<dyn Device> {
+  fn clone(&self) -> Self {
+    self.box_clone()
+  }
+}
+
+#[derive(Clone,Default)]
+pub struct Bus {
+  devices: Vec<(usize, Box<dyn Device>)>,
+}
+impl fmt::Debug for Bus {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut print = "Bus:".to_string();
+    for (addr, dev) in self.devices.iter() {
+      print.push_str(&format!{"\n\t{:#X}> {:?}", addr, dev});
+    }
+    write!{f, "{}", print}
+  }
+}
+impl Bus {
+  pub fn attach<D>(mut self, addr: usize, device: D) -> DevRes<Self>
+    where D: Device + 'static
+  {
+    if BUS_SIZE <= addr {
+      return Err(DevErr::OutOfBounds(addr))
+    }
+    if self.is_mapped(addr) {
+      return Err(DevErr::AddressInUse(addr))
+    }
+    self.devices.push((addr, Box::new(device)));
+    Ok(self)
+  }
+  pub fn is_empty(&self) -> bool {
+    self.devices.is_empty()
+  }
+  pub fn is_mapped(&self, addr: usize) -> bool {
+    self.devices.iter().any(|(a, _)| *a == addr)
+  }
+  pub fn get_device(&self, addr: usize) -> Option<&dyn Device> {
+    self.devices.iter()
+      .find(|(a, _)| *a == addr)
+      .map(|(_, d)| d.as_ref())
+  }
+  // None when nothing is mapped at addr
+  pub fn read(&self, addr: usize) -> Option<u8> {
+    self.get_device(addr).map(|d| d.read().get_bits(0..4))
+  }
+  // false when nothing is mapped at addr
+  pub fn write(&mut self, addr: usize, value: u8) -> bool {
+    match self.devices.iter_mut().find(|(a, _)| *a == addr) {
+      Some((_, dev)) => {
+        dev.write(value);
+        true
+      },
+      None => false,
+    }
+  }
+  pub fn tick(&mut self) {
+    for (_, dev) in self.devices.iter_mut() {
+      dev.tick();
+    }
+  }
+}
+
+// segments a-g as bits 0-6 for each hex digit
+pub const SEGMENTS: [u8; 16] = [
+  0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07,
+  0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71,
+];
+
+#[derive(Clone,Debug,Default)]
+pub struct SevenSegment {
+  digit: u8,
+}
+impl SevenSegment {
+  pub fn get_digit(&self) -> u8 {
+    self.digit
+  }
+  pub fn get_segments(&self) -> u8 {
+    SEGMENTS[self.digit as usize]
+  }
+}
+impl Device for SevenSegment {
+  fn read(&self) -> u8 {
+    self.digit
+  }
+  fn write(&mut self, value: u8) {
+    self.digit = value.get_bits(0..4);
+  }
+  fn box_clone(&self) -> Box<dyn Device> {
+    Box::new(self.clone())
+  }
+}
+
+// xorshift32, every read advances the sequence and writes reseed it
+#[derive(Clone,Debug)]
+pub struct Random {
+  state: Cell<u32>,
+}
+impl Random {
+  pub fn new(seed: u32) -> Self {
+    Random {
+      state: Cell::new(Self::fix_seed(seed)),
+    }
+  }
+  pub fn next_u32(&self) -> u32 {
+    let mut x = self.state.get();
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.state.set(x);
+    x
+  }
+  fn fix_seed(seed: u32) -> u32 {
+    match seed { // zero is a fixed point of xorshift
+      0 => 0x9E37_79B9,
+      s => s,
+    }
+  }
+}
+impl Device for Random {
+  fn read(&self) -> u8 {
+    self.next_u32().get_bits(0..4) as u8
+  }
+  fn write(&mut self, value: u8) {
+    let seed = self.state.get() ^ u32::from(value);
+    self.state.set(Self::fix_seed(seed));
+  }
+  fn box_clone(&self) -> Box<dyn Device> {
+    Box::new(self.clone())
+  }
+}
+
+// counts executed instructions, reads the low nibble and writes reset it
+#[derive(Clone,Debug,Default)]
+pub struct TickCounter {
+  ticks: usize,
+}
+impl TickCounter {
+  pub fn get_ticks(&self) -> usize {
+    self.ticks
+  }
+}
+impl Device for TickCounter {
+  fn read(&self) -> u8 {
+    (self.ticks % 16) as u8
+  }
+  fn write(&mut self, _value: u8) {
+    self.ticks = 0;
+  }
+  fn tick(&mut self) {
+    self.ticks += 1;
+  }
+  fn box_clone(&self) -> Box<dyn Device> {
+    Box::new(self.clone())
+  }
+}
+
+// collects written nibbles into bytes, high nibble first
+// reads return how many complete bytes were sent, saturating at 0xF
+#[derive(Clone,Debug,Default)]
+pub struct SerialConsole {
+  output: Vec<u8>,
+  high: Option<u8>,
+}
+impl SerialConsole {
+  pub fn get_output(&self) -> &[u8] {
+    &self.output
+  }
+}
+impl Device for SerialConsole {
+  fn read(&self) -> u8 {
+    self.output.len().min(crate::MAX_VALUE as usize) as u8
+  }
+  fn write(&mut self, value: u8) {
+    match self.high.take() {
+      None => self.high = Some(value.get_bits(0..4)),
+      Some(high) => self.output.push(high << 4 | value.get_bits(0..4)),
+    }
+  }
+  fn box_clone(&self) -> Box<dyn Device> {
+    Box::new(self.clone())
+  }
+}
@@ -0,0 +1,59 @@
+use crate::devices::*;
+
+#[test]
+fn attach() {
+  let bus = Bus::default().attach(0xE, SevenSegment::default()).unwrap();
+  assert!{bus.is_mapped(0xE)};
+  assert!{!bus.is_mapped(0xF)};
+  assert_eq!{bus.read(0xE), Some(0)};
+  assert_eq!{bus.read(0xF), None};
+}
+#[test]
+fn attach_invalid() {
+  let bus = Bus::default().attach(0xE, SevenSegment::default()).unwrap();
+  assert!{bus.clone().attach(0xE, TickCounter::default()).is_err()};
+  assert!{bus.attach(BUS_SIZE, TickCounter::default()).is_err()};
+}
+#[test]
+fn seven_segment() {
+  let mut bus = Bus::default().attach(0x0, SevenSegment::default()).unwrap();
+  assert!{bus.write(0x0, 0x8)};
+  assert_eq!{bus.read(0x0), Some(0x8)};
+  assert!{!bus.write(0x1, 0x8)};
+  let mut seg = SevenSegment::default();
+  seg.write(0x1);
+  assert_eq!{seg.get_segments(), 0x06};
+}
+#[test]
+fn random_is_seeded() {
+  let a = Random::new(42);
+  let b = Random::new(42);
+  let c = Random::new(43);
+  let a: Vec<u8> = (0..8).map(|_| a.read()).collect();
+  let b: Vec<u8> = (0..8).map(|_| b.read()).collect();
+  let c: Vec<u8> = (0..8).map(|_| c.read()).collect();
+  assert_eq!{a, b};
+  assert_ne!{a, c};
+  assert!{a.iter().all(|v| *v <= crate::MAX_VALUE)};
+}
+#[test]
+fn tick_counter() {
+  let mut bus = Bus::default().attach(0x3, TickCounter::default()).unwrap();
+  for _ in 0..17 {
+    bus.tick();
+  }
+  assert_eq!{bus.read(0x3), Some(1)};
+  bus.write(0x3, 0);
+  assert_eq!{bus.read(0x3), Some(0)};
+}
+#[test]
+fn serial_console() {
+  let mut con = SerialConsole::default();
+  con.write(0x4);
+  assert_eq!{con.read(), 0};
+  con.write(0x1);
+  con.write(0x4);
+  con.write(0x2);
+  assert_eq!{con.get_output(), b"AB"};
+  assert_eq!{con.read(), 2};
+}
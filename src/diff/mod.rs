@@ -0,0 +1,164 @@
+#[cfg(test)] mod test;
+
+use std::fmt;
+use std::fmt::Write;
+use crate::machine::Machine;
+use crate::registers::{Registers, RegisterInner};
+use crate::trace::{Trace, Step, PortOp};
+use crate::machine::{get_nibble, IMAGE_NIBBLES};
+
+pub const CONTEXT: usize = 3; // steps shown before a divergence
+const COLUMN: usize = 44;
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Field {
+  Ip,
+  Li,
+  Flags,
+  Ac,
+  Memory(u8), // nibble written differently
+  Input,
+  Output,
+  Fault,      // only one side faulted
+  Stopped,    // only one side is still running
+}
+impl fmt::Display for Field {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Field::Ip => write!{f, "IP"},
+      Field::Li => write!{f, "LI"},
+      Field::Flags => write!{f, "FR"},
+      Field::Ac => write!{f, "AC"},
+      Field::Memory(n) => write!{f, "[{:X}]", n},
+      Field::Input => write!{f, "input"},
+      Field::Output => write!{f, "output"},
+      Field::Fault => write!{f, "fault"},
+      Field::Stopped => write!{f, "stopped"},
+    }
+  }
+}
+
+// after step steps, 0 when the starting registers already differ
+#[derive(Clone,PartialEq,Debug)]
+pub struct Divergence {
+  pub step: usize,
+  pub fields: Vec<Field>,
+}
+
+fn regs_differ(left: RegisterInner, right: RegisterInner) -> Vec<Field> {
+  let (l, r) = (Registers::from(left), Registers::from(right));
+  let mut fields = Vec::new();
+  if l.get_ip() != r.get_ip() { fields.push(Field::Ip); }
+  if l.get_li() != r.get_li() { fields.push(Field::Li); }
+  if l.get_fr() != r.get_fr() { fields.push(Field::Flags); }
+  if l.get_ac() != r.get_ac() { fields.push(Field::Ac); }
+  fields
+}
+
+fn steps_differ(left: Option<&Step>, right: Option<&Step>) -> Vec<Field> {
+  let (l, r) = match (left, right) {
+    (Some(l), Some(r)) => (l, r),
+    (None, None) => return vec![],
+    _ => return vec![Field::Stopped],
+  };
+  let mut fields = regs_differ(l.regs, r.regs);
+  if l.write != r.write {
+    let mut addrs: Vec<u8> = l.write.iter().chain(r.write.iter()).map(|w| w.0).collect();
+    addrs.dedup();
+    fields.extend(addrs.into_iter().map(Field::Memory));
+  }
+  if l.port != r.port {
+    let input = |s: &Step| matches!{s.port, Some(PortOp::In(_))};
+    let output = |s: &Step| matches!{s.port, Some(PortOp::Out(_))};
+    if input(l) || input(r) { fields.push(Field::Input); }
+    if output(l) || output(r) { fields.push(Field::Output); }
+  }
+  if l.fault != r.fault {
+    fields.push(Field::Fault);
+  }
+  fields
+}
+
+// Two machines run in lockstep until their registers, memory writes or port
+// traffic first differ. Memory that differs from the start only counts once
+// a step writes it differently.
+#[derive(Clone,Debug)]
+pub struct Diff {
+  left: Trace,
+  right: Trace,
+  divergence: Option<Divergence>,
+}
+impl Diff {
+  pub fn new(left: &Machine, right: &Machine) -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Diff::new()"};
+    let mut left = left.clone().set_recording(true);
+    let mut right = right.clone().set_recording(true);
+    let fields = regs_differ(left.get_reg().clone().into(), right.get_reg().clone().into());
+    let mut divergence = match fields.is_empty() {
+      true => None,
+      false => Some(Divergence { step: 0, fields }),
+    };
+    let mut step = 0;
+    while divergence.is_none() && !(left.is_stopped() && right.is_stopped()) {
+      let (l, r) = (!left.is_stopped(), !right.is_stopped());
+      // faults are recorded in the trace
+      if l { let _ = left.step(); }
+      if r { let _ = right.step(); }
+      step += 1;
+      let last = |m: &Machine, ran: bool| match ran {
+        true => m.get_trace().and_then(|t| t.get_steps().last()).cloned(),
+        false => None,
+      };
+      let fields = steps_differ(last(&left, l).as_ref(), last(&right, r).as_ref());
+      if !fields.is_empty() {
+        divergence = Some(Divergence { step, fields });
+      }
+    }
+    Diff {
+      left: left.take_trace().unwrap(),
+      right: right.take_trace().unwrap(),
+      divergence,
+    }
+  }
+  pub fn get_left(&self) -> &Trace {
+    &self.left
+  }
+  pub fn get_right(&self) -> &Trace {
+    &self.right
+  }
+  pub fn get_divergence(&self) -> Option<&Divergence> {
+    self.divergence.as_ref()
+  }
+  // steps leading up to the divergence side by side, the diverging step marked
+  pub fn report(&self, context: usize) -> String {
+    let mut out = String::new();
+    let (last, end) = match self.divergence {
+      Some(ref d) => {
+        let fields: Vec<String> = d.fields.iter().map(|f| f.to_string()).collect();
+        let _ = writeln!{out, "diverged after {} steps: {}", d.step, fields.join(", ")};
+        (d.step, d.step)
+      },
+      None => {
+        let _ = writeln!{out, "no divergence in {} steps", self.left.len().max(self.right.len())};
+        (self.left.len().max(self.right.len()), 0)
+      },
+    };
+    let _ = writeln!{out, "      {:w$}  right", "left", w = COLUMN};
+    let line = |trace: &Trace, n: usize| match trace.get_steps().get(n) {
+      Some(step) => step.to_string(),
+      None => "-".to_string(),
+    };
+    for n in last.saturating_sub(context + 1)..last {
+      let mark = if n + 1 == end { ">" } else { " " };
+      let _ = writeln!{out, "{}{:4}  {:w$}  {}", mark, n,
+        line(&self.left, n), line(&self.right, n), w = COLUMN};
+    }
+    let image = |trace: &Trace| -> String {
+      let state = trace.state_at(last);
+      (0..IMAGE_NIBBLES).map(|i| format!{"{:X}", get_nibble(&state, i)}).collect()
+    };
+    let _ = writeln!{out, "image {:w$}  {}", image(&self.left), image(&self.right), w = COLUMN};
+    out
+  }
+}
@@ -0,0 +1,69 @@
+use crate::diff::*;
+use crate::machine::*;
+use crate::level::{Level, Ruleset, Endian};
+use crate::instructions::Isa;
+
+fn image(mem: [u8; 8]) -> MachineInner {
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  image[2..].copy_from_slice(&mem);
+  image
+}
+
+#[test]
+fn identical() {
+  // lda 7; sta 6; hlt
+  let m = Machine::from(image([0x47, 0x56, 0, 0x03, 0, 0, 0, 0]));
+  let diff = Diff::new(&m, &m);
+  assert_eq!{diff.get_divergence(), None};
+  assert_eq!{diff.get_left().len(), 3};
+  assert!{diff.report(CONTEXT).starts_with("no divergence in 3 steps")};
+}
+#[test]
+fn data_difference() {
+  // lda 7; sta 6; hlt -- loading 3 or 4
+  let left = Machine::from(image([0x47, 0x56, 0, 0x03, 0, 0, 0, 0]));
+  let right = Machine::from(image([0x47, 0x56, 0, 0x04, 0, 0, 0, 0]));
+  let diff = Diff::new(&left, &right);
+  assert_eq!{diff.get_divergence(), Some(&Divergence { step: 1, fields: vec![Field::Ac] })};
+  // lockstep stops at the divergence
+  assert_eq!{diff.get_right().len(), 1};
+  let report = diff.report(CONTEXT);
+  assert!{report.starts_with("diverged after 1 steps: AC\n")};
+  assert!{report.contains(">   0  0: 47  IP=2 LI=0 FR=0 AC=3")};
+  assert!{report.contains("0: 47  IP=2 LI=0 FR=0 AC=4\n")};
+}
+#[test]
+fn endianness() {
+  // scf; hlt big endian, hlt; scf little endian
+  let img = image([0xC0, 0, 0, 0, 0, 0, 0, 0]);
+  let big = Ruleset { level: Level::Two, ..Default::default() };
+  let little = Ruleset { endian: Endian::Little, ..big };
+  let diff = Diff::new(&big.load(img, 0), &little.load(img, 0));
+  let div = diff.get_divergence().unwrap();
+  assert_eq!{div.step, 1};
+  assert_eq!{div.fields, vec![Field::Ip, Field::Flags]};
+}
+#[test]
+fn isa_fault() {
+  // hlt in the standard set, ret on an empty stack when extended
+  let img = image([0x01, 0, 0, 0, 0, 0, 0, 0]);
+  let std = Ruleset { level: Level::Two, ..Default::default() };
+  let ext = Ruleset { isa: Isa::Extended, ..std };
+  let diff = Diff::new(&std.load(img, 0), &ext.load(img, 0));
+  assert_eq!{diff.get_divergence().map(|d| d.fields.clone()), Some(vec![Field::Fault])};
+  assert!{diff.report(CONTEXT).contains("fault")};
+}
+#[test]
+fn memory_write() {
+  // sta 6 or sta 5 with the same AC
+  let left = Machine::from(image([0x56, 0, 0, 0, 0, 0, 0, 0]));
+  let right = Machine::from(image([0x55, 0, 0, 0, 0, 0, 0, 0]));
+  let diff = Diff::new(&left, &right);
+  // storing 0 over 0 changes nothing
+  assert_eq!{diff.get_divergence(), None};
+  let left = left.set_reg([0x00, 0x02]);
+  let right = right.set_reg([0x00, 0x02]);
+  let diff = Diff::new(&left, &right);
+  let fields = vec![Field::Memory(6), Field::Memory(5)];
+  assert_eq!{diff.get_divergence(), Some(&Divergence { step: 1, fields })};
+}
@@ -0,0 +1,75 @@
+#[cfg(test)] mod test;
+
+use serde::{Serialize, Deserialize};
+use crate::instructions::InstructionError;
+use crate::memory::MemoryError;
+use crate::registers::RegisterError;
+use crate::stack::StackError;
+use crate::interrupts::InterruptError;
+use crate::ports::PortError;
+
+pub const FAULT_KINDS: usize = 6;
+
+// Groups of instruction errors a policy can treat differently
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum FaultKind {
+  InvalidOpcode,  // unused extended opcodes, GET/PUT below level 3
+  OutOfBounds,
+  ValueTooLarge,  // from any component
+  Stack,          // overflow or underflow
+  PortUnderflow,
+  PortOverflow,
+}
+impl FaultKind {
+  pub const ALL: [FaultKind; FAULT_KINDS] = [
+    FaultKind::InvalidOpcode,
+    FaultKind::OutOfBounds,
+    FaultKind::ValueTooLarge,
+    FaultKind::Stack,
+    FaultKind::PortUnderflow,
+    FaultKind::PortOverflow,
+  ];
+  pub fn of(err: &InstructionError) -> FaultKind {
+    match *err {
+      InstructionError::InvalidInstruction(_) => FaultKind::InvalidOpcode,
+      InstructionError::Memory(MemoryError::OutOfBounds(_)) => FaultKind::OutOfBounds,
+      InstructionError::Register(RegisterError::ValueTooLarge(_)) |
+      InstructionError::Memory(MemoryError::ValueTooLarge(_)) |
+      InstructionError::Stack(StackError::ValueTooLarge(_)) |
+      InstructionError::Interrupt(InterruptError::ValueTooLarge(_)) |
+      InstructionError::Port(PortError::ValueTooLarge(_)) => FaultKind::ValueTooLarge,
+      InstructionError::Stack(_) => FaultKind::Stack,
+      InstructionError::Port(PortError::Underflow) => FaultKind::PortUnderflow,
+      InstructionError::Port(PortError::Overflow(_)) => FaultKind::PortOverflow,
+    }
+  }
+}
+
+// What a step does once an instruction faults
+#[derive(Clone,Copy,PartialEq,Debug,Default,Serialize,Deserialize)]
+pub enum FaultAction {
+  Halt,     // set HF and finish the step quietly
+  #[default]
+  Raise,    // set HF and return the fault
+  Nop,      // skip past the instruction
+  Trap(u8), // push the next IP and jump to the handler
+}
+
+// Action per fault kind, raising everything by default
+#[derive(Clone,Copy,PartialEq,Debug,Default,Serialize,Deserialize)]
+pub struct FaultPolicy {
+  actions: [FaultAction; FAULT_KINDS],
+}
+impl FaultPolicy {
+  pub fn get(&self, kind: FaultKind) -> FaultAction {
+    self.actions[kind as usize]
+  }
+  pub fn set(mut self, kind: FaultKind, action: FaultAction) -> Self {
+    self.actions[kind as usize] = action;
+    self
+  }
+  // same action for every kind
+  pub fn all(action: FaultAction) -> Self {
+    FaultPolicy { actions: [action; FAULT_KINDS] }
+  }
+}
@@ -0,0 +1,50 @@
+use crate::faults::*;
+use crate::machine::*;
+use crate::instructions::{Isa, InstructionError};
+use crate::stack::StackError;
+use crate::ports::PortError;
+
+// 0x0: unused extended opcode 9; 0x2: hlt; 0x4: hlt
+const INVALID: MachineInner = [0, 0, 0x09, 0x00, 0x00, 0, 0, 0, 0, 0];
+
+fn run(policy: FaultPolicy) -> (MachineResult<usize>, Machine) {
+  let mut machine = Machine::from(INVALID).set_isa(Isa::Extended).set_faults(policy);
+  (machine.exec(), machine)
+}
+
+#[test]
+fn kinds() {
+  assert_eq!{FaultKind::of(&InstructionError::InvalidInstruction(9)), FaultKind::InvalidOpcode};
+  assert_eq!{FaultKind::of(&StackError::Underflow.into()), FaultKind::Stack};
+  assert_eq!{FaultKind::of(&StackError::ValueTooLarge(0x10).into()), FaultKind::ValueTooLarge};
+  assert_eq!{FaultKind::of(&PortError::Overflow(1).into()), FaultKind::PortOverflow};
+  let policy = FaultPolicy::default().set(FaultKind::Stack, FaultAction::Nop);
+  assert_eq!{policy.get(FaultKind::Stack), FaultAction::Nop};
+  assert_eq!{policy.get(FaultKind::PortUnderflow), FaultAction::Raise};
+}
+#[test]
+fn raise_and_halt() {
+  let (res, machine) = run(FaultPolicy::default());
+  assert!{matches!{res, Err(MachineError::Fault(_))}};
+  assert_eq!{(machine.get_cc(), machine.get_reg().get_hf()), (0, true)};
+  let (res, machine) = run(FaultPolicy::all(FaultAction::Halt));
+  assert_eq!{res, Ok(1)};
+  assert!{machine.get_reg().get_hf()};
+  assert_eq!{machine.get_error(), None};
+}
+#[test]
+fn nop_and_trap() {
+  let (res, machine) = run(FaultPolicy::all(FaultAction::Nop));
+  assert_eq!{res, Ok(2)};
+  assert_eq!{machine.get_reg().get_ip(), 0x2};
+  let (res, machine) = run(FaultPolicy::default().set(FaultKind::InvalidOpcode, FaultAction::Trap(4)));
+  assert_eq!{res, Ok(2)};
+  assert_eq!{machine.get_reg().get_ip(), 0x4};
+  assert_eq!{machine.get_stack().get_all(), &[0x2]};
+}
+#[test]
+fn level_one_halts() {
+  let machine = crate::level::Level::One.load(INVALID, 0).set_isa(Isa::Extended);
+  assert_eq!{machine.get_faults().get(FaultKind::InvalidOpcode), FaultAction::Halt};
+  assert_eq!{machine.get_faults().get(FaultKind::Stack), FaultAction::Raise};
+}
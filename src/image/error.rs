@@ -0,0 +1,43 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+pub type ImageResult<T> = Result<T, ImageError>;
+#[derive(Clone,PartialEq)]
+pub enum ImageError {
+  Length(usize),        // bytes for raw, nibbles for text forms
+  BadDigit(char),
+  BadLine(usize),       // line that does not parse, counted from 1
+  Checksum(usize),      // hex record whose checksum is wrong
+  UnknownFormat(String),
+  Io(String),
+}
+impl fmt::Display for ImageError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      ImageError::Length(l) =>
+        write!{f, "Wrong image length: {}", l},
+      ImageError::BadDigit(c) =>
+        write!{f, "Invalid hex digit: {:?}", c},
+      ImageError::BadLine(l) =>
+        write!{f, "Cannot parse line: {}", l},
+      ImageError::Checksum(l) =>
+        write!{f, "Bad checksum on line: {}", l},
+      ImageError::UnknownFormat(ref s) =>
+        write!{f, "Unknown image format: {}", s},
+      ImageError::Io(ref e) =>
+        write!{f, "{}", e},
+    }
+  }
+}
+impl fmt::Debug for ImageError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Image Error: {}", self}
+  }
+}
+impl Error for ImageError {}
+impl From<io::Error> for ImageError {
+  fn from(err: io::Error) -> ImageError {
+    ImageError::Io(err.to_string())
+  }
+}
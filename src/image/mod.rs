@@ -0,0 +1,223 @@
+#[cfg(test)] mod test;
+mod error;
+
+use std::io::Read;
+use std::str::FromStr;
+use crate::machine::{MachineInner, MACHINE_SIZE};
+use crate::machine::{get_nibble, set_nibble, IMAGE_NIBBLES, MEMORY_OFFSET};
+use self::error::ImageError as ImgErr;
+use self::error::ImageResult as ImgRes;
+
+pub use self::error::ImageError;
+pub use self::error::ImageResult;
+pub const REGISTER_NAMES: [&str; MEMORY_OFFSET] = ["IP", "LI", "FR", "AC"];
+const GRID_WIDTH: usize = 8; // nibbles per memory row
+
+// Ways an image is stored outside the machine
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Format {
+  Raw,       // the 10 bytes as sent to the server
+  Hex,       // 20 hex digits, whitespace ignored
+  IntelHex,  // ":LLAAAATT..CC" data records and an end record
+  Annotated, // [registers] and [memory] sections, '#' comments
+}
+impl FromStr for Format {
+  type Err = ImgErr;
+  fn from_str(name: &str) -> ImgRes<Format> {
+    match name {
+      "raw" => Ok(Format::Raw),
+      "hex" => Ok(Format::Hex),
+      "ihex" => Ok(Format::IntelHex),
+      "text" => Ok(Format::Annotated),
+      _ => Err(ImgErr::UnknownFormat(name.to_string())),
+    }
+  }
+}
+impl Format {
+  // best guess at the format of a file, parse still validates it
+  pub fn detect(bytes: &[u8]) -> Format {
+    // text forms are longer than the image itself
+    if bytes.len() == MACHINE_SIZE {
+      return Format::Raw
+    }
+    let text = match std::str::from_utf8(bytes) {
+      Ok(text) => text,
+      Err(_) => return Format::Raw,
+    };
+    if text.trim_start().starts_with(':') {
+      Format::IntelHex
+    } else if text.chars().any(|c| "[]=#:".contains(c)) {
+      Format::Annotated
+    } else {
+      Format::Hex
+    }
+  }
+  pub fn parse(&self, bytes: &[u8]) -> ImgRes<MachineInner> {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Format::parse({:?})", self};
+    if *self == Format::Raw {
+      if bytes.len() != MACHINE_SIZE {
+        return Err(ImgErr::Length(bytes.len()))
+      }
+      let mut image: MachineInner = [0; MACHINE_SIZE];
+      image.copy_from_slice(bytes);
+      return Ok(image)
+    }
+    let text = String::from_utf8_lossy(bytes);
+    match *self {
+      Format::Hex => parse_hex(&text),
+      Format::IntelHex => parse_intel(&text),
+      _ => parse_annotated(&text),
+    }
+  }
+  pub fn render(&self, image: &MachineInner) -> Vec<u8> {
+    match *self {
+      Format::Raw => image.to_vec(),
+      Format::Hex => {
+        let mut text = String::new();
+        for (i, c) in digits(image, 0..IMAGE_NIBBLES).chars().enumerate() {
+          if i > 0 && i % 4 == 0 { text.push(' '); }
+          text.push(c);
+        }
+        text.push('\n');
+        text.into_bytes()
+      },
+      Format::IntelHex => {
+        let mut record = vec![MACHINE_SIZE as u8, 0, 0, 0];
+        record.extend_from_slice(image);
+        let sum = record.iter().fold(0u8, |s, b| s.wrapping_add(*b));
+        record.push(sum.wrapping_neg());
+        let data: String = record.iter().map(|b| format!{"{:02X}", b}).collect();
+        format!{":{}\n:00000001FF\n", data}.into_bytes()
+      },
+      Format::Annotated => {
+        let mut text = "# TinyMachine image\n[registers]\n".to_string();
+        for (i, name) in REGISTER_NAMES.iter().enumerate() {
+          text.push_str(&format!{"{} = {:X}\n", name, get_nibble(image, i)});
+        }
+        text.push_str("\n[memory]\n");
+        for row in (0..IMAGE_NIBBLES - MEMORY_OFFSET).step_by(GRID_WIDTH) {
+          let start = MEMORY_OFFSET + row;
+          let cells: Vec<String> = digits(image, start..start + GRID_WIDTH)
+            .chars().map(|c| c.to_string()).collect();
+          text.push_str(&format!{"{:X}: {}\n", row, cells.join(" ")});
+        }
+        text.into_bytes()
+      },
+    }
+  }
+}
+
+// detects the format and parses with it
+pub fn load_image(bytes: &[u8]) -> ImgRes<MachineInner> {
+  Format::detect(bytes).parse(bytes)
+}
+
+pub fn read_image<R: Read>(reader: &mut R) -> ImgRes<MachineInner> {
+  let mut bytes = Vec::new();
+  reader.read_to_end(&mut bytes)?;
+  load_image(&bytes)
+}
+
+fn digits(image: &MachineInner, range: std::ops::Range<usize>) -> String {
+  range.map(|i| format!{"{:X}", get_nibble(image, i)}).collect()
+}
+
+fn nibble(c: char) -> ImgRes<u8> {
+  c.to_digit(16).map(|v| v as u8).ok_or(ImgErr::BadDigit(c))
+}
+
+fn parse_hex(text: &str) -> ImgRes<MachineInner> {
+  let nibbles: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+  if nibbles.len() != IMAGE_NIBBLES {
+    return Err(ImgErr::Length(nibbles.len()))
+  }
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  for (idx, c) in nibbles.into_iter().enumerate() {
+    set_nibble(&mut image, idx, nibble(c)?);
+  }
+  Ok(image)
+}
+
+// every image byte has to come from a data record
+fn parse_intel(text: &str) -> ImgRes<MachineInner> {
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  let mut seen = [false; MACHINE_SIZE];
+  for (n, line) in text.lines().enumerate().map(|(n, l)| (n + 1, l.trim())) {
+    if line.is_empty() {
+      continue;
+    }
+    let hex = line.strip_prefix(':').ok_or(ImgErr::BadLine(n))?;
+    if !hex.is_ascii() || hex.len() % 2 != 0 || hex.len() < 10 {
+      return Err(ImgErr::BadLine(n))
+    }
+    let record = (0..hex.len()).step_by(2)
+      .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| ImgErr::BadLine(n)))
+      .collect::<ImgRes<Vec<u8>>>()?;
+    let count = record[0] as usize;
+    if record.len() != count + 5 {
+      return Err(ImgErr::BadLine(n))
+    }
+    if record.iter().fold(0u8, |s, b| s.wrapping_add(*b)) != 0 {
+      return Err(ImgErr::Checksum(n))
+    }
+    let addr = (record[1] as usize) << 8 | record[2] as usize;
+    match record[3] {
+      0x00 if addr + count <= MACHINE_SIZE => {
+        image[addr..addr + count].copy_from_slice(&record[4..4 + count]);
+        seen[addr..addr + count].iter_mut().for_each(|s| *s = true);
+      },
+      0x01 => break,
+      _ => return Err(ImgErr::BadLine(n)),
+    };
+  }
+  match seen.iter().filter(|s| **s).count() {
+    MACHINE_SIZE => Ok(image),
+    count => Err(ImgErr::Length(count)),
+  }
+}
+
+// registers and nibbles left out are zero
+fn parse_annotated(text: &str) -> ImgRes<MachineInner> {
+  #[derive(PartialEq)]
+  enum Section { None, Registers, Memory }
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  let mut section = Section::None;
+  for (n, line) in text.lines().enumerate().map(|(n, l)| (n + 1, l)) {
+    let line = line.split('#').next().unwrap().trim();
+    if line.is_empty() {
+      continue;
+    }
+    match line.to_ascii_lowercase().as_str() {
+      "[registers]" => { section = Section::Registers; continue; },
+      "[memory]" => { section = Section::Memory; continue; },
+      _ => {},
+    };
+    match section {
+      Section::Registers => {
+        let (name, value) = line.split_once('=').ok_or(ImgErr::BadLine(n))?;
+        let idx = REGISTER_NAMES.iter()
+          .position(|r| r.eq_ignore_ascii_case(name.trim()))
+          .ok_or(ImgErr::BadLine(n))?;
+        let mut value = value.trim().chars();
+        match (value.next(), value.next()) {
+          (Some(c), None) => set_nibble(&mut image, idx, nibble(c)?),
+          _ => return Err(ImgErr::BadLine(n)),
+        };
+      },
+      Section::Memory => {
+        let (addr, cells) = line.split_once(':').ok_or(ImgErr::BadLine(n))?;
+        let addr = usize::from_str_radix(addr.trim(), 16).map_err(|_| ImgErr::BadLine(n))?;
+        let cells: Vec<char> = cells.chars().filter(|c| !c.is_whitespace()).collect();
+        if addr >= IMAGE_NIBBLES - MEMORY_OFFSET || MEMORY_OFFSET + addr + cells.len() > IMAGE_NIBBLES {
+          return Err(ImgErr::BadLine(n))
+        }
+        for (i, c) in cells.into_iter().enumerate() {
+          set_nibble(&mut image, MEMORY_OFFSET + addr + i, nibble(c)?);
+        }
+      },
+      Section::None => return Err(ImgErr::BadLine(n)),
+    };
+  }
+  Ok(image)
+}
@@ -0,0 +1,56 @@
+use crate::image::*;
+use crate::machine::MachineInner;
+
+// del; jnz 0; hlt counting AC down from 3
+const COUNTDOWN: MachineInner = [0x00, 0x03, 0xD3, 0x00, 0, 0, 0, 0, 0, 0x5A];
+const FORMATS: [Format; 4] = [Format::Raw, Format::Hex, Format::IntelHex, Format::Annotated];
+
+#[test]
+fn round_trip() {
+  for format in FORMATS.iter() {
+    let bytes = format.render(&COUNTDOWN);
+    assert_eq!{Format::detect(&bytes), *format};
+    assert_eq!{format.parse(&bytes), Ok(COUNTDOWN)};
+    assert_eq!{load_image(&bytes), Ok(COUNTDOWN)};
+  }
+  assert_eq!{Format::Hex.render(&COUNTDOWN), b"0003 D300 0000 0000 005A\n".to_vec()};
+  assert_eq!{Format::IntelHex.render(&COUNTDOWN),
+    b":0A0000000003D30000000000005AC6\n:00000001FF\n".to_vec()};
+  let text = String::from_utf8(Format::Annotated.render(&COUNTDOWN)).unwrap();
+  assert!{text.contains("[registers]\nIP = 0\nLI = 0\nFR = 0\nAC = 3\n")};
+  assert!{text.contains("[memory]\n0: D 3 0 0 0 0 0 0\n8: 0 0 0 0 0 0 5 A\n")};
+}
+#[test]
+fn annotated_by_hand() {
+  let text = "
+    # counts AC down from 3
+    [Registers]
+    ac = 3   # the rest start at zero
+    [memory]
+    0: D300    # del; jnz 0
+    E: 5A
+  ";
+  assert_eq!{load_image(text.as_bytes()), Ok(COUNTDOWN)};
+  assert_eq!{Format::Annotated.parse(b"AC = 3"), Err(ImageError::BadLine(1))};
+  assert_eq!{Format::Annotated.parse(b"[registers]\nSP = 1"), Err(ImageError::BadLine(2))};
+  assert_eq!{Format::Annotated.parse(b"[memory]\nC: 12345"), Err(ImageError::BadLine(2))};
+  assert_eq!{Format::Annotated.parse(b"[memory]\n10: 1"), Err(ImageError::BadLine(2))};
+  assert_eq!{load_image(b"[memory]\nFFFFFFFFFFFFFFFF: 1\n"), Err(ImageError::BadLine(2))};
+  assert_eq!{Format::Annotated.parse(b"[memory]\n0: G"), Err(ImageError::BadDigit('G'))};
+}
+#[test]
+fn bad_images() {
+  assert_eq!{Format::Raw.parse(&[0; 9]), Err(ImageError::Length(9))};
+  assert_eq!{Format::Hex.parse(b"0003 D300"), Err(ImageError::Length(8))};
+  assert_eq!{Format::Hex.parse(b"0003 D300 0000 0000 00?A"), Err(ImageError::BadDigit('?'))};
+  // checksum off by one
+  let bad = b":0A0000000003D30000000000005AC7\n";
+  assert_eq!{Format::IntelHex.parse(bad), Err(ImageError::Checksum(1))};
+  // only the first five bytes
+  assert_eq!{Format::IntelHex.parse(b":050000000003D3000025\n:00000001FF"), Err(ImageError::Length(5))};
+  assert_eq!{Format::IntelHex.parse(b"0A00"), Err(ImageError::BadLine(1))};
+  // found by fuzzing, multibyte characters once lossily decoded
+  assert_eq!{Format::IntelHex.parse(b":\xff\xff\xff\xff"), Err(ImageError::BadLine(1))};
+  assert_eq!{"elf".parse::<Format>(), Err(ImageError::UnknownFormat("elf".to_string()))};
+  assert_eq!{"ihex".parse::<Format>(), Ok(Format::IntelHex)};
+}
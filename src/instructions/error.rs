@@ -1,47 +1,89 @@
+use std::error::Error;
 use std::fmt;
+use serde::{Serialize, Deserialize};
 
 use crate::memory::MemoryError;
 use crate::registers::RegisterError;
+use crate::stack::StackError;
+use crate::interrupts::InterruptError;
+use crate::ports::PortError;
 
 pub type InstructionResult<I> = Result<I, InstructionError>;
+// Instruction's own faults, or the component that refused it as the source
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
 pub enum InstructionError {
-  JumpNotTaken,
   InvalidInstruction(u8),
-  OutOfBounds(usize),
-  ValueTooLarge(u8),
-  MathError(usize),
+  Register(RegisterError),
+  Memory(MemoryError),
+  Stack(StackError),
+  Interrupt(InterruptError),
+  Port(PortError),
+}
+impl fmt::Display for InstructionError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      InstructionError::InvalidInstruction(v) =>
+        write!{f, "Invalid instruction: {:x}", v},
+      InstructionError::Register(_) =>
+        write!{f, "Register fault"},
+      InstructionError::Memory(_) =>
+        write!{f, "Memory fault"},
+      InstructionError::Stack(_) =>
+        write!{f, "Stack fault"},
+      InstructionError::Interrupt(_) =>
+        write!{f, "Interrupt fault"},
+      InstructionError::Port(_) =>
+        write!{f, "Port fault"},
+    }
+  }
 }
 impl fmt::Debug for InstructionError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     let _ = write!{f, "Instruction Error: "};
     match *self {
-      InstructionError::JumpNotTaken =>
-        write!(f, "Jump not taken"),
-      InstructionError::InvalidInstruction(v) =>
-        write!(f, "Invalid instruction: {:x}", v),
-      InstructionError::OutOfBounds(v) =>
-        write!{f, "Out of bounds operation: {:x}", v},
-      InstructionError::ValueTooLarge(v) =>
-        write!{f, "Value too large for register: {}", v},
-      InstructionError::MathError(v) =>
-        write!{f, "Math error from: {}", v},
+      InstructionError::Register(ref e) => write!{f, "{:?}", e},
+      InstructionError::Memory(ref e) => write!{f, "{:?}", e},
+      InstructionError::Stack(ref e) => write!{f, "{:?}", e},
+      InstructionError::Interrupt(ref e) => write!{f, "{:?}", e},
+      InstructionError::Port(ref e) => write!{f, "{:?}", e},
+      _ => write!{f, "{}", self},
+    }
+  }
+}
+impl Error for InstructionError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match *self {
+      InstructionError::Register(ref e) => Some(e),
+      InstructionError::Memory(ref e) => Some(e),
+      InstructionError::Stack(ref e) => Some(e),
+      InstructionError::Interrupt(ref e) => Some(e),
+      InstructionError::Port(ref e) => Some(e),
+      _ => None,
     }
   }
 }
 impl From<RegisterError> for InstructionError {
   fn from(err: RegisterError) -> Self {
-    match err {
-      RegisterError::ValueTooLarge(v) => InstructionError::ValueTooLarge(v),
-      RegisterError::MathError(v) => InstructionError::MathError(v),
-    }
+    InstructionError::Register(err)
   }
 }
 impl From<MemoryError> for InstructionError {
   fn from(err: MemoryError) -> Self {
-    match err {
-      MemoryError::OutOfBounds(v) => InstructionError::OutOfBounds(v),
-      MemoryError::ValueTooLarge(v) => InstructionError::ValueTooLarge(v),
-      MemoryError::MathError(v) => InstructionError::MathError(v),
-    }
+    InstructionError::Memory(err)
+  }
+}
+impl From<StackError> for InstructionError {
+  fn from(err: StackError) -> Self {
+    InstructionError::Stack(err)
+  }
+}
+impl From<InterruptError> for InstructionError {
+  fn from(err: InterruptError) -> Self {
+    InstructionError::Interrupt(err)
+  }
+}
+impl From<PortError> for InstructionError {
+  fn from(err: PortError) -> Self {
+    InstructionError::Port(err)
   }
 }
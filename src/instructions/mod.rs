@@ -1,9 +1,11 @@
 #[cfg(test)] mod test;
+#[cfg(test)] mod model;
 mod error;
 
 use std::fmt;
 use std::ops::Range;
 use bit_field::*;
+use serde::{Serialize, Deserialize};
 //use registers::*;
 //use memory::*;
 use crate::machine::Machine;
@@ -15,9 +17,111 @@ pub use self::error::InstructionResult;
 pub const VALUE_BITS: Range<usize> = 0..3;  // slow so we don't shift
 pub const OPCODE_BITS: Range<usize> = 4..8; // high bits u8::from()
 pub const ADDR_BITS: Range<usize> = 0..3;   // low bits u8::from()
+// base cycle cost per opcode, one per nibble fetched plus one per memory access
+pub const CYCLE_TABLE: [usize; 16] = [
+  1, // HLT
+  2, // JMP
+  2, // JZE
+  2, // JNZ
+  3, // LDA
+  3, // STA
+  2, // GET
+  2, // PUT
+  1, // ROL
+  1, // ROR
+  3, // ADC
+  1, // CCF
+  1, // SCF
+  1, // DEL
+  3, // LDL
+  1, // FLA
+];
+// cycle cost of extended ops, indexed by the nibble following the 0x0 prefix
+pub const EXT_CYCLE_TABLE: [usize; 9] = [
+  1, // HLT
+  2, // RET
+  3, // PUSH
+  3, // POP
+  4, // CALL
+  2, // EI
+  2, // DI
+  2, // RTI
+  2, // TMR
+];
+pub const JUMP_TAKEN_CYCLES: usize = 1; // extra cost to refill fetch on taken jumps
+
+// Extended repurposes opcode 0x0 as a prefix selecting the stack operations
+#[derive(Clone,Copy,PartialEq,Debug,Default,Serialize,Deserialize)]
+pub enum Isa {
+  #[default]
+  Standard,
+  Extended,
+}
+
+// What a call did when it did not fault, a jump not taken continues
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Outcome {
+  Continue,     // IP moved past the instruction
+  Jumped(u8),   // IP set to an address
+  Halted,       // HLT set HF
+  Input(u8),    // GET read a value into AC
+  Output(u8),   // PUT wrote AC out
+}
+
+// Every byte decoded once for an ISA, CALL entries have address 0
+#[derive(Clone)]
+pub struct DecodeTable {
+  isa: Isa,
+  insts: [Instruction; 256],
+}
+impl From<Isa> for DecodeTable {
+  fn from(isa: Isa) -> Self {
+    DecodeTable {
+      isa,
+      insts: std::array::from_fn(|b| Instruction::decode(b as u8, 0, isa)),
+    }
+  }
+}
+impl DecodeTable {
+  pub fn get_isa(&self) -> Isa {
+    self.isa
+  }
+  pub fn get(&self, byte: u8) -> &Instruction {
+    &self.insts[byte as usize]
+  }
+}
+
+// Instruction decoded at each IP, forgotten once its nibbles are written
+#[derive(Clone,Default)]
+pub struct InstCache {
+  insts: [Option<Instruction>; 16],
+}
+impl InstCache {
+  pub fn get(&self, ip: u8) -> Option<&Instruction> {
+    self.insts[ip as usize % 16].as_ref()
+  }
+  pub fn insert(&mut self, ip: u8, inst: Instruction) {
+    self.insts[ip as usize % 16] = Some(inst);
+  }
+  // an instruction reads its IP and up to two nibbles after it
+  pub fn invalidate(&mut self, nibble: u8) {
+    for back in 0..3 {
+      self.insts[(nibble as usize + 16 - back) % 16] = None;
+    }
+  }
+  pub fn clear(&mut self) {
+    *self = InstCache::default();
+  }
+  pub fn len(&self) -> usize {
+    self.insts.iter().filter(|i| i.is_some()).count()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
 
 // all arguments are really u4 sized... thanks rust?
-#[derive(Clone,PartialEq)]
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
 pub enum Instruction {
   HLT,         // 0x00 Halt
   JMP(u8),     // 0x01 Unconditional Jump
@@ -35,28 +139,30 @@ pub enum Instruction {
   DEL,         // 0x0D Decrement Loop Index
   LDL(u8),     // 0x0E Load LI from addr
   FLA,         // 0x0F Negate AC
+  RET,         // 0x01 Return from subroutine (extended)
+  PUSH,        // 0x02 Push AC to stack (extended)
+  POP,         // 0x03 Pop AC from stack (extended)
+  CALL(u8),    // 0x04 Call subroutine at addr (extended)
+  EI,          // 0x05 Enable interrupts (extended)
+  DI,          // 0x06 Disable interrupts (extended)
+  RTI,         // 0x07 Return from interrupt (extended)
+  TMR,         // 0x08 Load timer from AC (extended)
   INVALID(u8), // XXX  Invalid instruction for conversions
 }
 impl Default for Instruction {
   fn default() -> Self {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Instruction::default()"};
     Instruction::INVALID(0x00)
   }
 }
 impl fmt::Debug for Instruction {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Instruction::debug()"};
     let s: String = self.into();
     write!{f, "Instruction: {}", s}
   }
 }
-impl Into<String> for Instruction {
-  fn into(self) -> String {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Instruction::into::<String>()"};
-    match self {
+impl From<Instruction> for String {
+  fn from(inst: Instruction) -> String {
+    match inst {
       Instruction::HLT => "Halt".to_string(),
       Instruction::JMP(addr) => format!{"Jump ({:x})", addr},
       Instruction::JZE(addr) => format!{"Jump Zero ({:x})", addr},
@@ -73,15 +179,21 @@ impl Into<String> for Instruction {
       Instruction::DEL => "Decrement Loop Index".to_string(),
       Instruction::LDL(addr) => format!{"Load Loop Index ({})", addr},
       Instruction::FLA => "Negate AC".to_string(),
+      Instruction::RET => "Return".to_string(),
+      Instruction::PUSH => "Push AC".to_string(),
+      Instruction::POP => "Pop AC".to_string(),
+      Instruction::CALL(addr) => format!{"Call ({:x})", addr},
+      Instruction::EI => "Enable Interrupts".to_string(),
+      Instruction::DI => "Disable Interrupts".to_string(),
+      Instruction::RTI => "Return from Interrupt".to_string(),
+      Instruction::TMR => "Load Timer from AC".to_string(),
       Instruction::INVALID(inst) => format!{"Invalid Instruction ({})", inst},
     }
   }
 }
-impl<'i> Into<String> for &'i Instruction {
-  fn into(self) -> String {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"&Instruction::into::<String>()"};
-    match *self {
+impl From<&Instruction> for String {
+  fn from(inst: &Instruction) -> String {
+    match *inst {
       Instruction::HLT => "Halt".to_string(),
       Instruction::JMP(addr) => format!{"Jump ({:x})", addr},
       Instruction::JZE(addr) => format!{"Jump Zero ({:x})", addr},
@@ -98,19 +210,23 @@ impl<'i> Into<String> for &'i Instruction {
       Instruction::DEL => "Decrement Loop Index".to_string(),
       Instruction::LDL(addr) => format!{"Load Loop Index ({})", addr},
       Instruction::FLA => "Negate AC".to_string(),
+      Instruction::RET => "Return".to_string(),
+      Instruction::PUSH => "Push AC".to_string(),
+      Instruction::POP => "Pop AC".to_string(),
+      Instruction::CALL(addr) => format!{"Call ({:x})", addr},
+      Instruction::EI => "Enable Interrupts".to_string(),
+      Instruction::DI => "Disable Interrupts".to_string(),
+      Instruction::RTI => "Return from Interrupt".to_string(),
+      Instruction::TMR => "Load Timer from AC".to_string(),
       Instruction::INVALID(inst) => format!{"Invalid Instruction ({})", inst},
     }
   }
 }
 impl From<u8> for Instruction {
   fn from(val: u8) -> Instruction {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Instruction::from::<u8>()"};
     // expect high bits as opcode and low as value, if applicable
     let opcode: u8 = val.get_bits(OPCODE_BITS);
     let addr: u8 = val.get_bits(ADDR_BITS);
-    #[cfg(not(feature = "lvl3"))]
-    debug!{"opcode {:#X} {:04b} value: {:#X} {:04b}", opcode, opcode, addr, addr};
     match opcode {
       0x0 => Instruction::HLT,
       0x1 => Instruction::JMP(addr),
@@ -127,38 +243,75 @@ impl From<u8> for Instruction {
       0xC => Instruction::SCF,
       0xD => Instruction::DEL,
       0xE => Instruction::LDL(addr),
-      0xF => Instruction::FLA,
-      // technically impossible but not statically resolvable
-      i @ _ => Instruction::INVALID(i),
+      // 0xF, nothing wider comes out of the opcode bits
+      _ => Instruction::FLA,
     }
   }
 }
 impl Instruction {
-  pub fn call(&self, mut machine: &mut Machine) -> InstRes<()>{
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Instruction::call()"};
+  // next is the nibble after the fetched byte, only used by CALL
+  pub fn decode(val: u8, next: u8, isa: Isa) -> Instruction {
+    if isa == Isa::Standard || val.get_bits(OPCODE_BITS) != 0x0 {
+      return val.into();
+    }
+    match val.get_bits(0..4) {
+      0x0 => Instruction::HLT,
+      0x1 => Instruction::RET,
+      0x2 => Instruction::PUSH,
+      0x3 => Instruction::POP,
+      0x4 => Instruction::CALL(next.get_bits(0..4)),
+      0x5 => Instruction::EI,
+      0x6 => Instruction::DI,
+      0x7 => Instruction::RTI,
+      0x8 => Instruction::TMR,
+      _ => Instruction::INVALID(val),
+    }
+  }
+  // fetched byte and next nibble that decode back to this instruction
+  pub fn encode(&self) -> (u8, u8) {
+    match *self {
+      Instruction::RET => (0x1, 0),
+      Instruction::PUSH => (0x2, 0),
+      Instruction::POP => (0x3, 0),
+      Instruction::CALL(addr) => (0x4, addr),
+      Instruction::EI => (0x5, 0),
+      Instruction::DI => (0x6, 0),
+      Instruction::RTI => (0x7, 0),
+      Instruction::TMR => (0x8, 0),
+      Instruction::INVALID(i) => (i, 0),
+      Instruction::JMP(addr) | Instruction::JZE(addr) |
+      Instruction::JNZ(addr) | Instruction::LDA(addr) |
+      Instruction::STA(addr) | Instruction::ADC(addr) |
+      Instruction::LDL(addr) => (self.opcode() << 4 | addr, 0),
+      _ => (self.opcode() << 4, 0),
+    }
+  }
+  pub fn call(&self, machine: &mut Machine) -> InstRes<Outcome> {
     // handle incrementing/not externally
     match *self {
       // hf = 1
-      Instruction::HLT => Ok(machine.get_mut_reg().set_hf(true)),
+      Instruction::HLT => {
+        machine.get_mut_reg().set_hf(true);
+        Ok(Outcome::Halted)
+      },
       // IP = addr
-      Instruction::JMP(addr) => self.jump(addr, &mut machine),
+      Instruction::JMP(addr) => self.jump(addr, machine),
       // IP = zf ? addr : IP++
       Instruction::JZE(addr) => match machine.get_reg().get_zf() {
-                                  true => self.jump(addr, &mut machine),
+                                  true => self.jump(addr, machine),
                                   false => {
                                     machine.get_mut_reg().inc_ip();
                                     machine.get_mut_reg().inc_ip();
-                                    Err(InstErr::JumpNotTaken)
+                                    Ok(Outcome::Continue)
                                   },
                                 },
       // IP = !zf ? addr : IP++
       Instruction::JNZ(addr) => match machine.get_reg().get_zf() {
-                                  false => self.jump(addr, &mut machine),
+                                  false => self.jump(addr, machine),
                                   true => {
                                     machine.get_mut_reg().inc_ip();
                                     machine.get_mut_reg().inc_ip();
-                                    Err(InstErr::JumpNotTaken)
+                                    Ok(Outcome::Continue)
                                   }, // inc past addr
                                 },
       // LI = *addr; zf = (AC==0)
@@ -167,32 +320,32 @@ impl Instruction {
         machine.get_mut_reg().inc_ip();
         let ac: u8 = machine.get_mem().get_loc(addr.into())?;
         machine.get_mut_reg().set_ac(ac)?;
-        Ok(machine.get_mut_reg().set_zf(ac == 0))
+        machine.get_mut_reg().set_zf(ac == 0);
+        Ok(Outcome::Continue)
       },
       // *addr = LI
       Instruction::STA(addr) => {
         machine.get_mut_reg().inc_ip();
         machine.get_mut_reg().inc_ip();
         let ac = machine.get_reg().get_ac();
-        machine.get_mut_mem()
-            .set_loc(addr.into(), ac)
-            .map_err(|e| e.into()) // returns result
+        machine.store(addr, ac)?;
+        Ok(Outcome::Continue)
       },
       // AC = Input   //TODO
       Instruction::GET => {
         #[cfg(not(feature="lvl3"))] return Err(InstErr::InvalidInstruction(0x6));
         #[cfg(feature="lvl3")] machine.get_mut_reg().inc_ip();
-        #[cfg(feature="lvl3")] let ac = machine.pop_inp();
+        #[cfg(feature="lvl3")] let ac = machine.pop_inp()?;
         #[cfg(feature="lvl3")] machine.get_mut_reg().set_ac(ac)?;
-        #[cfg(feature="lvl3")] Ok(())
+        #[cfg(feature="lvl3")] Ok(Outcome::Input(ac))
       },
       // Output = AC  //TODO
       Instruction::PUT => {
         #[cfg(not(feature="lvl3"))] return Err(InstErr::InvalidInstruction(0x7));
         #[cfg(feature="lvl3")] machine.get_mut_reg().inc_ip();
         #[cfg(feature="lvl3")] let ac = machine.get_reg().get_ac();
-        #[cfg(feature="lvl3")] machine.push_outp(ac);
-        #[cfg(feature="lvl3")] Ok(())
+        #[cfg(feature="lvl3")] machine.push_outp(ac)?;
+        #[cfg(feature="lvl3")] Ok(Outcome::Output(ac))
       },
       // cf|AC = AC|cf; zf = (AC == 0); of = cf(pre)==cf(post)
       Instruction::ROL => {
@@ -207,7 +360,7 @@ impl Instruction {
         let cf2: bool = machine.get_reg().get_cf();
         machine.get_mut_reg().set_of(cf == cf2);
         machine.get_mut_reg().set_zf(ac == 0);
-        Ok(())
+        Ok(Outcome::Continue)
       },
       // AC|cf = cf|AC; zf = (AC == 0); of = cf(pre)==cf(post)
       Instruction::ROR => {
@@ -222,7 +375,7 @@ impl Instruction {
         let cf2: bool = machine.get_reg().get_cf();
         machine.get_mut_reg().set_of(cf == cf2);
         machine.get_mut_reg().set_zf(ac == 0);
-        Ok(())
+        Ok(Outcome::Continue)
       },
       // CF|AC = AC + *addr + cf; zf = (AC == 0); of = cf(pre)==cf(post)
       Instruction::ADC(addr) => {
@@ -237,17 +390,19 @@ impl Instruction {
         machine.get_mut_reg().set_of(cf == cf2);
         let ac = machine.get_reg().get_ac();
         machine.get_mut_reg().set_zf(ac == 0);
-        Ok(())
+        Ok(Outcome::Continue)
       },
       // cf = 0
       Instruction::CCF => {
         machine.get_mut_reg().inc_ip();
-        Ok(machine.get_mut_reg().set_cf(false))
+        machine.get_mut_reg().set_cf(false);
+        Ok(Outcome::Continue)
       },
       // cf = 1
       Instruction::SCF => {
         machine.get_mut_reg().inc_ip();
-        Ok(machine.get_mut_reg().set_cf(true))
+        machine.get_mut_reg().set_cf(true);
+        Ok(Outcome::Continue)
       },
       // LI = LI-1; zf = (LI == 0)
       Instruction::DEL => {
@@ -255,7 +410,7 @@ impl Instruction {
         machine.get_mut_reg().dec_li();
         let li = machine.get_reg().get_li();
         machine.get_mut_reg().set_zf(li == 0);
-        Ok(())
+        Ok(Outcome::Continue)
       },
       // LI = *addr; zf = (LI == 0)
       Instruction::LDL(addr) => {
@@ -264,7 +419,7 @@ impl Instruction {
         let li: u8 = machine.get_mem().get_loc(addr.into())?;
         machine.get_mut_reg().set_li(li)?;
         machine.get_mut_reg().set_zf(li == 0);
-        Ok(())
+        Ok(Outcome::Continue)
       },
       // AC != AC; zf = (AC == 0)
       Instruction::FLA => {
@@ -277,15 +432,68 @@ impl Instruction {
         });
         machine.get_mut_reg().set_ac(ac)?;
         machine.get_mut_reg().set_zf(ac == 0);
-        Ok(())
+        Ok(Outcome::Continue)
+      },
+      // IP = pop
+      Instruction::RET => {
+        let ip = machine.get_mut_stack().pop()?;
+        self.jump(ip, machine)
+      },
+      // push AC
+      Instruction::PUSH => {
+        let ac = machine.get_reg().get_ac();
+        machine.get_mut_stack().push(ac)?;
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_reg().inc_ip();
+        Ok(Outcome::Continue)
+      },
+      // AC = pop; zf = (AC == 0)
+      Instruction::POP => {
+        let ac = machine.get_mut_stack().pop()?;
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_reg().set_ac(ac)?;
+        Ok(Outcome::Continue)
+      },
+      // push IP+3; IP = addr
+      Instruction::CALL(addr) => {
+        let ret = (machine.get_reg().get_ip() + 3) % (crate::MAX_VALUE + 1);
+        machine.get_mut_stack().push(ret)?;
+        self.jump(addr, machine)
+      },
+      // ie = 1
+      Instruction::EI => {
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_irq().set_enabled(true);
+        Ok(Outcome::Continue)
       },
-      // NOP
+      // ie = 0
+      Instruction::DI => {
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_irq().set_enabled(false);
+        Ok(Outcome::Continue)
+      },
+      // IP = pop; ie = 1
+      Instruction::RTI => {
+        let ip = machine.get_mut_stack().pop()?;
+        machine.get_mut_irq().set_enabled(true);
+        self.jump(ip, machine)
+      },
+      // timer = AC, 0 stops the timer
+      Instruction::TMR => {
+        machine.get_mut_reg().inc_ip();
+        machine.get_mut_reg().inc_ip();
+        let ac = machine.get_reg().get_ac();
+        machine.get_mut_irq().set_timer(ac)?;
+        Ok(Outcome::Continue)
+      },
+      // decoded from an unused extended opcode
       Instruction::INVALID(inst) => Err(InstErr::InvalidInstruction(inst)),
     }
   }
   pub fn size(&self) -> u8 {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Instruction::size()"};
     match *self {
       Instruction::HLT | Instruction::GET |
       Instruction::PUT | Instruction::ROL |
@@ -296,15 +504,59 @@ impl Instruction {
       Instruction::JNZ(_) | Instruction::LDA(_) |
       Instruction::STA(_) | Instruction::ADC(_) |
       Instruction::LDL(_) => 2,
+      Instruction::RET | Instruction::PUSH |
+      Instruction::POP | Instruction::EI |
+      Instruction::DI | Instruction::RTI |
+      Instruction::TMR => 2,
+      Instruction::CALL(_) => 3,
       Instruction::INVALID(_) => 2,
     }
   }
-  fn jump(&self, addr: u8, machine: &mut Machine) -> InstRes<()> {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Instruction::jump()"};
-    #[cfg(not(feature = "lvl3"))]
-    debug!{"Jumping to: {}", addr}
+  pub fn opcode(&self) -> u8 {
+    match *self {
+      Instruction::HLT => 0x0,
+      Instruction::JMP(_) => 0x1,
+      Instruction::JZE(_) => 0x2,
+      Instruction::JNZ(_) => 0x3,
+      Instruction::LDA(_) => 0x4,
+      Instruction::STA(_) => 0x5,
+      Instruction::GET => 0x6,
+      Instruction::PUT => 0x7,
+      Instruction::ROL => 0x8,
+      Instruction::ROR => 0x9,
+      Instruction::ADC(_) => 0xA,
+      Instruction::CCF => 0xB,
+      Instruction::SCF => 0xC,
+      Instruction::DEL => 0xD,
+      Instruction::LDL(_) => 0xE,
+      Instruction::FLA => 0xF,
+      Instruction::RET | Instruction::PUSH |
+      Instruction::POP | Instruction::CALL(_) |
+      Instruction::EI | Instruction::DI |
+      Instruction::RTI | Instruction::TMR => 0x0,
+      Instruction::INVALID(i) => i,
+    }
+  }
+  // taken is only meaningful for jumps, JMP is always taken
+  pub fn cycles(&self, taken: bool) -> usize {
+    match *self {
+      Instruction::INVALID(_) => self.size() as usize,
+      Instruction::JMP(_) => CYCLE_TABLE[0x1] + JUMP_TAKEN_CYCLES,
+      Instruction::RET => EXT_CYCLE_TABLE[0x1] + JUMP_TAKEN_CYCLES,
+      Instruction::PUSH => EXT_CYCLE_TABLE[0x2],
+      Instruction::POP => EXT_CYCLE_TABLE[0x3],
+      Instruction::CALL(_) => EXT_CYCLE_TABLE[0x4] + JUMP_TAKEN_CYCLES,
+      Instruction::EI => EXT_CYCLE_TABLE[0x5],
+      Instruction::DI => EXT_CYCLE_TABLE[0x6],
+      Instruction::RTI => EXT_CYCLE_TABLE[0x7] + JUMP_TAKEN_CYCLES,
+      Instruction::TMR => EXT_CYCLE_TABLE[0x8],
+      Instruction::JZE(_) | Instruction::JNZ(_) if taken =>
+        CYCLE_TABLE[self.opcode() as usize] + JUMP_TAKEN_CYCLES,
+      _ => CYCLE_TABLE[self.opcode() as usize],
+    }
+  }
+  fn jump(&self, addr: u8, machine: &mut Machine) -> InstRes<Outcome> {
     machine.get_mut_reg().set_ip(addr)?;
-    Ok(())
+    Ok(Outcome::Jumped(addr))
   }
 }
@@ -0,0 +1,223 @@
+// Instruction semantics written out on plain values, checked against call
+use std::collections::VecDeque;
+use proptest::prelude::*;
+use crate::machine::Machine;
+use crate::machine::arb;
+use crate::instructions::{Instruction, Outcome};
+use crate::ports::{Port, PortMode, PORT_SIZE};
+use crate::stack::STACK_SIZE;
+
+// three value bits, as VALUE_BITS keeps after ROL, ROR, ADC and FLA
+const KEPT: u8 = 0b111;
+
+#[derive(Clone,PartialEq,Debug)]
+struct Model {
+  ip: u8,
+  li: u8,
+  ac: u8,
+  cf: bool,
+  zf: bool,
+  of: bool,
+  hf: bool,
+  mem: [u8; 16],
+  stack: Vec<u8>,
+  enabled: bool,
+  timer: u8,
+  inp: VecDeque<u8>,
+  outp: VecDeque<u8>,
+  saturate: bool,
+  underflow: bool,
+  overflow: bool,
+}
+impl<'a> From<&'a Machine> for Model {
+  fn from(machine: &'a Machine) -> Self {
+    let reg = machine.get_reg();
+    let mut mem = [0; 16];
+    for (n, v) in mem.iter_mut().enumerate() {
+      *v = machine.get_mem().get_loc(n).unwrap();
+    }
+    let port = |p: &Port| p.iter().collect::<VecDeque<u8>>();
+    Model {
+      ip: reg.get_ip(),
+      li: reg.get_li(),
+      ac: reg.get_ac(),
+      cf: reg.get_cf(),
+      zf: reg.get_zf(),
+      of: reg.get_of(),
+      hf: reg.get_hf(),
+      mem,
+      stack: machine.get_stack().get_all().to_vec(),
+      enabled: machine.get_irq().is_enabled(),
+      timer: machine.get_irq().get_reload(),
+      inp: port(machine.get_inp()),
+      outp: port(machine.get_outp()),
+      saturate: machine.get_inp().get_mode() == PortMode::Saturate,
+      underflow: machine.get_inp().underflowed(),
+      overflow: machine.get_outp().overflowed(),
+    }
+  }
+}
+impl Model {
+  fn skip(&mut self, size: u8) -> Option<Outcome> {
+    self.ip = (self.ip + size) % 16;
+    Some(Outcome::Continue)
+  }
+  fn jump(&mut self, addr: u8) -> Option<Outcome> {
+    self.ip = addr;
+    Some(Outcome::Jumped(addr))
+  }
+  fn set_ac(&mut self, ac: u8) {
+    self.ac = ac;
+    self.zf = ac == 0;
+  }
+  // carry and overflow once a result wider than the kept bits is known
+  fn carry(&mut self, cf: bool) {
+    self.of = self.cf == cf;
+    self.cf = cf;
+  }
+  fn pop(&mut self) -> Option<u8> {
+    self.stack.pop()
+  }
+  fn push(&mut self, v: u8) -> Option<()> {
+    if self.stack.len() == STACK_SIZE {
+      return None
+    }
+    self.stack.push(v);
+    Some(())
+  }
+  // none when the instruction faults
+  fn exec(&mut self, inst: &Instruction) -> Option<Outcome> {
+    match *inst {
+      Instruction::HLT => {
+        self.hf = true;
+        Some(Outcome::Halted)
+      },
+      Instruction::JMP(addr) => self.jump(addr),
+      Instruction::JZE(addr) if self.zf => self.jump(addr),
+      Instruction::JNZ(addr) if !self.zf => self.jump(addr),
+      Instruction::JZE(_) | Instruction::JNZ(_) => self.skip(2),
+      Instruction::LDA(addr) => {
+        self.set_ac(self.mem[addr as usize]);
+        self.skip(2)
+      },
+      Instruction::STA(addr) => {
+        self.mem[addr as usize] = self.ac;
+        self.skip(2)
+      },
+      Instruction::GET if cfg!(feature = "lvl3") => {
+        let v = match (self.inp.pop_front(), self.saturate) {
+          (Some(v), _) => v,
+          (None, true) => { self.underflow = true; 0 },
+          (None, false) => return None,
+        };
+        self.set_ac(v);
+        self.skip(1);
+        Some(Outcome::Input(v))
+      },
+      Instruction::PUT if cfg!(feature = "lvl3") => {
+        if self.outp.len() == PORT_SIZE {
+          match self.saturate {
+            true => { self.overflow = true; self.outp.pop_front(); },
+            false => return None,
+          };
+        }
+        self.outp.push_back(self.ac);
+        self.skip(1);
+        Some(Outcome::Output(self.ac))
+      },
+      Instruction::ROL => {
+        let wide = self.ac << 1 | self.cf as u8;
+        self.carry(wide & 0x10 != 0);
+        self.set_ac(wide & KEPT);
+        self.skip(1)
+      },
+      Instruction::ROR => {
+        let cf = self.ac & 1 != 0;
+        self.carry(cf);
+        self.set_ac(self.ac >> 1 & KEPT);
+        self.skip(1)
+      },
+      Instruction::ADC(addr) => {
+        let sum = self.ac + self.mem[addr as usize] + self.cf as u8;
+        self.carry(sum & 0x10 != 0);
+        self.set_ac(sum & KEPT);
+        self.skip(2)
+      },
+      Instruction::CCF => {
+        self.cf = false;
+        self.skip(1)
+      },
+      Instruction::SCF => {
+        self.cf = true;
+        self.skip(1)
+      },
+      Instruction::DEL => {
+        self.li = (self.li + 15) % 16;
+        self.zf = self.li == 0;
+        self.skip(1)
+      },
+      Instruction::LDL(addr) => {
+        self.li = self.mem[addr as usize];
+        self.zf = self.li == 0;
+        self.skip(2)
+      },
+      Instruction::FLA => {
+        self.set_ac(!self.ac & KEPT);
+        self.skip(1)
+      },
+      Instruction::RET => {
+        let ip = self.pop()?;
+        self.jump(ip)
+      },
+      Instruction::PUSH => {
+        self.push(self.ac)?;
+        self.skip(2)
+      },
+      Instruction::POP => {
+        let v = self.pop()?;
+        self.set_ac(v);
+        self.skip(2)
+      },
+      Instruction::CALL(addr) => {
+        self.push((self.ip + 3) % 16)?;
+        self.jump(addr)
+      },
+      Instruction::EI | Instruction::DI => {
+        self.enabled = *inst == Instruction::EI;
+        self.skip(2)
+      },
+      Instruction::RTI => {
+        let ip = self.pop()?;
+        self.enabled = true;
+        self.jump(ip)
+      },
+      Instruction::TMR => {
+        self.timer = self.ac;
+        self.skip(2)
+      },
+      Instruction::GET | Instruction::PUT | Instruction::INVALID(_) => None,
+    }
+  }
+}
+
+proptest!{
+  #[test]
+  fn call_matches_model(mut machine in arb::machine(), inst in arb::instruction()) {
+    let mut model = Model::from(&machine);
+    let expect = model.exec(&inst);
+    match inst.call(&mut machine) {
+      Ok(outcome) => {
+        prop_assert_eq!{Some(outcome), expect, "{:?}", inst};
+        prop_assert_eq!{Model::from(&machine), model, "{:?}", inst};
+      },
+      Err(e) => prop_assert!{expect.is_none(), "{:?} faulted: {:?}", inst, e},
+    }
+  }
+  #[test]
+  fn ip_wraps(mut machine in arb::machine(), inst in arb::instruction()) {
+    let ip = machine.get_reg().get_ip();
+    if let Ok(Outcome::Continue) = inst.call(&mut machine) {
+      prop_assert_eq!{machine.get_reg().get_ip(), (ip + inst.size()) % 16, "{:?}", inst};
+    }
+  }
+}
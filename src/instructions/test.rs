@@ -1,4 +1,4 @@
-use instructions::*;
+use crate::instructions::*;
 // Instructions from u8 expect high bits as opcode and low as param
 #[test]
 fn default() {
@@ -108,3 +108,71 @@ fn invalid() {
   assert_ne!{inst, Instruction::INVALID(0xF)};
   assert_eq!{inst, Instruction::FLA};
 }
+
+#[test]
+fn cycles() {
+  assert_eq!{Instruction::HLT.cycles(true), CYCLE_TABLE[0x0]};
+  assert_eq!{Instruction::LDA(0).cycles(true), CYCLE_TABLE[0x4]};
+  assert_eq!{Instruction::JMP(0).cycles(false), CYCLE_TABLE[0x1] + JUMP_TAKEN_CYCLES};
+  assert_eq!{Instruction::JZE(0).cycles(false), CYCLE_TABLE[0x2]};
+  assert_eq!{Instruction::JZE(0).cycles(true), CYCLE_TABLE[0x2] + JUMP_TAKEN_CYCLES};
+  assert_eq!{Instruction::JNZ(0).cycles(true), CYCLE_TABLE[0x3] + JUMP_TAKEN_CYCLES};
+}
+
+#[test]
+fn opcode() {
+  for op in 0..=0xFu8 {
+    let inst: Instruction = (op << 4).into();
+    assert_eq!{inst.opcode(), op};
+  }
+}
+
+#[test]
+fn decode_standard() {
+  assert_eq!{Instruction::decode(0x01, 0x5, Isa::Standard), Instruction::HLT};
+  assert_eq!{Instruction::decode(0x40, 0x5, Isa::Standard), Instruction::LDA(0)};
+}
+
+#[test]
+fn decode_extended() {
+  assert_eq!{Instruction::decode(0x00, 0x5, Isa::Extended), Instruction::HLT};
+  assert_eq!{Instruction::decode(0x01, 0x5, Isa::Extended), Instruction::RET};
+  assert_eq!{Instruction::decode(0x02, 0x5, Isa::Extended), Instruction::PUSH};
+  assert_eq!{Instruction::decode(0x03, 0x5, Isa::Extended), Instruction::POP};
+  assert_eq!{Instruction::decode(0x04, 0x5, Isa::Extended), Instruction::CALL(5)};
+  assert_eq!{Instruction::decode(0x0F, 0x5, Isa::Extended), Instruction::INVALID(0x0F)};
+  assert_eq!{Instruction::decode(0x40, 0x5, Isa::Extended), Instruction::LDA(0)};
+  assert_eq!{Instruction::CALL(5).size(), 3};
+}
+
+#[test]
+fn decode_interrupts() {
+  assert_eq!{Instruction::decode(0x05, 0x0, Isa::Extended), Instruction::EI};
+  assert_eq!{Instruction::decode(0x06, 0x0, Isa::Extended), Instruction::DI};
+  assert_eq!{Instruction::decode(0x07, 0x0, Isa::Extended), Instruction::RTI};
+  assert_eq!{Instruction::decode(0x08, 0x0, Isa::Extended), Instruction::TMR};
+}
+
+#[test]
+fn encode_round_trip() {
+  for isa in [Isa::Standard, Isa::Extended] {
+    for val in 0..=0xFF {
+      let inst = Instruction::decode(val, 0x9, isa);
+      let (byte, next) = inst.encode();
+      assert_eq!{Instruction::decode(byte, next, isa), inst};
+    }
+  }
+}
+
+#[test]
+fn call_outcomes() {
+  use crate::machine::Machine;
+  let mut machine = Machine::default();
+  // zf is clear so jze falls through
+  assert_eq!{Instruction::JZE(4).call(&mut machine), Ok(Outcome::Continue)};
+  assert_eq!{machine.get_reg().get_ip(), 2};
+  assert_eq!{Instruction::JNZ(6).call(&mut machine), Ok(Outcome::Jumped(6))};
+  assert_eq!{Instruction::JMP(4).call(&mut machine), Ok(Outcome::Jumped(4))};
+  assert_eq!{Instruction::HLT.call(&mut machine), Ok(Outcome::Halted)};
+  assert_eq!{Instruction::INVALID(0x9).call(&mut machine), Err(InstructionError::InvalidInstruction(0x9))};
+}
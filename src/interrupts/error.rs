@@ -0,0 +1,23 @@
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+pub type InterruptResult<I> = Result<I, InterruptError>;
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
+pub enum InterruptError {
+  ValueTooLarge(u8),
+}
+impl fmt::Display for InterruptError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      InterruptError::ValueTooLarge(v) =>
+        write!{f, "Value provided is above 4 bits: {:X}", v},
+    }
+  }
+}
+impl fmt::Debug for InterruptError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Interrupt Error: {}", self}
+  }
+}
+impl Error for InterruptError {}
@@ -0,0 +1,140 @@
+#[cfg(test)] mod test;
+mod error;
+
+use std::fmt;
+use bit_field::*;
+use self::error::InterruptError as IntErr;
+use self::error::InterruptResult as IntRes;
+
+pub use self::error::InterruptError;
+pub use self::error::InterruptResult;
+pub const TIMER_BIT: usize = 0;
+pub const INPUT_BIT: usize = 1;
+pub const INTERRUPT_CYCLES: usize = 2; // push IP and load vector
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Irq {
+  Timer, // countdown reached zero
+  Input, // input port holds unread values
+}
+
+// Dispatch pushes IP to the machine stack, clears enabled and jumps to vector
+#[derive(Clone,PartialEq,Default)]
+pub struct Interrupts {
+  enabled: bool,
+  mask: u8,    // sources allowed to fire, TIMER_BIT | INPUT_BIT
+  pending: u8, // latched sources, only the timer latches
+  timer: u8,   // instructions left until the timer fires
+  reload: u8,  // timer restarts from here, 0 stops it
+  vector: u8,  // IP of the handler
+}
+impl fmt::Debug for Interrupts {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f,
+      "Interrupts: IE={} MASK={:02b} PENDING={:02b} TIMER={:#X}/{:#X} VECTOR={:#X}",
+      self.enabled, self.mask, self.pending, self.timer, self.reload, self.vector
+    }
+  }
+}
+impl Interrupts {
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+  pub fn get_mask(&self) -> u8 {
+    self.mask
+  }
+  pub fn get_pending(&self) -> u8 {
+    self.pending
+  }
+  pub fn get_timer(&self) -> u8 {
+    self.timer
+  }
+  pub fn get_reload(&self) -> u8 {
+    self.reload
+  }
+  pub fn get_vector(&self) -> u8 {
+    self.vector
+  }
+  pub fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+  }
+  pub fn set_mask(&mut self, irq: Irq, allowed: bool) {
+    self.mask.set_bit(Self::bit(irq), allowed);
+  }
+  // starts counting down from reload, 0 stops the timer
+  pub fn set_timer(&mut self, reload: u8) -> IntRes<()> {
+    if crate::MAX_VALUE < reload {
+      return Err(IntErr::ValueTooLarge(reload))
+    }
+    self.reload = reload;
+    self.timer = reload;
+    Ok(())
+  }
+  pub fn set_vector(&mut self, vector: u8) -> IntRes<()> {
+    if crate::MAX_VALUE < vector {
+      return Err(IntErr::ValueTooLarge(vector))
+    }
+    self.vector = vector;
+    Ok(())
+  }
+  // called once per executed instruction
+  pub fn tick(&mut self) {
+    if self.reload == 0 {
+      return;
+    }
+    if self.timer <= 1 {
+      self.pending.set_bit(TIMER_BIT, true);
+      self.timer = self.reload;
+    } else {
+      self.timer -= 1;
+    }
+  }
+  // highest priority source ready to fire, input is level triggered
+  pub fn next(&self, input_ready: bool) -> Option<Irq> {
+    if !self.enabled {
+      None
+    } else if self.pending.get_bit(TIMER_BIT) && self.mask.get_bit(TIMER_BIT) {
+      Some(Irq::Timer)
+    } else if input_ready && self.mask.get_bit(INPUT_BIT) {
+      Some(Irq::Input)
+    } else {
+      None
+    }
+  }
+  // acknowledge irq and block further interrupts until re-enabled
+  pub fn acknowledge(&mut self, irq: Irq) {
+    self.pending.set_bit(Self::bit(irq), false);
+    self.enabled = false;
+  }
+  // enabled, mask, pending, timer, reload, vector as snapshots store them
+  pub(crate) fn to_bytes(&self) -> [u8; 6] {
+    [self.enabled as u8, self.mask, self.pending, self.timer, self.reload, self.vector]
+  }
+  pub(crate) fn from_bytes(bytes: [u8; 6]) -> IntRes<Interrupts> {
+    if let Some(v) = bytes[1..].iter().find(|v| crate::MAX_VALUE < **v) {
+      return Err(IntErr::ValueTooLarge(*v))
+    }
+    // a running timer counts from reload down to 1, a stopped one sits at 0
+    let (timer, reload) = (bytes[3], bytes[4]);
+    if reload < timer {
+      return Err(IntErr::ValueTooLarge(timer))
+    }
+    if timer == 0 && reload != 0 {
+      return Err(IntErr::ValueTooLarge(reload))
+    }
+    Ok(Interrupts {
+      enabled: bytes[0] != 0,
+      mask: bytes[1],
+      pending: bytes[2],
+      timer: bytes[3],
+      reload: bytes[4],
+      vector: bytes[5],
+    })
+  }
+  fn bit(irq: Irq) -> usize {
+    match irq {
+      Irq::Timer => TIMER_BIT,
+      Irq::Input => INPUT_BIT,
+    }
+  }
+}
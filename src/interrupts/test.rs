@@ -0,0 +1,62 @@
+use bit_field::*;
+use crate::interrupts::*;
+
+#[test]
+fn default() {
+  let irq = Interrupts::default();
+  assert!{!irq.is_enabled()};
+  assert_eq!{irq.get_mask(), 0};
+  assert_eq!{irq.get_pending(), 0};
+  assert_eq!{irq.get_timer(), 0};
+  assert_eq!{irq.get_vector(), 0};
+  assert_eq!{irq.next(true), None};
+}
+#[test]
+fn timer_countdown() {
+  let mut irq = Interrupts::default();
+  assert!{irq.set_timer(2).is_ok()};
+  irq.tick();
+  assert_eq!{irq.get_timer(), 1};
+  assert!{!irq.get_pending().get_bit(TIMER_BIT)};
+  irq.tick();
+  assert_eq!{irq.get_timer(), 2}; // reloaded
+  assert!{irq.get_pending().get_bit(TIMER_BIT)};
+}
+#[test]
+fn timer_stopped() {
+  let mut irq = Interrupts::default();
+  irq.tick();
+  assert_eq!{irq.get_pending(), 0};
+}
+#[test]
+fn next_requires_enable_and_mask() {
+  let mut irq = Interrupts::default();
+  assert!{irq.set_timer(1).is_ok()};
+  irq.tick();
+  assert_eq!{irq.next(false), None};
+  irq.set_enabled(true);
+  assert_eq!{irq.next(false), None};
+  irq.set_mask(Irq::Timer, true);
+  assert_eq!{irq.next(false), Some(Irq::Timer)};
+}
+#[test]
+fn timer_before_input() {
+  let mut irq = Interrupts::default();
+  irq.set_enabled(true);
+  irq.set_mask(Irq::Timer, true);
+  irq.set_mask(Irq::Input, true);
+  assert_eq!{irq.next(true), Some(Irq::Input)};
+  assert!{irq.set_timer(1).is_ok()};
+  irq.tick();
+  assert_eq!{irq.next(true), Some(Irq::Timer)};
+  irq.acknowledge(Irq::Timer);
+  assert!{!irq.is_enabled()};
+  irq.set_enabled(true);
+  assert_eq!{irq.next(true), Some(Irq::Input)};
+}
+#[test]
+fn invalid_values() {
+  let mut irq = Interrupts::default();
+  assert_eq!{irq.set_timer(0x10), Err(InterruptError::ValueTooLarge(0x10))};
+  assert_eq!{irq.set_vector(0x10), Err(InterruptError::ValueTooLarge(0x10))};
+}
@@ -0,0 +1,99 @@
+#[cfg(test)] mod test;
+
+use crate::machine::Machine;
+use crate::memory::MemoryInner;
+use crate::registers::RegisterInner;
+use crate::stack::Stack;
+use crate::interrupts::Interrupts;
+use crate::ports::Port;
+use crate::devices::Bus;
+use crate::machine::Fault;
+
+// What a single step overwrote. Registers, counters, fault and memory access
+// bits are always kept, everything else only when the step changed it.
+#[derive(Clone,Debug)]
+pub struct Delta {
+  pub(crate) regs: RegisterInner,
+  pub(crate) calls: usize,
+  pub(crate) error: Option<Fault>,
+  pub(crate) cycles: usize,
+  pub(crate) access: (u16, u16),       // memory reads and writes
+  pub(crate) mem: Option<MemoryInner>,
+  pub(crate) stack: Option<Stack>,
+  pub(crate) irq: Option<Interrupts>,
+  pub(crate) inp: Option<Port>,
+  pub(crate) outp: Option<Port>,
+  pub(crate) bus: Option<Bus>,         // devices can change on any tick
+}
+
+// port equality ignores the overflow flags
+fn same_port(before: &Port, after: &Port) -> bool {
+  before == after
+    && before.underflowed() == after.underflowed()
+    && before.overflowed() == after.overflowed()
+}
+
+impl Delta {
+  // everything a step might change, trimmed by finish once it ran
+  pub(crate) fn before(machine: &Machine) -> Self {
+    let mem = machine.get_mem();
+    Delta {
+      regs: machine.get_reg().clone().into(),
+      calls: machine.get_cc(),
+      error: machine.get_error().cloned(),
+      cycles: machine.get_cycles(),
+      access: (mem.get_reads(), mem.get_writes()),
+      mem: Some(*mem.get_all()),
+      stack: Some(machine.get_stack().clone()),
+      irq: Some(machine.get_irq().clone()),
+      inp: Some(machine.get_inp().clone()),
+      outp: Some(machine.get_outp().clone()),
+      bus: match mem.get_bus().is_empty() {
+        true => None,
+        false => Some(mem.get_bus().clone()),
+      },
+    }
+  }
+  pub(crate) fn finish(mut self, machine: &Machine) -> Self {
+    self.mem = self.mem.filter(|m| m != machine.get_mem().get_all());
+    self.stack = self.stack.filter(|s| s != machine.get_stack());
+    self.irq = self.irq.filter(|i| i != machine.get_irq());
+    self.inp = self.inp.filter(|p| !same_port(p, machine.get_inp()));
+    self.outp = self.outp.filter(|p| !same_port(p, machine.get_outp()));
+    self
+  }
+  pub fn get_regs(&self) -> RegisterInner {
+    self.regs
+  }
+  pub fn get_mem(&self) -> Option<&MemoryInner> {
+    self.mem.as_ref()
+  }
+}
+
+// Undo log of the steps run since journaling started, oldest first.
+#[derive(Clone,Debug,Default)]
+pub struct Journal {
+  deltas: Vec<Delta>,
+}
+impl Journal {
+  pub fn new() -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Journal::new()"};
+    Journal::default()
+  }
+  pub fn len(&self) -> usize {
+    self.deltas.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.deltas.is_empty()
+  }
+  pub fn get_deltas(&self) -> &[Delta] {
+    &self.deltas
+  }
+  pub(crate) fn push(&mut self, delta: Delta) {
+    self.deltas.push(delta);
+  }
+  pub(crate) fn pop(&mut self) -> Option<Delta> {
+    self.deltas.pop()
+  }
+}
@@ -0,0 +1,74 @@
+use crate::journal::*;
+use crate::machine::*;
+use crate::instructions::Isa;
+
+fn journaled(mem: [u8; 8]) -> Machine {
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  image[2..].copy_from_slice(&mem);
+  Machine::from(image).set_journal(true)
+}
+
+#[test]
+fn undo_steps() {
+  // lda 7; sta 6; hlt
+  let mut machine = journaled([0x47, 0x56, 0, 0x03, 0, 0, 0, 0]);
+  let start = MachineInner::from(&machine);
+  machine.exec().unwrap();
+  let journal = machine.get_journal().unwrap();
+  assert_eq!{journal.len(), 3};
+  // only the store touched memory
+  let mems: Vec<bool> = journal.get_deltas().iter().map(|d| d.get_mem().is_some()).collect();
+  assert_eq!{mems, vec![false, true, false]};
+  assert!{machine.step_back()};
+  assert_eq!{machine.get_reg().get_ip(), 4};
+  assert!{!machine.is_stopped()};
+  assert!{machine.step_back()};
+  assert_eq!{machine.get_mem().get_loc(6), Ok(0)};
+  assert!{machine.step_back()};
+  assert!{!machine.step_back()};
+  assert!{machine.get_journal().is_some_and(Journal::is_empty)};
+  assert_eq!{MachineInner::from(&machine), start};
+  assert_eq!{(machine.get_cc(), machine.get_cycles()), (0, 0)};
+  // and forward again to the same place
+  machine.exec().unwrap();
+  assert_eq!{machine.get_mem().get_loc(6), Ok(3)};
+}
+#[test]
+fn rewind() {
+  let mut machine = journaled([0x47, 0x56, 0, 0x03, 0, 0, 0, 0]).set_recording(true);
+  machine.exec().unwrap();
+  assert!{!machine.rewind_to(4)};
+  assert!{machine.rewind_to(1)};
+  let trace = machine.get_trace().unwrap();
+  assert_eq!{trace.len(), 1};
+  assert_eq!{MachineInner::from(&machine), trace.state_at(1)};
+  assert_eq!{machine.get_cc(), 1};
+  // without a journal there is nothing to undo
+  let mut machine = Machine::default();
+  assert!{!machine.step_back()};
+  assert!{!machine.rewind_to(0)};
+}
+#[test]
+fn undo_fault() {
+  // ret with an empty stack
+  let mut machine = journaled([0x01, 0, 0, 0, 0, 0, 0, 0]).set_isa(Isa::Extended);
+  assert!{machine.exec().is_err()};
+  assert!{machine.get_reg().get_hf()};
+  assert!{machine.step_back()};
+  assert!{!machine.get_reg().get_hf()};
+  assert_eq!{machine.get_error(), None};
+  assert_eq!{machine.get_reg().get_ip(), 0};
+}
+#[test]
+fn undo_stack() {
+  // call 4; hlt; ret at 4
+  let mut machine = Machine::from([0, 0, 0x04, 0x40, 0x01, 0, 0, 0, 0, 0])
+    .set_isa(Isa::Extended)
+    .set_journal(true);
+  machine.step().unwrap();
+  assert_eq!{machine.get_stack().len(), 1};
+  assert!{machine.get_journal().unwrap().get_deltas()[0].get_mem().is_none()};
+  assert!{machine.step_back()};
+  assert!{machine.get_stack().is_empty()};
+  assert_eq!{machine.get_reg().get_ip(), 0};
+}
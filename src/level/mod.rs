@@ -0,0 +1,178 @@
+#[cfg(test)] mod test;
+
+use serde::{Serialize, Deserialize};
+use crate::devices::{Bus, Random};
+use crate::ports::{Port, PortMode};
+use std::convert::TryFrom;
+use crate::machine::{Machine, MachineInner, MachineResult, MAX_CALLS};
+use crate::instructions::Isa;
+use crate::faults::{FaultPolicy, FaultKind, FaultAction};
+
+pub const RANDOM_ADDR: usize = 0xF; // nibble Level::Open maps its random source at
+
+// Challenge level, selected at build time through the lvl features
+#[derive(Clone,Copy,PartialEq,Debug,Serialize,Deserialize)]
+pub enum Level {
+  Open,
+  One,
+  Two,
+  Three,
+}
+impl Default for Level {
+  fn default() -> Self {
+    Level::current()
+  }
+}
+impl Level {
+  #[cfg(feature="lvl1")]
+  pub fn current() -> Level {
+    Level::One
+  }
+  #[cfg(feature="lvl2")]
+  pub fn current() -> Level {
+    Level::Two
+  }
+  #[cfg(feature="lvl3")]
+  pub fn current() -> Level {
+    Level::Three
+  }
+  #[cfg(not(any(feature="lvl1", feature="lvl2", feature="lvl3")))]
+  pub fn current() -> Level {
+    Level::Open
+  }
+  // machine a player image runs on, seed varies per session
+  pub fn load(&self, image: MachineInner, seed: u32) -> Machine {
+    Machine::from(image)
+      .set_ruleset(Ruleset { level: *self, ..Ruleset::default() })
+      .set_bus(self.bus(seed))
+      .set_port_mode(self.port_mode())
+      .set_inp(self.input())
+      .set_faults(self.faults())
+      .set_budget(self.budget())
+  }
+  // as load, for bytes that came from a player
+  pub fn try_load(&self, bytes: &[u8], seed: u32) -> MachineResult<Machine> {
+    let machine = Machine::try_from(bytes)?;
+    Ok(self.load((&machine).into(), seed))
+  }
+  // devices mapped into memory for a session
+  pub fn bus(&self, seed: u32) -> Bus {
+    match *self {
+      Level::Open => Bus::default().attach(RANDOM_ADDR, Random::new(seed))
+        .expect("RANDOM_ADDR is a memory nibble"),
+      Level::One | Level::Two | Level::Three => Bus::default(),
+    }
+  }
+  // behaviour of GET on an empty input and PUT on a full output
+  pub fn port_mode(&self) -> PortMode {
+    match *self {
+      Level::Open | Level::One | Level::Two => PortMode::Fault,
+      // existing solutions rely on reading zeros once drained
+      Level::Three => PortMode::Saturate,
+    }
+  }
+  // what each kind of fault does to a run
+  pub fn faults(&self) -> FaultPolicy {
+    match *self {
+      // invalid instructions quietly halt
+      Level::One => FaultPolicy::default().set(FaultKind::InvalidOpcode, FaultAction::Halt),
+      Level::Open | Level::Two | Level::Three => FaultPolicy::default(),
+    }
+  }
+  // calls a run gets before it is stopped
+  pub fn budget(&self) -> usize {
+    match *self {
+      Level::Open | Level::One | Level::Two | Level::Three => MAX_CALLS,
+    }
+  }
+  pub fn input(&self) -> Port {
+    match *self {
+      Level::Three => Port::from([1, 2, 3, 4, 5]),
+      _ => Port::default(),
+    }
+  }
+  pub fn is_valid(&self, machine: &Machine) -> bool {
+    self.unmet(machine) == 0
+  }
+  // number of winning conditions the end state misses, 0 is a win
+  pub fn unmet(&self, machine: &Machine) -> usize {
+    let reg = machine.get_reg();
+    let cc = machine.get_cc();
+    match *self {
+      Level::Open => 0,
+      Level::One => {
+        let mem = machine.get_mem().get_all();
+        let checks = [
+          cc < 10,
+          reg.get_ip() > 0x4,
+          reg.get_ip() < 0x8,
+          reg.get_ac() == 4,
+        ];
+        unmet(&checks) + mem.iter().zip(b"0ByteCTF".iter())
+          .filter(|(m, e)| m != e)
+          .count()
+      },
+      Level::Two => unmet(&[
+        cc < 100,
+        reg.get_ac() == 5,
+      ]),
+      Level::Three => {
+        let inp = machine.get_inp();
+        let outp = machine.get_outp();
+        let checks = [
+          cc < 100,
+          cc > 40,
+          inp.is_empty(),
+          outp.len() == 5,
+        ];
+        unmet(&checks) + (1..=5).enumerate()
+          .filter(|(i, v)| outp.get(*i) != Some(*v))
+          .count()
+      },
+    }
+  }
+}
+
+fn unmet(checks: &[bool]) -> usize {
+  checks.iter().filter(|c| !**c).count()
+}
+
+// Nibble order within each image byte
+#[derive(Clone,Copy,PartialEq,Debug,Default,Serialize,Deserialize)]
+pub enum Endian {
+  #[default]
+  Big,    // high nibble first, as the machine stores it
+  Little, // low nibble first, swapped on load
+}
+impl Endian {
+  pub fn apply(&self, image: MachineInner) -> MachineInner {
+    match *self {
+      Endian::Big => image,
+      Endian::Little => {
+        let mut image = image;
+        for b in image.iter_mut() {
+          *b = b.rotate_left(4);
+        }
+        image
+      },
+    }
+  }
+}
+
+// Everything besides the image that decides how a run goes
+#[derive(Clone,Copy,PartialEq,Debug,Default,Serialize,Deserialize)]
+pub struct Ruleset {
+  pub level: Level,
+  pub isa: Isa,
+  pub endian: Endian,
+}
+impl Ruleset {
+  pub fn load(&self, image: MachineInner, seed: u32) -> Machine {
+    self.level.load(self.endian.apply(image), seed).set_ruleset(*self)
+  }
+  // as load, for bytes that came from a player
+  pub fn try_load(&self, bytes: &[u8], seed: u32) -> MachineResult<Machine> {
+    let machine = Machine::try_from(bytes)?;
+    Ok(self.load((&machine).into(), seed))
+  }
+}
@@ -0,0 +1,93 @@
+use crate::level::*;
+use crate::machine::*;
+use crate::ports::PortMode;
+use crate::instructions::Isa;
+
+#[test]
+#[cfg(not(any(feature="lvl1", feature="lvl2", feature="lvl3")))]
+fn current() {
+  assert_eq!{Level::current(), Level::Open};
+  assert_eq!{Level::default(), Level::Open};
+}
+#[test]
+fn buses() {
+  assert!{Level::Open.bus(0).is_mapped(RANDOM_ADDR)};
+  for level in [Level::One, Level::Two, Level::Three].iter() {
+    assert!{level.bus(0).is_empty()};
+  }
+  // the random nibble follows the session seed
+  let draws = |seed| {
+    let mac = Level::Open.load([0; MACHINE_SIZE], seed);
+    (0..8).map(|_| mac.get_mem().get_loc(RANDOM_ADDR).unwrap()).collect::<Vec<_>>()
+  };
+  assert_eq!{draws(7), draws(7)};
+  assert_ne!{draws(7), draws(8)};
+}
+#[test]
+fn port_modes() {
+  assert_eq!{Level::Open.port_mode(), PortMode::Fault};
+  assert_eq!{Level::Three.port_mode(), PortMode::Saturate};
+}
+#[test]
+fn budgets() {
+  let image: MachineInner = [0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0];
+  let mut mac = Level::Two.load(image, 0);
+  assert_eq!{mac.get_budget(), Level::Two.budget()};
+  assert_eq!{mac.run(), HaltReason::Budget};
+  assert!{!Level::Two.is_valid(&mac)};
+}
+#[test]
+fn open_always_valid() {
+  let mac = Machine::default();
+  assert!{Level::Open.is_valid(&mac)};
+}
+#[test]
+fn level_one() {
+  // hlt at 0x6 with the flag text in memory
+  let mut image: MachineInner = [0x60, 0x04,
+    b'0', b'B', b'y', b't', b'e', b'C', b'T', b'F'];
+  let mac = Level::One.load(image, 0);
+  assert!{Level::One.is_valid(&mac)};
+  image[3] = b'b';
+  let mac = Level::One.load(image, 0);
+  assert_eq!{Level::One.unmet(&mac), 1};
+}
+#[test]
+fn level_two() {
+  let mac = Level::Two.load([0x00, 0x05, 0, 0, 0, 0, 0, 0, 0, 0], 0);
+  assert!{Level::Two.is_valid(&mac)};
+  let mac = Level::Two.load([0x00, 0x04, 0, 0, 0, 0, 0, 0, 0, 0], 0);
+  assert_eq!{Level::Two.unmet(&mac), 1};
+}
+#[test]
+fn level_three_input() {
+  let mac = Level::Three.load([0; MACHINE_SIZE], 0);
+  assert_eq!{mac.get_inp().to_vec(), vec![1, 2, 3, 4, 5]};
+  assert_eq!{mac.get_inp().get_mode(), PortMode::Saturate};
+  // too few calls, input untouched, nothing written
+  assert_eq!{Level::Three.unmet(&mac), 8};
+}
+#[test]
+fn ruleset_endian() {
+  let image: MachineInner = [0x12, 0x34, 0x56, 0, 0, 0, 0, 0, 0, 0x0F];
+  assert_eq!{Endian::Big.apply(image), image};
+  assert_eq!{Endian::Little.apply(image), [0x21, 0x43, 0x65, 0, 0, 0, 0, 0, 0, 0xF0]};
+  let rules = Ruleset { level: Level::Two, isa: Isa::Extended, endian: Endian::Little };
+  let machine = rules.load(image, 0);
+  assert_eq!{machine.get_isa(), Isa::Extended};
+  assert_eq!{machine.get_reg().get_ip(), 0x2};
+  assert_eq!{machine.get_mem().get_loc(0).unwrap(), 0x6};
+}
+#[test]
+fn try_load() {
+  let mac = Level::Two.try_load(&[0x00, 0x05, 0, 0, 0, 0, 0, 0, 0, 0], 0).unwrap();
+  assert!{Level::Two.is_valid(&mac)};
+  assert!{Level::Two.try_load(&[0x00, 0x05], 0).is_err()};
+  // HF already set still wins when the rest is right
+  let mac = Level::Two.try_load(&[0x00, 0x85, 0, 0, 0, 0, 0, 0, 0, 0], 0).unwrap();
+  assert!{mac.get_reg().get_hf()};
+  assert!{Level::Two.is_valid(&mac)};
+  let rules = Ruleset { level: Level::Two, endian: Endian::Little, ..Default::default() };
+  let mac = rules.try_load(&[0x00, 0x58, 0, 0, 0, 0, 0, 0, 0, 0], 0).unwrap();
+  assert_eq!{rules.load([0x00, 0x58, 0, 0, 0, 0, 0, 0, 0, 0], 0).get_reg(), mac.get_reg()};
+}
@@ -6,6 +6,8 @@ extern crate pretty_env_logger;
 extern crate colored;
 extern crate bit_field;
 extern crate bytes;
+extern crate serde;
+extern crate serde_json;
 extern crate mio;
 extern crate tokio;
 extern crate tokio_core;
@@ -14,18 +16,65 @@ extern crate tokio_core;
 
 pub mod memory;
 pub mod registers;
+pub mod stack;
+pub mod interrupts;
+pub mod devices;
+pub mod ports;
 pub mod instructions;
 pub mod machine;
 pub mod state;
+pub mod level;
+pub mod solver;
+pub mod symbolic;
+pub mod analysis;
+pub mod cfg;
+pub mod trace;
+pub mod diff;
+pub mod journal;
+pub mod debugger;
+pub mod snapshot;
+pub mod image;
+pub mod faults;
+pub mod batch;
 
 pub const MAX_VALUE: u8 = 0b1111; // 0xF 15
 
+// an error and each of its sources, outermost first, "a: b: c"
+pub fn error_chain(err: &dyn std::error::Error) -> String {
+  let mut text = err.to_string();
+  let mut source = err.source();
+  while let Some(e) = source {
+    text.push_str(": ");
+    text.push_str(&e.to_string());
+    source = e.source();
+  }
+  text
+}
+
 pub mod prelude {
+  pub use crate::error_chain;
   pub use crate::memory::*;
   pub use crate::registers::*;
+  pub use crate::stack::*;
+  pub use crate::interrupts::*;
+  pub use crate::devices::*;
+  pub use crate::ports::*;
   pub use crate::instructions::*;
   pub use crate::machine::*;
   pub use crate::state::*;
+  pub use crate::level::*;
+  pub use crate::solver::*;
+  pub use crate::symbolic::*;
+  pub use crate::analysis::*;
+  pub use crate::cfg::*;
+  pub use crate::trace::*;
+  pub use crate::diff::*;
+  pub use crate::journal::*;
+  pub use crate::debugger::*;
+  pub use crate::snapshot::*;
+  pub use crate::image::*;
+  pub use crate::faults::*;
+  pub use crate::batch::*;
 }
 
 #[cfg(test)] use bit_field::*;
@@ -37,10 +86,10 @@ fn bits() {
   assert_eq!(bits.get_bits(0..4), 0b1100);
   assert_eq!(bits.get_bits(0..2), 0);
   assert_eq!(bits.get_bits(2..4), 0b11u8);
-  assert_eq!(bits.get_bit(0), false);
-  assert_eq!(bits.get_bit(1), false);
-  assert_eq!(bits.get_bit(2), true);
-  assert_eq!(bits.get_bit(3), true);
+  assert!(!bits.get_bit(0));
+  assert!(!bits.get_bit(1));
+  assert!(bits.get_bit(2));
+  assert!(bits.get_bit(3));
 }
 
 #[test]
@@ -0,0 +1,53 @@
+// proptest strategies for machines in any state an image and a run can reach
+use proptest::prelude::*;
+use proptest::collection::vec;
+use crate::machine::{Machine, MachineInner};
+use crate::instructions::{Instruction, Isa};
+use crate::interrupts::{Interrupts, Irq};
+use crate::ports::{PortMode, PORT_SIZE};
+use crate::stack::STACK_SIZE;
+
+pub fn nibble() -> impl Strategy<Value = u8> {
+  0..=crate::MAX_VALUE
+}
+pub fn image() -> impl Strategy<Value = MachineInner> {
+  any::<MachineInner>()
+}
+pub fn isa() -> impl Strategy<Value = Isa> {
+  prop_oneof![Just(Isa::Standard), Just(Isa::Extended)]
+}
+// anything either ISA can decode, CALL with any address
+pub fn instruction() -> impl Strategy<Value = Instruction> {
+  (any::<u8>(), nibble(), isa()).prop_map(|(byte, next, isa)| Instruction::decode(byte, next, isa))
+}
+fn interrupts() -> impl Strategy<Value = Interrupts> {
+  (any::<bool>(), any::<bool>(), any::<bool>(), nibble(), nibble())
+    .prop_map(|(enabled, timer_irq, input_irq, timer, vector)| {
+      let mut irq = Interrupts::default();
+      irq.set_enabled(enabled);
+      irq.set_mask(Irq::Timer, timer_irq);
+      irq.set_mask(Irq::Input, input_irq);
+      irq.set_timer(timer).unwrap();
+      irq.set_vector(vector).unwrap();
+      irq
+    })
+}
+// an image with stack, ports and interrupts already in use
+pub fn machine() -> impl Strategy<Value = Machine> {
+  let mode = prop_oneof![Just(PortMode::Saturate), Just(PortMode::Fault)];
+  (image(), isa(), mode, interrupts(),
+   vec(nibble(), 0..=STACK_SIZE), vec(nibble(), 0..=PORT_SIZE), vec(nibble(), 0..=PORT_SIZE))
+    .prop_map(|(image, isa, mode, irq, stack, inp, outp)| {
+      let mut machine = Machine::from(image).set_isa(isa).set_port_mode(mode).set_irq(irq);
+      for v in stack {
+        machine.get_mut_stack().push(v).unwrap();
+      }
+      for v in inp {
+        machine.push_inp(v).unwrap();
+      }
+      for v in outp {
+        machine.push_outp(v).unwrap();
+      }
+      machine
+    })
+}
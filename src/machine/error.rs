@@ -1,42 +1,77 @@
+use std::error::Error;
 use std::fmt;
+use serde::{Serialize, Deserialize};
 
+use crate::instructions::Instruction;
 use crate::instructions::InstructionError as InstErr;
-use crate::registers::RegisterError as RegErr;
-use crate::memory::MemoryError as MemErr;
 
 pub type MachineResult<M> = Result<M, MachineError>;
+#[derive(Clone,PartialEq)]
 pub enum MachineError {
-  InstructionError(InstErr),
-  RegisterError(RegErr),
-  MemoryError(MemErr),
-  UnknownError,
+  Fault(Fault),
+  InvalidImage(InvalidImage),
 }
-impl fmt::Debug for MachineError {
+// where a step failed, the instruction error is the source
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
+pub struct Fault {
+  pub ip: u8,                            // IP the step started at
+  pub instruction: Option<Instruction>,  // none for fetches and interrupts
+  pub step: usize,                       // steps completed before it
+  pub error: InstErr,
+}
+// why untrusted bytes were refused as a machine
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum InvalidImage {
+  Length(usize),
+}
+impl fmt::Display for Fault {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let _ = write!{f, "Step {} at {:#X}", self.step, self.ip};
+    match self.instruction {
+      Some(ref inst) => write!{f, " faulted in {}", String::from(inst)},
+      None => write!{f, " faulted"},
+    }
+  }
+}
+impl fmt::Debug for Fault {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "{}: {:?}", self, self.error}
+  }
+}
+impl Error for Fault {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    Some(&self.error)
+  }
+}
+impl fmt::Display for MachineError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    let _ = write!{f, "Machine Error: "};
     match *self {
-      MachineError::InstructionError(ref e) =>
-        write!{f, "{:?}", e},
-      MachineError::RegisterError(ref e) =>
-        write!{f, "{:?}", e},
-      MachineError::MemoryError(ref e) =>
-        write!{f, "{:?}", e},
-      MachineError::UnknownError => write!{f, "Unknown Error"},
+      MachineError::Fault(ref e) =>
+        write!{f, "{}", e},
+      MachineError::InvalidImage(InvalidImage::Length(l)) =>
+        write!{f, "Image is {} bytes, not {}", l, crate::machine::MACHINE_SIZE},
     }
   }
 }
-impl From<InstErr> for MachineError {
-  fn from(err: InstErr) -> MachineError {
-    MachineError::InstructionError(err)
+impl fmt::Debug for MachineError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      MachineError::Fault(ref e) => write!{f, "Machine Error: {:?}", e},
+      _ => write!{f, "Machine Error: {}", self},
+    }
   }
 }
-impl From<RegErr> for MachineError {
-  fn from(err: RegErr) -> MachineError {
-    MachineError::RegisterError(err)
+// a fault displays as itself, so its source is the instruction error
+impl Error for MachineError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match *self {
+      MachineError::Fault(ref e) => e.source(),
+      MachineError::InvalidImage(_) => None,
+    }
   }
 }
-impl From<MemErr> for MachineError {
-  fn from(err: MemErr) -> MachineError {
-    MachineError::MemoryError(err)
+impl From<Fault> for MachineError {
+  fn from(err: Fault) -> MachineError {
+    MachineError::Fault(err)
   }
 }
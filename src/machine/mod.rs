@@ -1,294 +1,701 @@
 #[cfg(test)] mod test;
+#[cfg(test)] pub(crate) mod arb;
 mod error;
 
 use std::fmt;
-use crate::memory::{Memory, MemoryInner, MEMORY_SIZE};
+use std::convert::TryFrom;
+use bit_field::*;
+use crate::memory::{Memory, MemoryInner, MemoryResult, MEMORY_SIZE};
+use crate::devices::Bus;
+use crate::ports::{Port, PortMode};
+use crate::level::{Level, Ruleset};
+use crate::faults::{FaultPolicy, FaultKind, FaultAction};
 use crate::registers::{Registers, RegisterInner, REGISTER_SIZE};
-use crate::instructions::Instruction;
+use crate::stack::Stack;
+use crate::trace::{Trace, Step, StepHook, PortOp};
+use crate::journal::{Journal, Delta};
+use crate::snapshot::{Snapshot, PortState, SnapshotResult};
+use crate::interrupts::{Interrupts, Irq, INTERRUPT_CYCLES};
+use crate::instructions::{Instruction, Isa, Outcome, DecodeTable, InstCache};
 use crate::instructions::InstructionError as InstErr;
 use crate::instructions::InstructionResult as InstRes;
 use self::error::MachineResult as MacRes;
 
 pub use self::error::MachineError;
+pub use self::error::Fault;
+pub use self::error::InvalidImage;
 pub use self::error::MachineResult;
-pub const PORT_SIZE: usize = 5;
+pub use crate::ports::{PORT_SIZE, PortStorage};
 pub const MACHINE_SIZE: usize = MEMORY_SIZE + REGISTER_SIZE;
 pub const MAX_CALLS: usize = 100;
 
 pub type MachineInner = [u8; MACHINE_SIZE];
-pub type PortStorage = [u8; PORT_SIZE];
+pub const IMAGE_NIBBLES: usize = MACHINE_SIZE * 2;
+pub const MEMORY_OFFSET: usize = REGISTER_SIZE * 2; // first memory nibble in an image
 
+// nibble idx of an image or memory, high nibble of each byte first
+pub fn get_nibble(bytes: &[u8], idx: usize) -> u8 {
+  match idx % 2 {
+    0 => bytes[idx / 2].get_bits(4..8),
+    _ => bytes[idx / 2].get_bits(0..4),
+  }
+}
+pub fn set_nibble(bytes: &mut [u8], idx: usize, value: u8) {
+  match idx % 2 {
+    0 => bytes[idx / 2].set_bits(4..8, value.get_bits(0..4)),
+    _ => bytes[idx / 2].set_bits(0..4, value.get_bits(0..4)),
+  };
+}
+
+// Why a run stopped
+#[derive(Clone,PartialEq,Debug)]
+pub enum HaltReason {
+  Halted,          // HF set, by HLT or a halting fault policy
+  Budget,          // every call in the budget used
+  Fault(Fault),    // a fault was raised
+  Breakpoint(u8),  // a caller's check stopped it at IP
+  Stopped,         // a caller's check stopped it
+}
+impl fmt::Display for HaltReason {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HaltReason::Halted => write!{f, "halted"},
+      HaltReason::Budget => write!{f, "out of calls"},
+      HaltReason::Fault(ref e) => write!{f, "{}", crate::error_chain(e)},
+      HaltReason::Breakpoint(ip) => write!{f, "breakpoint at {:#X}", ip},
+      HaltReason::Stopped => write!{f, "stopped"},
+    }
+  }
+}
+
+// How a machine turns memory into instructions
+#[derive(Clone,Copy,PartialEq,Debug,Default)]
+pub enum Engine {
+  #[default]
+  Interpreter, // decodes at every step
+  Cached,      // decodes each IP once, until a store writes over it
+}
+
+#[derive(Clone)]
 pub struct Machine {
   reg: Registers,
   mem: Memory,
-  inp: PortStorage,
-  outp: PortStorage,
+  stack: Stack,
+  ruleset: Ruleset,
+  irq: Interrupts,
+  inp: Port,
+  outp: Port,
   call_count: usize,
-  #[allow(dead_code)]
-  error: InstRes<()>,
+  cycle_count: usize,
+  trace: Option<Trace>,
+  hook: Option<StepHook>,
+  cache: Option<InstCache>, // only with the cached engine
+  journal: Option<Journal>,
+  faults: FaultPolicy,
+  budget: usize,
+  error: Option<Fault>, // fault that stopped the machine
 }
 impl Default for Machine {
   fn default() -> Self {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::default()"};
     Machine {
       reg: Registers::default(),
       mem: Memory::default(),
-      inp: [0; PORT_SIZE],
-      outp: [0; PORT_SIZE],
+      stack: Stack::default(),
+      ruleset: Ruleset::default(),
+      irq: Interrupts::default(),
+      inp: Port::default(),
+      outp: Port::default(),
       call_count: 0,
-      error: Ok(()),
+      cycle_count: 0,
+      trace: None,
+      hook: None,
+      cache: None,
+      journal: None,
+      faults: Level::current().faults(),
+      budget: MAX_CALLS,
+      error: None,
     }
   }
 }
 impl From<MachineInner> for Machine {
   fn from(slice: MachineInner) -> Self {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::from::<slice>()"};
-    let mut reg: [u8; REGISTER_SIZE] = [0; REGISTER_SIZE];
-    for idx in 0..REGISTER_SIZE {
-      reg[idx] = slice[idx];
-    }
-    let mut mem: [u8; MEMORY_SIZE] = [0; MEMORY_SIZE];
-    for idx in REGISTER_SIZE..REGISTER_SIZE+MEMORY_SIZE {
-      mem[idx-REGISTER_SIZE] = slice[idx];
-    }
-    let reg: RegisterInner = reg.into();
-    let mem: MemoryInner = mem.into();
+    let mut reg: RegisterInner = [0; REGISTER_SIZE];
+    reg.copy_from_slice(&slice[..REGISTER_SIZE]);
+    let mut mem: MemoryInner = [0; MEMORY_SIZE];
+    mem.copy_from_slice(&slice[REGISTER_SIZE..REGISTER_SIZE+MEMORY_SIZE]);
     Machine {
       reg: reg.into(),
       mem: mem.into(),
-      #[cfg(not(feature="lvl3"))] inp: [0; PORT_SIZE],
-      #[cfg(feature="lvl3")] inp: [1,2,3,4,5],
-      outp: [0; PORT_SIZE],
+      stack: Stack::default(),
+      ruleset: Ruleset::default(),
+      irq: Interrupts::default(),
+      inp: Port::default(),
+      outp: Port::default(),
       call_count: 0,
-      error: Ok(()),
+      cycle_count: 0,
+      trace: None,
+      hook: None,
+      cache: None,
+      journal: None,
+      faults: Level::current().faults(),
+      budget: MAX_CALLS,
+      error: None,
     }
   }
 }
-impl From<Vec<u8>> for Machine {
-  fn from(bytes: Vec<u8>) -> Self {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::from::<Vec<u8>>()"};
+impl<'a> From<&'a Machine> for MachineInner {
+  fn from(machine: &'a Machine) -> MachineInner {
     let mut slice: MachineInner = [0; MACHINE_SIZE];
-    for idx in 0..slice.len() {
-      slice[idx] = bytes[idx];
+    let reg: RegisterInner = machine.reg.clone().into();
+    slice[..REGISTER_SIZE].copy_from_slice(&reg);
+    slice[REGISTER_SIZE..].copy_from_slice(machine.mem.get_all());
+    slice
+  }
+}
+// Untrusted bytes, exactly one image
+impl<'a> TryFrom<&'a [u8]> for Machine {
+  type Error = MachineError;
+  fn try_from(bytes: &'a [u8]) -> MacRes<Machine> {
+    if bytes.len() != MACHINE_SIZE {
+      return Err(MachineError::InvalidImage(InvalidImage::Length(bytes.len())))
     }
-    slice.into()
+    let mut slice: MachineInner = [0; MACHINE_SIZE];
+    slice.copy_from_slice(bytes);
+    Ok(Machine::from(slice))
+  }
+}
+impl TryFrom<Vec<u8>> for Machine {
+  type Error = MachineError;
+  fn try_from(bytes: Vec<u8>) -> MacRes<Machine> {
+    Machine::try_from(&bytes[..])
   }
 }
 impl fmt::Debug for Machine {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::debug()"};
-    write!{f, "Machine: calls = {}\n{:?}\n{:?}\n{:?}\n{:?}", self.call_count, self.reg, self.mem, self.inp, self.outp}
+    write!{f, "Machine: calls = {}, cycles = {}, isa = {:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}",
+      self.call_count, self.cycle_count, self.ruleset.isa, self.reg, self.mem, self.stack, self.irq, self.inp, self.outp}
   }
 }
 impl Machine {
   pub fn get_reg(&self) -> &Registers {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_reg()"};
     &self.reg
   }
   pub fn get_mem(&self) -> &Memory {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_mem()"};
     &self.mem
   }
+  pub fn get_stack(&self) -> &Stack {
+    &self.stack
+  }
+  pub fn get_isa(&self) -> Isa {
+    self.ruleset.isa
+  }
+  pub fn get_irq(&self) -> &Interrupts {
+    &self.irq
+  }
   pub fn get_cc(&self) -> usize {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_cc()"};
     self.call_count
   }
+  pub fn get_cycles(&self) -> usize {
+    self.cycle_count
+  }
   pub fn get_mut_reg(&mut self) -> &mut Registers {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_mut_reg()"};
     &mut self.reg
   }
+  // anything may be written, so decoded instructions are forgotten
   pub fn get_mut_mem(&mut self) -> &mut Memory {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_mut_mem()"};
+    self.forget();
     &mut self.mem
   }
-  pub fn get_inp(&self) -> &PortStorage {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_inp()"};
+  pub fn get_mut_stack(&mut self) -> &mut Stack {
+    &mut self.stack
+  }
+  pub fn get_mut_irq(&mut self) -> &mut Interrupts {
+    &mut self.irq
+  }
+  pub fn get_inp(&self) -> &Port {
     &self.inp
   }
-  pub fn get_outp(&self) -> &PortStorage {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_outp()"};
+  pub fn get_outp(&self) -> &Port {
     &self.outp
   }
   pub fn get_mut_cc(&mut self) -> &mut usize {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::get_mut_cc()"};
     &mut self.call_count
   }
+  pub fn get_mut_cycles(&mut self) -> &mut usize {
+    &mut self.cycle_count
+  }
   pub fn set_reg<R>(mut self, reg: R) -> Self
     where R: Into<Registers>
   {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::set_reg()"};
     self.reg = reg.into();
     self
   }
   pub fn set_mem<M>(mut self, mem: M) -> Self
     where M: Into<Memory>
   {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::set_mem()"};
     self.mem = mem.into();
+    self.forget();
+    self
+  }
+  pub fn set_isa(mut self, isa: Isa) -> Self {
+    self.ruleset.isa = isa;
+    self.forget();
+    self
+  }
+  pub fn get_ruleset(&self) -> Ruleset {
+    self.ruleset
+  }
+  // recorded for snapshots, the image is loaded already
+  pub fn set_ruleset(mut self, ruleset: Ruleset) -> Self {
+    self.ruleset = ruleset;
+    self.forget();
+    self
+  }
+  // the fault that stopped the machine, if any
+  pub fn get_error(&self) -> Option<&Fault> {
+    self.error.as_ref()
+  }
+  pub fn get_faults(&self) -> FaultPolicy {
+    self.faults
+  }
+  pub fn set_faults(mut self, faults: FaultPolicy) -> Self {
+    self.faults = faults;
+    self
+  }
+  pub fn get_budget(&self) -> usize {
+    self.budget
+  }
+  pub fn set_budget(mut self, budget: usize) -> Self {
+    self.budget = budget;
+    self
+  }
+  pub fn set_bus(mut self, bus: Bus) -> Self {
+    self.mem = self.mem.set_bus(bus);
+    self.forget();
+    self
+  }
+  pub fn set_inp(mut self, inp: Port) -> Self {
+    let mode = self.inp.get_mode();
+    self.inp = inp.set_mode(mode);
+    self
+  }
+  pub fn set_irq(mut self, irq: Interrupts) -> Self {
+    self.irq = irq;
     self
   }
   pub fn set_cc<U>(mut self, calls: U) -> Self
     where U: Into<usize>
   {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::set_cc()"};
     self.call_count = calls.into();
     self
   }
-  pub fn pop_inp(&mut self) -> u8 {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::pop_inp()"};
-    let val = self.inp[0]; // grab 0th elem
-    #[cfg(not(feature = "lvl3"))]
-    debug!{"Get val: {}", val};
-    let inp: PortStorage = self.inp.iter().enumerate()
-      .fold([0; PORT_SIZE], |mut acc, (i,v)| {
-        if i != 0 { // drop 0th
-          acc[i-1] = *v; // shift all left 1
+  pub fn set_cycles<U>(mut self, cycles: U) -> Self
+    where U: Into<usize>
+  {
+    self.cycle_count = cycles.into();
+    self
+  }
+  pub fn set_port_mode(mut self, mode: PortMode) -> Self {
+    self.inp = self.inp.set_mode(mode);
+    self.outp = self.outp.set_mode(mode);
+    self
+  }
+  // records every following step, starting from the current image
+  pub fn set_recording(mut self, on: bool) -> Self {
+    self.trace = match on {
+      true => Some(Trace::new((&self).into())),
+      false => None,
+    };
+    self
+  }
+  pub fn get_engine(&self) -> Engine {
+    match self.cache {
+      Some(_) => Engine::Cached,
+      None => Engine::Interpreter,
+    }
+  }
+  pub fn set_engine(mut self, engine: Engine) -> Self {
+    self.cache = match engine {
+      Engine::Cached => Some(InstCache::default()),
+      Engine::Interpreter => None,
+    };
+    self
+  }
+  // instructions the cached engine has decoded and not forgotten
+  pub fn get_cache(&self) -> Option<&InstCache> {
+    self.cache.as_ref()
+  }
+  fn forget(&mut self) {
+    if let Some(cache) = self.cache.as_mut() {
+      cache.clear();
+    }
+  }
+  // called after every step, log_step logs them
+  pub fn set_hook(mut self, hook: Option<StepHook>) -> Self {
+    self.hook = hook;
+    self
+  }
+  pub fn get_trace(&self) -> Option<&Trace> {
+    self.trace.as_ref()
+  }
+  // stops recording
+  pub fn take_trace(&mut self) -> Option<Trace> {
+    self.trace.take()
+  }
+  // keeps an undo entry for every following step
+  pub fn set_journal(mut self, on: bool) -> Self {
+    self.journal = match on {
+      true => Some(Journal::new()),
+      false => None,
+    };
+    self
+  }
+  pub fn get_journal(&self) -> Option<&Journal> {
+    self.journal.as_ref()
+  }
+  // undoes the last journaled step, false when there is none
+  pub fn step_back(&mut self) -> bool {
+    let delta = match self.journal.as_mut().and_then(|j| j.pop()) {
+      Some(delta) => delta,
+      None => return false,
+    };
+    self.undo(delta);
+    if let Some(trace) = self.trace.as_mut() {
+      trace.unrecord();
+    }
+    true
+  }
+  // back to the state after step journaled steps, false if that is ahead
+  pub fn rewind_to(&mut self, step: usize) -> bool {
+    match self.journal.as_ref() {
+      Some(journal) if step <= journal.len() => {
+        while self.journal.as_ref().map_or(0, |j| j.len()) > step {
+          self.step_back();
         }
-        acc
-      });
+        true
+      },
+      _ => false,
+    }
+  }
+  fn undo(&mut self, delta: Delta) {
+    self.forget();
+    self.reg = delta.regs.into();
+    self.call_count = delta.calls;
+    self.error = delta.error;
+    self.cycle_count = delta.cycles;
+    if let Some(inner) = delta.mem {
+      let mem = std::mem::take(&mut self.mem);
+      self.mem = mem.set_all(inner);
+    }
+    if let Some(bus) = delta.bus {
+      let mem = std::mem::take(&mut self.mem);
+      self.mem = mem.set_bus(bus);
+    }
+    self.mem.set_access(delta.access.0, delta.access.1);
+    if let Some(stack) = delta.stack { self.stack = stack; }
+    if let Some(irq) = delta.irq { self.irq = irq; }
+    if let Some(inp) = delta.inp { self.inp = inp; }
+    if let Some(outp) = delta.outp { self.outp = outp; }
+  }
+  // full state, see snapshot for the formats
+  pub fn snapshot(&self) -> Snapshot {
+    let port = |p: &Port| PortState {
+      mode: p.get_mode(),
+      values: p.to_vec(),
+      underflow: p.underflowed(),
+      overflow: p.overflowed(),
+    };
+    Snapshot {
+      image: self.into(),
+      ruleset: self.ruleset,
+      calls: self.call_count,
+      cycles: self.cycle_count,
+      budget: self.budget,
+      faults: self.faults,
+      stack: self.stack.get_all().to_vec(),
+      irq: self.irq.to_bytes(),
+      inp: port(&self.inp),
+      outp: port(&self.outp),
+      error: self.get_error().cloned(),
+    }
+  }
+  // replaces everything but attached devices, restarting any journal or trace
+  pub fn restore(&mut self, snap: &Snapshot) -> SnapshotResult<()> {
+    let mut stack = Stack::default();
+    for v in snap.stack.iter() {
+      stack.push(*v).map_err(InstErr::from)?;
+    }
+    let irq = Interrupts::from_bytes(snap.irq).map_err(InstErr::from)?;
+    // fault mode so values beyond the port size are refused
+    let port = |state: &PortState| -> SnapshotResult<Port> {
+      let mut port = Port::default().set_mode(PortMode::Fault);
+      for v in state.values.iter() {
+        port.push(*v).map_err(InstErr::from)?;
+      }
+      Ok(port.set_mode(state.mode).set_flags(state.underflow, state.overflow))
+    };
+    let (inp, outp) = (port(&snap.inp)?, port(&snap.outp)?);
+    let mut reg: RegisterInner = [0; REGISTER_SIZE];
+    reg.copy_from_slice(&snap.image[..REGISTER_SIZE]);
+    let mut mem: MemoryInner = [0; MEMORY_SIZE];
+    mem.copy_from_slice(&snap.image[REGISTER_SIZE..]);
+    self.reg = reg.into();
+    self.mem = std::mem::take(&mut self.mem).set_all(mem);
+    self.stack = stack;
+    self.ruleset = snap.ruleset;
+    self.irq = irq;
     self.inp = inp;
-    val
-  }
-  pub fn push_outp(&mut self, val: u8) {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::push_outp()"};
-    let mut out: PortStorage = self.outp.iter().enumerate()
-      .fold([0; PORT_SIZE], |mut acc, (i,v)| { // shift all right 1, drop last item
-        if i+1 < acc.len() {
-          acc[i+1] = *v;
-        }
-        acc
-      });
-    #[cfg(not(feature = "lvl3"))]
-    debug!{"Push val: {}", val};
-    out[0] = val; // push is 0th elem
-    self.outp = out;
+    self.outp = outp;
+    self.call_count = snap.calls;
+    self.cycle_count = snap.cycles;
+    self.budget = snap.budget;
+    self.faults = snap.faults;
+    self.error = snap.error.clone();
+    self.forget();
+    if self.journal.is_some() {
+      self.journal = Some(Journal::new());
+    }
+    if self.trace.is_some() {
+      self.trace = Some(Trace::new((&*self).into()));
+    }
+    Ok(())
+  }
+  pub fn pop_inp(&mut self) -> InstRes<u8> {
+    let val = self.inp.pop()?;
+    Ok(val)
+  }
+  // input arriving from outside the machine
+  pub fn push_inp(&mut self, val: u8) -> InstRes<()> {
+    self.inp.push(val)?;
+    Ok(())
+  }
+  pub fn inp_ready(&self) -> bool {
+    !self.inp.is_empty()
   }
+  pub fn push_outp(&mut self, val: u8) -> InstRes<()> {
+    self.outp.push(val)?;
+    Ok(())
+  }
+  // calls run, faults as errors
   pub fn exec(&mut self) -> MacRes<usize> {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::exec()"};
+    match self.run() {
+      HaltReason::Fault(f) => Err(MachineError::Fault(f)),
+      _ => Ok(self.call_count),
+    }
+  }
+  pub fn run(&mut self) -> HaltReason {
+    self.run_until(|_| None)
+  }
+  // runs until the machine stops or check returns a reason before a step
+  pub fn run_until<F>(&mut self, mut check: F) -> HaltReason
+    where F: FnMut(&Machine) -> Option<HaltReason>
+  {
     loop {
-      if self.reg.get_hf() || MAX_CALLS <= self.call_count {
-        break;
+      if let Some(reason) = self.get_halt_reason() {
+        break reason;
       }
-      let inst: Instruction = self.current_instruction();
-      match inst.call(self) {
-        Ok(()) => { /* Nothing to do, is ok */ },
-        // JZE, JNE - legal - state of zf
-        Err(InstErr::JumpNotTaken) => {
-          #[cfg(not(feature = "lvl3"))]
-          debug!{"Jump not taken: {:#X}\tContinuing...", self.reg.get_ip()};
-        },
-        // on conversion from u8, not applicable
-        Err(InstErr::InvalidInstruction(i)) => {
-          #[cfg(not(feature = "lvl3"))]
-          debug!{"Halting II: {:#X}", i};
-          self.reg.set_hf(true);
-          // do not error on level 1
-          #[cfg(not(feature="lvl1"))] let err = InstErr::InvalidInstruction(i).into();
-          #[cfg(not(feature="lvl1"))] return Err(err);
-        },
-        // memory access only
-        Err(InstErr::OutOfBounds(a)) => {
-          #[cfg(not(feature = "lvl3"))]
-          debug!{"Halting OOB: {:#X}", a};
-          self.reg.set_hf(true);
-          let err = InstErr::OutOfBounds(a).into();
-          return Err(err);
-        },
-        // memory or register setting value
-        Err(InstErr::ValueTooLarge(v)) => {
-          #[cfg(not(feature = "lvl3"))]
-          debug!{"Halting VTL: {:#X}", v};
-          self.reg.set_hf(true);
-          let err = InstErr::ValueTooLarge(v).into();
-          return Err(err);
-        },
-        //
-        Err(InstErr::MathError(m)) => {
-          #[cfg(not(feature = "lvl3"))]
-          debug!{"Halting Math: {:#X}", m}
-          self.reg.set_hf(true);
-          let err = InstErr::MathError(m).into();
-          return Err(err);
-        },
+      if let Some(reason) = check(self) {
+        break reason;
+      }
+      if let Err(MachineError::Fault(f)) = self.step() {
+        break HaltReason::Fault(f);
+      }
+    }
+  }
+  // halted, faulted or out of calls
+  pub fn is_stopped(&self) -> bool {
+    self.reg.get_hf() || self.budget <= self.call_count
+  }
+  // why the machine cannot step any more, none while it can
+  pub fn get_halt_reason(&self) -> Option<HaltReason> {
+    if let Some(ref f) = self.error {
+      return Some(HaltReason::Fault(f.clone()))
+    }
+    if self.reg.get_hf() {
+      return Some(HaltReason::Halted)
+    }
+    if self.budget <= self.call_count {
+      return Some(HaltReason::Budget)
+    }
+    None
+  }
+  // executes the instruction at IP then services timers, devices and interrupts
+  pub fn step(&mut self) -> MacRes<()> {
+    let res = match self.journal.take() {
+      Some(mut journal) => {
+        let delta = Delta::before(self);
+        let res = self.step_recorded();
+        journal.push(delta.finish(self));
+        self.journal = Some(journal);
+        res
+      },
+      None => self.step_recorded(),
+    };
+    if let Err(MachineError::Fault(ref f)) = res {
+      self.error = Some(f.clone());
+    }
+    res
+  }
+  // the interpreter only pays for observation when it is asked for
+  fn step_recorded(&mut self) -> MacRes<()> {
+    if self.trace.is_none() && self.hook.is_none() {
+      return self.step_inner().map(|_| ());
+    }
+    let ip = self.reg.get_ip();
+    let opcode = self.mem.peek_u8(ip as usize);
+    let before = *self.mem.get_all();
+    let res = self.step_inner();
+    let after = self.mem.get_all();
+    // STA changes at most one nibble
+    let write = (0..MEMORY_SIZE * 2).find_map(|n| {
+      let shift = if n % 2 == 0 { 4 } else { 0 };
+      let (old, new) = ((before[n / 2] >> shift) & 0xF, (after[n / 2] >> shift) & 0xF);
+      if old != new { Some((n as u8, new)) } else { None }
+    });
+    let port = match res {
+      Ok(Outcome::Input(v)) => Some(PortOp::In(v)),
+      Ok(Outcome::Output(v)) => Some(PortOp::Out(v)),
+      _ => None,
+    };
+    let regs: RegisterInner = self.reg.clone().into();
+    let step = Step { ip, opcode, regs, write, port, fault: res.is_err() };
+    if let Some(trace) = self.trace.as_mut() {
+      trace.record(step);
+    }
+    if let Some(hook) = self.hook {
+      hook(self, &step);
+    }
+    res.map(|_| ())
+  }
+  fn step_inner(&mut self) -> MacRes<Outcome> {
+    let inst: Instruction = match self.cache {
+      Some(_) => self.cached_instruction()?,
+      None => self.current_instruction()?,
+    };
+    self.execute(inst)
+  }
+  // the decoded instruction at IP, counting the reads a fetch makes
+  fn cached_instruction(&mut self) -> MacRes<Instruction> {
+    let ip = self.reg.get_ip();
+    if let Some(inst) = self.cache.as_ref().and_then(|c| c.get(ip)) {
+      let inst = inst.clone();
+      let nibbles = match self.ruleset.isa {
+        Isa::Extended => 3,
+        Isa::Standard => 2,
       };
-      self.call_count += 1;
+      self.mem.note_fetch(ip as usize, nibbles);
+      return Ok(inst)
+    }
+    let inst = self.current_instruction()?;
+    // devices can change what a fetch reads without a store
+    if self.mem.get_bus().is_empty() {
+      if let Some(cache) = self.cache.as_mut() {
+        cache.insert(ip, inst.clone());
+      }
+    }
+    Ok(inst)
+  }
+  // STA, forgetting the instructions that read the nibble
+  pub(crate) fn store(&mut self, addr: u8, val: u8) -> MemoryResult<()> {
+    self.mem.set_loc(addr as usize, val)?;
+    if let Some(cache) = self.cache.as_mut() {
+      cache.invalidate(addr);
+    }
+    Ok(())
+  }
+  // as step, decoding through a table and without journal, trace or hook
+  pub fn step_with(&mut self, table: &DecodeTable) -> MacRes<Outcome> {
+    let res = self.fetch_from(table).and_then(|inst| self.execute(inst));
+    if let Err(MachineError::Fault(ref f)) = res {
+      self.error = Some(f.clone());
     }
-    #[cfg(not(feature = "lvl3"))]
-    debug!{"Machine exec ended: {} calls", self.call_count};
-    Ok(self.call_count)
+    res
   }
-  pub fn current_instruction(&self) -> Instruction {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Machine::current_instruction()"};
+  // the rest of a step once its instruction is fetched
+  fn execute(&mut self, inst: Instruction) -> MacRes<Outcome> {
     let ip = self.reg.get_ip();
-    let inst = self.mem.get_loc_u8(ip as usize).unwrap();
-    let inst: Instruction = inst.into();
-    #[cfg(not(feature = "lvl3"))]
-    debug!{"Fetched: {:?}", inst}
-    inst
+    let outcome = match inst.call(self) {
+      Ok(outcome) => outcome,
+      Err(e) => self.handle(ip, &inst, e)?,
+    };
+    // JZE, JNZ cost more when taken
+    let taken = matches!{outcome, Outcome::Jumped(_)};
+    self.call_count += 1;
+    self.cycle_count += inst.cycles(taken);
+    self.irq.tick();
+    self.mem.get_mut_bus().tick();
+    if !self.reg.get_hf() {
+      if let Some(irq) = self.irq.next(self.inp_ready()) {
+        if let Err(e) = self.interrupt(irq) {
+          self.reg.set_hf(true);
+          return Err(self.fault(ip, None, e));
+        }
+      }
+    }
+    Ok(outcome)
   }
-  #[cfg(feature="lvl1")]
-  pub fn is_valid(&self) -> bool {
-    trace!{"Machine::is_valid(1)"};
-    let mem = self.mem.get_all();
-    self.call_count < 10 &&
-    self.reg.get_ip() > 0x4 &&
-    self.reg.get_ip() < 0x8 &&
-    self.reg.get_ac() == 4 &&
-    mem[0] == '0' as u8 &&
-    mem[1] == 'B' as u8 &&
-    mem[2] == 'y' as u8 &&
-    mem[3] == 't' as u8 &&
-    mem[4] == 'e' as u8 &&
-    mem[5] == 'C' as u8 &&
-    mem[6] == 'T' as u8 &&
-    mem[7] == 'F' as u8
-  }
-  #[cfg(feature="lvl2")]
-  pub fn is_valid(&self) -> bool {
-    trace!{"Machine::is_valid(2)"};
-    self.call_count < 100 && self.reg.get_ac() == 5
+  // applies the fault policy, anything but raise lets the step finish
+  fn handle(&mut self, ip: u8, inst: &Instruction, err: InstErr) -> MacRes<Outcome> {
+    let action = self.faults.get(FaultKind::of(&err));
+    let next = (ip + inst.size()) % (crate::MAX_VALUE + 1);
+    let res = match action {
+      FaultAction::Raise => Err(err),
+      FaultAction::Halt => {
+        self.reg.set_hf(true);
+        Ok(Outcome::Halted)
+      },
+      FaultAction::Nop => self.reg.set_ip(next)
+        .map(|_| Outcome::Continue).map_err(InstErr::from),
+      FaultAction::Trap(addr) => self.stack.push(next).map_err(InstErr::from)
+        .and_then(|_| self.reg.set_ip(addr).map_err(InstErr::from))
+        .map(|_| Outcome::Jumped(addr)),
+    };
+    res.map_err(|e| {
+      self.reg.set_hf(true);
+      self.fault(ip, Some(inst.clone()), e)
+    })
   }
-  #[cfg(feature="lvl3")]
-  pub fn is_valid(&self) -> bool {
-    trace!{"Machine::is_valid(3)"};
-    let inp = self.get_inp();
-    let outp = self.get_outp();
-    self.call_count < 100 &&
-    self.call_count > 40 &&
-    inp[0] == 0 &&
-    inp[1] == 0 &&
-    inp[2] == 0 &&
-    inp[3] == 0 &&
-    inp[4] == 0 &&
-    outp[3] == 2 &&
-    outp[1] == 4 &&
-    outp[4] == 1 &&
-    outp[2] == 3 &&
-    outp[0] == 5
-  }
-  // when building without flags
-  #[cfg(not(any(feature="lvl1", feature="lvl2", feature="lvl3")))]
+  // context for an error raised by the step that started at ip
+  fn fault(&self, ip: u8, instruction: Option<Instruction>, error: InstErr) -> MachineError {
+    MachineError::Fault(Fault { ip, instruction, step: self.call_count, error })
+  }
+  // push IP and enter the handler at the vector
+  fn interrupt(&mut self, irq: Irq) -> InstRes<()> {
+    let ip = self.reg.get_ip();
+    self.stack.push(ip)?;
+    self.irq.acknowledge(irq);
+    self.reg.set_ip(self.irq.get_vector())?;
+    self.cycle_count += INTERRUPT_CYCLES;
+    Ok(())
+  }
+  pub fn current_instruction(&self) -> MacRes<Instruction> {
+    let (inst, next) = self.fetch()?;
+    Ok(Instruction::decode(inst, next, self.ruleset.isa))
+  }
+  // as current_instruction, decoding through a table
+  pub fn fetch_from(&self, table: &DecodeTable) -> MacRes<Instruction> {
+    if table.get_isa() != self.ruleset.isa {
+      return self.current_instruction()
+    }
+    let (byte, next) = self.fetch()?;
+    match table.get(byte) {
+      Instruction::CALL(_) => Ok(Instruction::decode(byte, next, self.ruleset.isa)),
+      inst => Ok(inst.clone()),
+    }
+  }
+  // byte at IP and the nibble after it, devices see the same reads either way
+  fn fetch(&self) -> MacRes<(u8, u8)> {
+    let ip = self.reg.get_ip();
+    let fetch = |e| self.fault(ip, None, InstErr::Memory(e));
+    let inst = self.mem.get_loc_u8(ip as usize).map_err(fetch)?;
+    let next = match self.ruleset.isa { // only CALL looks past the fetched byte
+      Isa::Extended => self.mem.get_loc((ip as usize + 2) % 16).map_err(fetch)?,
+      Isa::Standard => 0,
+    };
+    Ok((inst, next))
+  }
+  // validated against the level this was built for
   pub fn is_valid(&self) -> bool {
-    trace!{"Machine::is_valid(0)"};
-    true
+    Level::current().is_valid(self)
   }
 }
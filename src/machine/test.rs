@@ -1,7 +1,10 @@
 use crate::memory::*;
 use crate::registers::*;
+use crate::devices::*;
+use crate::ports::*;
 #[allow(unused_imports)] use crate::instructions::*;
-use machine::*;
+use crate::machine::*;
+use proptest::prelude::*;
 
 #[test]
 fn default() {
@@ -16,8 +19,8 @@ fn mut_registers() {
   let reg = mac.get_mut_reg();
   let _ = reg.set_ip(1);
   let _ = reg.set_li(2);
+  let _ = reg.set_ac(4); // set_ac updates zf, so set fr after
   let _ = reg.set_fr(3);
-  let _ = reg.set_ac(4);
   assert_eq!{reg.get_ip(), 1};
   assert_eq!{reg.get_li(), 2};
   assert_eq!{reg.get_fr(), 3};
@@ -56,7 +59,7 @@ fn exec_halt() {
   assert_eq!{mac.get_cc(), 1};
   let reg = mac.get_reg();
   assert_eq!{reg.get_ip(), 0};
-  assert_eq!{reg.get_hf(), true};
+  assert!{reg.get_hf()};
 }
 
 #[test]
@@ -69,6 +72,7 @@ fn jmp_halt() {
   assert_eq!{mac.get_reg().get_ip(), 0x7};
 }
 
+#[test]
 fn jmp_jmp_halt() {
   let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
               0x1F, 0x00, 0x00, 0x01, 0x20, 0x00, 0x00, 0x00];
@@ -78,12 +82,293 @@ fn jmp_jmp_halt() {
   assert_eq!{mac.get_reg().get_ip(), 0x2};
 }
 
+#[test]
+#[cfg(not(any(feature = "lvl1", feature = "lvl3")))]
 fn byte_test() {
   //3430 4279 7443 5446
   let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
               0x34, 0x30, 0x42, 0x79, 0x74, 0x43, 0x54, 0x46];
   let mut mac: Machine = slice.into();
+  // jnz 0x4; lda 0x2; put, which faults below lvl3
+  assert!{mac.exec().is_err()};
+  assert_eq!{mac.get_cc(), 2};
+  assert_eq!{mac.get_reg().get_ip(), 0x6};
+  assert_eq!{mac.get_reg().get_ac(), 0x3};
+  assert!{mac.get_reg().get_hf()};
+}
+
+#[test]
+fn exec_cycles() {
+  // jmp 0x7; hlt
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x1F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+  let mut mac: Machine = slice.into();
   assert!{mac.exec().is_ok()};
-  assert_eq!{mac.get_cc(), 3}; // jmp 0x7; jmp 0x2; hlt
-  assert_eq!{mac.get_reg().get_ip(), 0x2};
+  assert_eq!{mac.get_cycles(), CYCLE_TABLE[0x1] + JUMP_TAKEN_CYCLES + CYCLE_TABLE[0x0]};
+}
+
+#[test]
+fn jump_not_taken_cycles() {
+  // zf clear: jze 0x7 not taken; hlt
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x27, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+  let mut mac: Machine = slice.into();
+  assert!{mac.exec().is_ok()};
+  assert_eq!{mac.get_cc(), 2};
+  assert_eq!{mac.get_cycles(), CYCLE_TABLE[0x2] + CYCLE_TABLE[0x0]};
+}
+
+#[test]
+fn call_ret() {
+  // call 0x8; hlt; ... 0x8: lda 0x1; ret
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x04, 0x80, 0x00, 0x00, 0x41, 0x01, 0x00, 0x00];
+  let mut mac: Machine = Machine::from(slice).set_isa(Isa::Extended);
+  assert!{mac.exec().is_ok()};
+  assert_eq!{mac.get_cc(), 4}; // call; lda; ret; hlt
+  assert_eq!{mac.get_reg().get_ip(), 0x3};
+  assert_eq!{mac.get_reg().get_ac(), 0x4};
+  assert!{mac.get_stack().is_empty()};
+}
+
+#[test]
+fn push_pop() {
+  // lda 0x3; push; fla; pop; hlt
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x43, 0x02, 0xF0, 0x30, 0x00, 0x00, 0x00, 0x00];
+  let mut mac: Machine = Machine::from(slice).set_isa(Isa::Extended);
+  assert!{mac.exec().is_ok()};
+  assert_eq!{mac.get_reg().get_ac(), 0x2};
+  assert!{mac.get_stack().is_empty()};
+}
+
+#[test]
+fn stack_underflow() {
+  // ret
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+  let mut mac: Machine = Machine::from(slice).set_isa(Isa::Extended);
+  assert!{mac.exec().is_err()};
+  assert!{mac.get_reg().get_hf()};
+}
+
+#[test]
+fn stack_overflow() {
+  // push; jmp 0x0
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x02, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+  let mut mac: Machine = Machine::from(slice).set_isa(Isa::Extended);
+  assert!{mac.exec().is_err()};
+  assert!{mac.get_stack().is_full()};
+  assert!{mac.get_reg().get_hf()};
+}
+
+#[test]
+fn timer_interrupt() {
+  // 0x0: ei; jmp 0x2 ... 0x8: lda 0x1; hlt
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x05, 0x12, 0x00, 0x00, 0x41, 0x00, 0x00, 0x00];
+  let mut irq = Interrupts::default();
+  irq.set_mask(Irq::Timer, true);
+  assert!{irq.set_timer(3).is_ok()};
+  assert!{irq.set_vector(0x8).is_ok()};
+  let mut mac: Machine = Machine::from(slice).set_isa(Isa::Extended).set_irq(irq);
+  assert!{mac.exec().is_ok()};
+  assert_eq!{mac.get_cc(), 5}; // ei; jmp; jmp; <irq> lda; hlt
+  assert_eq!{mac.get_reg().get_ac(), 0x5};
+  assert_eq!{mac.get_stack().get_all(), &[0x2]};
+  assert!{!mac.get_irq().is_enabled()};
+}
+
+#[test]
+#[cfg(not(any(feature = "lvl1", feature = "lvl3")))]
+fn input_interrupt() {
+  // 0x0: ei; jmp 0x2 ... 0x8: get
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x05, 0x12, 0x00, 0x00, 0x60, 0x70, 0x00, 0x00];
+  let mut irq = Interrupts::default();
+  irq.set_mask(Irq::Input, true);
+  assert!{irq.set_vector(0x8).is_ok()};
+  let mut mac: Machine = Machine::from(slice).set_isa(Isa::Extended).set_irq(irq);
+  assert!{!mac.inp_ready()};
+  assert!{mac.push_inp(0x3).is_ok()};
+  assert!{mac.inp_ready()};
+  // GET is only wired up on lvl3, so the handler faults on entry
+  assert!{mac.exec().is_err()};
+  assert_eq!{mac.get_cc(), 1};
+  assert_eq!{mac.get_reg().get_ip(), 0x8};
+  assert_eq!{mac.get_stack().get_all(), &[0x2]};
+}
+
+#[test]
+fn device_bus() {
+  // lda 0x7; sta 0x6; hlt
+  let slice: [u8; MACHINE_SIZE] = [0x00, 0x00,
+              0x47, 0x56, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+  let bus = Bus::default()
+    .attach(0x7, TickCounter::default()).unwrap()
+    .attach(0x6, SevenSegment::default()).unwrap();
+  let mut mac: Machine = Machine::from(slice).set_bus(bus);
+  assert!{mac.exec().is_ok()};
+  assert_eq!{mac.get_reg().get_ac(), 0x0}; // no ticks before the first instruction
+  assert_eq!{mac.get_mem().get_bus().read(0x6), Some(0x0)};
+  assert_eq!{mac.get_mem().get_bus().read(0x7), Some(0x3)};
+}
+
+#[test]
+fn ports_emission_order() {
+  let mut mac = Machine::default();
+  for v in 1..=PORT_SIZE as u8 {
+    assert!{mac.push_outp(v).is_ok()};
+  }
+  assert_eq!{mac.get_outp().to_vec(), vec![1, 2, 3, 4, 5]};
+  assert!{mac.push_inp(0x7).is_ok()};
+  assert_eq!{mac.pop_inp().unwrap(), 0x7};
+  assert_eq!{mac.pop_inp().unwrap(), 0x0}; // saturate by default
+  assert!{mac.get_inp().underflowed()};
+}
+
+#[test]
+fn ports_fault_mode() {
+  let mut mac = Machine::default().set_port_mode(PortMode::Fault);
+  assert!{mac.pop_inp().is_err()};
+  for v in 1..=PORT_SIZE as u8 {
+    assert!{mac.push_outp(v).is_ok()};
+  }
+  assert!{mac.push_outp(0x6).is_err()};
+  assert!{!mac.get_outp().overflowed()};
+}
+
+#[test]
+fn try_from_bytes() {
+  use std::convert::TryFrom;
+  let slice: MachineInner = [0x60, 0x04, 0, 0, 0, 0, 0, 0, 0, 0];
+  let mac = Machine::try_from(&slice[..]).unwrap();
+  assert_eq!{MachineInner::from(&mac), slice};
+  assert_eq!{mac.current_instruction(), Ok(Instruction::HLT)};
+  let short = Machine::try_from(&slice[..9]).err();
+  assert_eq!{short, Some(MachineError::InvalidImage(InvalidImage::Length(9)))};
+  let long = Machine::try_from(vec![0; MACHINE_SIZE + 1]).err();
+  assert_eq!{long, Some(MachineError::InvalidImage(InvalidImage::Length(11)))};
+}
+
+#[test]
+fn fault_chain() {
+  use std::error::Error;
+  // ret with an empty stack, after a step that worked
+  let mut mac = Machine::from([0, 0, 0xB0, 0x10, 0, 0, 0, 0, 0, 0]).set_isa(Isa::Extended);
+  mac.step().unwrap();
+  let err = mac.step().unwrap_err();
+  assert_eq!{err.to_string(), "Step 1 at 0x1 faulted in Return"};
+  assert_eq!{err.source().map(|e| e.to_string()), Some("Stack fault".to_string())};
+  assert_eq!{crate::error_chain(&err), "Step 1 at 0x1 faulted in Return: Stack fault: Stack is empty, unable to pop"};
+  assert_eq!{mac.get_error().map(|f| f.ip), Some(1)};
+  assert!{mac.get_reg().get_hf()};
+}
+
+#[test]
+fn halt_reasons() {
+  // jmp 0 forever
+  let spin: MachineInner = [0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0];
+  let mut mac = Machine::from(spin).set_budget(7);
+  assert_eq!{mac.get_halt_reason(), None};
+  assert_eq!{mac.run(), HaltReason::Budget};
+  assert_eq!{mac.get_cc(), 7};
+  assert_eq!{Machine::from(spin).get_budget(), MAX_CALLS};
+  let mut mac = Machine::from([0; MACHINE_SIZE]);
+  assert_eq!{mac.run(), HaltReason::Halted};
+  assert_eq!{mac.exec(), Ok(1)};
+  // stopped from outside, it can carry on later
+  let mut mac = Machine::from(spin);
+  let stop = |m: &Machine| if m.get_cc() == 3 { Some(HaltReason::Stopped) } else { None };
+  assert_eq!{mac.run_until(stop), HaltReason::Stopped};
+  assert_eq!{(mac.get_cc(), mac.get_halt_reason()), (3, None)};
+  assert_eq!{mac.run().to_string(), "out of calls"};
+}
+#[test]
+fn cached_store_over_code() {
+  // jmp 4; sta 4; jmp 2, the store turns the cached jmp 2 into hlt
+  let image: MachineInner = [0, 0, 0x14, 0x54, 0x12, 0x00, 0, 0, 0, 0];
+  let mut mac = Machine::from(image).set_engine(Engine::Cached);
+  assert_eq!{mac.get_engine(), Engine::Cached};
+  assert_eq!{mac.run(), HaltReason::Halted};
+  assert_eq!{mac.get_cc(), 4};
+  let cache = mac.get_cache().unwrap();
+  assert_eq!{cache.get(4), Some(&Instruction::HLT)};
+  assert_eq!{cache.get(0), Some(&Instruction::JMP(4))};
+  // outside writes forget everything
+  mac.get_mut_mem().set_loc(0, 0).unwrap();
+  assert!{mac.get_cache().unwrap().is_empty()};
+  // call 3; sta 2; jmp 0, the store rewrites the call's address to 7 where hlt is
+  let image: MachineInner = [0x00, 0x07, 0x04, 0x35, 0x21, 0x00, 0, 0, 0, 0];
+  let mut mac = Machine::from(image).set_isa(Isa::Extended).set_engine(Engine::Cached);
+  assert_eq!{mac.run(), HaltReason::Halted};
+  assert_eq!{(mac.get_cc(), mac.get_reg().get_ip()), (5, 7)};
+}
+#[test]
+fn cached_matches_interpreter() {
+  use crate::level::{Level, Ruleset};
+  let rulesets = [
+    Ruleset::default(),
+    Ruleset { isa: Isa::Extended, ..Ruleset::default() },
+    Ruleset { level: Level::Three, isa: Isa::Extended, ..Ruleset::default() },
+  ];
+  let mut x: u32 = 0x9E37_79B9;
+  for ruleset in rulesets.iter() {
+    for _ in 0..2000 {
+      let mut image: MachineInner = [0; MACHINE_SIZE];
+      for b in image.iter_mut() {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        *b = x as u8;
+      }
+      image[1] &= 0x7F;
+      let mut reference = ruleset.load(image, 0);
+      let mut cached = ruleset.load(image, 0).set_engine(Engine::Cached);
+      // compared after every step, reads included
+      while !reference.is_stopped() && reference.get_error().is_none() {
+        assert_eq!{cached.step(), reference.step(), "{:02X?} {:?}", image, ruleset};
+        assert_eq!{cached.snapshot(), reference.snapshot(), "{:02X?} {:?}", image, ruleset};
+        assert_eq!{cached.get_mem().get_reads(), reference.get_mem().get_reads()};
+        assert_eq!{cached.get_mem().get_writes(), reference.get_mem().get_writes()};
+      }
+      assert_eq!{cached.get_halt_reason(), reference.get_halt_reason()};
+    }
+  }
+}
+// every value the machine holds fits a nibble
+fn assert_nibbles(mac: &Machine) {
+  let reg = mac.get_reg();
+  let regs = [reg.get_ip(), reg.get_li(), reg.get_fr(), reg.get_ac()];
+  let mem = (0..16).map(|n| mac.get_mem().get_loc(n).unwrap());
+  let irq = mac.get_irq();
+  let irqs = [irq.get_timer(), irq.get_reload(), irq.get_vector()];
+  let all: Vec<u8> = regs.iter().cloned().chain(mem)
+    .chain(mac.get_stack().get_all().iter().cloned())
+    .chain(mac.get_inp().iter()).chain(mac.get_outp().iter())
+    .chain(irqs.iter().cloned()).collect();
+  assert!{all.iter().all(|v| *v <= crate::MAX_VALUE), "{:?}", mac};
+}
+
+proptest!{
+  #[test]
+  fn steps_keep_nibbles(mut mac in arb::machine(), steps in 1..40usize) {
+    assert_nibbles(&mac);
+    for _ in 0..steps {
+      let (calls, cycles) = (mac.get_cc(), mac.get_cycles());
+      if mac.step().is_err() {
+        break;
+      }
+      assert_nibbles(&mac);
+      prop_assert_eq!{mac.get_cc(), calls + 1};
+      prop_assert!{mac.get_cycles() > cycles};
+    }
+  }
+  #[test]
+  fn image_round_trip(image in arb::image()) {
+    let mac = Machine::from(image);
+    assert_nibbles(&mac);
+    prop_assert_eq!{MachineInner::from(&mac), image};
+  }
 }
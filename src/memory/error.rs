@@ -1,22 +1,26 @@
+use std::error::Error;
 use std::fmt;
+use serde::{Serialize, Deserialize};
 
 pub type MemoryResult<M> = Result<M, MemoryError>;
-#[derive(Clone,PartialEq)]
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
 pub enum MemoryError {
   OutOfBounds(usize),
   ValueTooLarge(u8),
-  MathError(usize),
 }
-impl fmt::Debug for MemoryError {
+impl fmt::Display for MemoryError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    let _ = write!{f, "Memory Error: "};
     match *self {
       MemoryError::OutOfBounds(e) =>
         write!{f, "Address requested is out of bounds: {:X}", e},
       MemoryError::ValueTooLarge(e) =>
         write!{f, "Value provided is above 4 bits: {:X}", e},
-      MemoryError::MathError(e) =>
-        write!{f, "Invalid math operation: {:X}", e},
     }
   }
-}
\ No newline at end of file
+}
+impl fmt::Debug for MemoryError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Memory Error: {}", self}
+  }
+}
+impl Error for MemoryError {}
@@ -2,7 +2,9 @@
 mod error;
 
 use std::fmt;
+use std::cell::Cell;
 use bit_field::*;
+use crate::devices::Bus;
 use self::error::MemoryError as MemErr;
 use self::error::MemoryResult as MemRes;
 
@@ -12,63 +14,108 @@ pub const MEMORY_BITS: usize = 64;
 pub const MEMORY_SIZE: usize = 8;             // bytes // 64 bits
 pub type MemoryInner = [u8; MEMORY_SIZE];     // [u8; 8]
 
-#[derive(Clone,PartialEq)]
+#[derive(Clone)]
 pub struct Memory {
   inner: MemoryInner,
+  bus: Bus,
+  reads: Cell<u16>, // nibbles read before being written
+  writes: u16,      // nibbles written
+}
+// devices are not part of the memory contents
+impl PartialEq for Memory {
+  fn eq(&self, other: &Memory) -> bool {
+    self.inner == other.inner
+  }
 }
 impl Default for Memory {
   fn default() -> Self {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::default()"};
     Memory {
       inner: [0; MEMORY_SIZE],
+      bus: Bus::default(),
+      reads: Cell::new(0),
+      writes: 0,
     }
   }
 }
 impl fmt::Debug for Memory {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::debug()"};
     let mut print = format!{"Memory: {:?}", self.inner};
     for byte in 0..MEMORY_SIZE {
       print.push_str(&format!{"\n\t{:#X}> {:b} | 0x{:02X}", byte, self.inner[byte], self.inner[byte]});
     }
+    if !self.bus.is_empty() {
+      print.push_str(&format!{"\n{:?}", self.bus});
+    }
     write!{f, "{}", print}
   }
 }
 impl From<MemoryInner> for Memory {
   fn from(mem: MemoryInner) -> Self {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::from::<slice>()"};
-    Memory { inner: mem, }
+    Memory { inner: mem, ..Memory::default() }
   }
 }
-impl Into<MemoryInner> for Memory {
-  fn into(self) -> MemoryInner {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::into::<slice>()"};
-    self.inner
+impl From<Memory> for MemoryInner {
+  fn from(mem: Memory) -> MemoryInner {
+    mem.inner
   }
 }
 impl Memory {
   pub fn get_all(&self) -> &MemoryInner {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::get_all()"};
     &self.inner
   }
   pub fn set_all(mut self, memory: MemoryInner) -> Self {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::set_all()"};
     self.inner = memory;
+    self.clear_access();
+    self
+  }
+  // nibbles whose initial value was observed, as a bitmask by offset
+  pub fn get_reads(&self) -> u16 {
+    self.reads.get()
+  }
+  pub fn get_writes(&self) -> u16 {
+    self.writes
+  }
+  pub fn clear_access(&mut self) {
+    self.reads.set(0);
+    self.writes = 0;
+  }
+  // puts back counters saved before a step that is being undone
+  pub(crate) fn set_access(&mut self, reads: u16, writes: u16) {
+    self.reads.set(reads);
+    self.writes = writes;
+  }
+  fn note_read(&self, offset: usize) {
+    if !self.writes.get_bit(offset) {
+      let mut reads = self.reads.get();
+      reads.set_bit(offset, true);
+      self.reads.set(reads);
+    }
+  }
+  // reads of a fetch that was served without touching memory
+  pub(crate) fn note_fetch(&self, offset: usize, nibbles: usize) {
+    for n in 0..nibbles {
+      self.note_read((offset + n) % (MEMORY_SIZE * 2));
+    }
+  }
+  pub fn get_bus(&self) -> &Bus {
+    &self.bus
+  }
+  pub fn get_mut_bus(&mut self) -> &mut Bus {
+    &mut self.bus
+  }
+  pub fn set_bus(mut self, bus: Bus) -> Self {
+    self.bus = bus;
     self
   }
   pub fn get_loc(&self, offset: usize) -> MemRes<u8> {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::get_loc()"};
     let loc = offset / 2; // 4bit to u8 location
     if MEMORY_SIZE <= loc {
       return Err(MemErr::OutOfBounds(offset))
     }
+    if let Some(val) = self.bus.read(offset) {
+      return Ok(val)
+    }
+    self.note_read(offset);
     let val: u8 = self.inner[loc];
     match offset % 2 {
       0 => Ok(val.get_bits(4..8)),
@@ -77,33 +124,26 @@ impl Memory {
     }
   }
   pub fn get_loc_u8(&self, offset: usize) -> MemRes<u8> {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::get_loc_u8()"};
     if MEMORY_SIZE <= offset / 2 {
       return Err(MemErr::OutOfBounds(offset))
     }
-    match offset % 2 {
-      // even accesses are whole values
-      0 => Ok(self.inner[offset/2]),
-      1 => {
-        let mut v = 0;
-        // odd require grabbing low bits of current byte
-        v.set_bits(4..8, self.inner[offset/2].get_bits(0..4));
-        // then grabbing high of second byte
-        if offset == 15 {
-          // if low is 15, wrap to 0
-          v.set_bits(0..4, self.inner[0].get_bits(4..8));
-        } else {
-          v.set_bits(0..4, self.inner[offset/2+1].get_bits(4..8));
-        }
-        Ok(v)
-      },
-      _ => Err(MemErr::OutOfBounds(offset/2))
-    }
+    // both nibbles go through the bus, the low one wraps from 15 to 0
+    let high = self.get_loc(offset)?;
+    let low = self.get_loc((offset + 1) % (MEMORY_SIZE * 2))?;
+    Ok(high << 4 | low)
+  }
+  // the byte at offset as stored, without devices or read tracking
+  pub fn peek_u8(&self, offset: usize) -> u8 {
+    let nibble = |n: usize| {
+      let byte = self.inner[(n / 2) % MEMORY_SIZE];
+      match n % 2 {
+        0 => byte.get_bits(4..8),
+        _ => byte.get_bits(0..4),
+      }
+    };
+    nibble(offset) << 4 | nibble(offset + 1)
   }
   pub fn set_loc(&mut self, offset: usize, value: u8) -> MemRes<()> {
-    // #[cfg(not(feature = "lvl3"))]
-    trace!{"Memory::set_loc()"};
     let loc = offset / 2; // 4bit to u8 location
     if MEMORY_SIZE <= loc {
       return Err(MemErr::OutOfBounds(offset))
@@ -111,6 +151,9 @@ impl Memory {
     if crate::MAX_VALUE < value {
       return Err(MemErr::ValueTooLarge(value))
     }
+    if self.bus.write(offset, value) {
+      return Ok(())
+    }
     let mut val: u8 = self.inner[loc]; // take byte for modification
     match offset % 2 {
       0 => val.set_bits(4..8, value.get_bits(0..4)),
@@ -118,6 +161,7 @@ impl Memory {
       _ => return Err(MemErr::OutOfBounds(loc)),
     };
     self.inner[loc] = val;
+    self.writes.set_bit(offset, true);
     Ok(())
   }
 }
@@ -1,4 +1,6 @@
-use memory::*;
+use proptest::prelude::*;
+use crate::memory::*;
+use crate::devices::*;
 
 
 #[test]
@@ -78,4 +80,39 @@ fn into_slice() {
   let mem = Memory::default().set_all([0,1,2,3,4,5,6,7]);
   let slice: [u8; MEMORY_SIZE] = mem.into();
   assert_eq!{slice, [0,1,2,3,4,5,6,7]};
-}
\ No newline at end of file
+}
+
+#[test]
+fn device_access() {
+  let bus = Bus::default().attach(0x3, SevenSegment::default()).unwrap();
+  let mut mem = Memory::from([0xFF; MEMORY_SIZE]).set_bus(bus);
+  assert_eq!{mem.get_loc(0x3).unwrap(), 0};
+  assert!{mem.set_loc(0x3, 0x7).is_ok()};
+  assert_eq!{mem.get_loc(0x3).unwrap(), 0x7};
+  assert_eq!{mem.get_all()[1], 0xFF}; // backing byte untouched
+  assert_eq!{mem.get_loc_u8(0x2).unwrap(), 0xF7}; // fetch goes through the bus
+  assert_eq!{mem.get_loc_u8(0x3).unwrap(), 0x7F};
+  assert_eq!{mem.peek_u8(0x2), 0xFF}; // peeking leaves devices alone
+}
+#[test]
+fn access_tracking() {
+  let mut mem = Memory::default();
+  assert!{mem.get_loc(0x2).is_ok()};
+  assert!{mem.set_loc(0x4, 0x1).is_ok()};
+  assert!{mem.get_loc(0x4).is_ok()}; // written first, initial value unseen
+  assert!{mem.get_loc_u8(0xF).is_ok()}; // wraps to 0x0
+  assert_eq!{mem.get_reads(), 0b1000_0000_0000_0101};
+  assert_eq!{mem.get_writes(), 0b1_0000};
+  mem.clear_access();
+  assert_eq!{mem.get_reads(), 0};
+}
+
+proptest!{
+  #[test]
+  fn u8_is_two_nibbles(inner in any::<MemoryInner>(), offset in 0..16usize) {
+    let mem = Memory::from(inner);
+    let high = mem.get_loc(offset).unwrap();
+    let low = mem.get_loc((offset + 1) % 16).unwrap();
+    prop_assert_eq!{mem.get_loc_u8(offset).unwrap(), high << 4 | low};
+  }
+}
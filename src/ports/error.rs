@@ -0,0 +1,29 @@
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+pub type PortResult<P> = Result<P, PortError>;
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
+pub enum PortError {
+  Underflow,
+  Overflow(u8),
+  ValueTooLarge(u8),
+}
+impl fmt::Display for PortError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      PortError::Underflow =>
+        write!{f, "Port is empty, unable to read"},
+      PortError::Overflow(v) =>
+        write!{f, "Port is full, unable to write: {:X}", v},
+      PortError::ValueTooLarge(v) =>
+        write!{f, "Value provided is above 4 bits: {:X}", v},
+    }
+  }
+}
+impl fmt::Debug for PortError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Port Error: {}", self}
+  }
+}
+impl Error for PortError {}
@@ -0,0 +1,153 @@
+#[cfg(test)] mod test;
+mod error;
+
+use std::fmt;
+use std::ops::Index;
+use serde::{Serialize, Deserialize};
+use self::error::PortError as PortErr;
+use self::error::PortResult as PortRes;
+
+pub use self::error::PortError;
+pub use self::error::PortResult;
+pub const PORT_SIZE: usize = 5;
+
+pub type PortStorage = [u8; PORT_SIZE];
+
+// What a port does when read empty or written full
+#[derive(Clone,Copy,PartialEq,Debug,Default,Serialize,Deserialize)]
+pub enum PortMode {
+  // reads return 0 and writes drop the oldest value, setting a flag
+  #[default]
+  Saturate,
+  // reads and writes fail with a PortError
+  Fault,
+}
+
+// FIFO of nibbles, indexed oldest first
+#[derive(Clone)]
+pub struct Port {
+  inner: PortStorage,
+  head: usize,
+  len: usize,
+  mode: PortMode,
+  underflow: bool,
+  overflow: bool,
+}
+impl Default for Port {
+  fn default() -> Self {
+    Port {
+      inner: [0; PORT_SIZE],
+      head: 0,
+      len: 0,
+      mode: PortMode::default(),
+      underflow: false,
+      overflow: false,
+    }
+  }
+}
+impl From<PortStorage> for Port {
+  fn from(values: PortStorage) -> Self {
+    Port {
+      inner: values,
+      len: PORT_SIZE,
+      ..Port::default()
+    }
+  }
+}
+// ports with the same values in the same order are equal
+impl PartialEq for Port {
+  fn eq(&self, other: &Port) -> bool {
+    self.len == other.len && self.iter().eq(other.iter())
+  }
+}
+impl Index<usize> for Port {
+  type Output = u8;
+  fn index(&self, idx: usize) -> &u8 {
+    assert!{idx < self.len, "port index out of range: {} >= {}", idx, self.len};
+    &self.inner[(self.head + idx) % PORT_SIZE]
+  }
+}
+impl fmt::Debug for Port {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Port: {:?} ({}/{}) {:?} underflow={} overflow={}",
+      self.to_vec(), self.len, PORT_SIZE, self.mode, self.underflow, self.overflow}
+  }
+}
+impl Port {
+  pub fn len(&self) -> usize {
+    self.len
+  }
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+  pub fn is_full(&self) -> bool {
+    self.len == PORT_SIZE
+  }
+  pub fn get(&self, idx: usize) -> Option<u8> {
+    if idx < self.len {
+      Some(self[idx])
+    } else {
+      None
+    }
+  }
+  pub fn iter(&self) -> impl Iterator<Item=u8> + '_ {
+    (0..self.len).map(move |i| self[i])
+  }
+  pub fn to_vec(&self) -> Vec<u8> {
+    self.iter().collect()
+  }
+  pub fn get_mode(&self) -> PortMode {
+    self.mode
+  }
+  pub fn underflowed(&self) -> bool {
+    self.underflow
+  }
+  pub fn overflowed(&self) -> bool {
+    self.overflow
+  }
+  pub fn set_mode(mut self, mode: PortMode) -> Self {
+    self.mode = mode;
+    self
+  }
+  // restores flags saved by a snapshot
+  pub(crate) fn set_flags(mut self, underflow: bool, overflow: bool) -> Self {
+    self.underflow = underflow;
+    self.overflow = overflow;
+    self
+  }
+  // oldest value
+  pub fn pop(&mut self) -> PortRes<u8> {
+    if self.is_empty() {
+      return match self.mode {
+        PortMode::Fault => Err(PortErr::Underflow),
+        PortMode::Saturate => {
+          self.underflow = true;
+          Ok(0)
+        },
+      }
+    }
+    let val = self.inner[self.head];
+    self.inner[self.head] = 0;
+    self.head = (self.head + 1) % PORT_SIZE;
+    self.len -= 1;
+    Ok(val)
+  }
+  pub fn push(&mut self, value: u8) -> PortRes<()> {
+    if crate::MAX_VALUE < value {
+      return Err(PortErr::ValueTooLarge(value))
+    }
+    if self.is_full() {
+      match self.mode {
+        PortMode::Fault => return Err(PortErr::Overflow(value)),
+        PortMode::Saturate => {
+          self.overflow = true;
+          self.head = (self.head + 1) % PORT_SIZE;
+          self.len -= 1;
+        },
+      }
+    }
+    self.inner[(self.head + self.len) % PORT_SIZE] = value;
+    self.len += 1;
+    Ok(())
+  }
+}
@@ -0,0 +1,70 @@
+use crate::ports::*;
+
+#[test]
+fn default() {
+  let port = Port::default();
+  assert!{port.is_empty()};
+  assert_eq!{port.get_mode(), PortMode::Saturate};
+  assert!{!port.underflowed()};
+  assert!{!port.overflowed()};
+}
+#[test]
+fn from_slice() {
+  let port = Port::from([1, 2, 3, 4, 5]);
+  assert!{port.is_full()};
+  assert_eq!{port.to_vec(), vec![1, 2, 3, 4, 5]};
+  assert_eq!{port[0], 1};
+  assert_eq!{port[4], 5};
+}
+#[test]
+fn fifo_order() {
+  let mut port = Port::default();
+  assert!{port.push(1).is_ok()};
+  assert!{port.push(2).is_ok()};
+  assert_eq!{port.pop().unwrap(), 1};
+  assert!{port.push(3).is_ok()};
+  assert_eq!{port.to_vec(), vec![2, 3]};
+  assert_eq!{port.get(2), None};
+}
+#[test]
+fn saturate_underflow() {
+  let mut port = Port::default();
+  assert_eq!{port.pop().unwrap(), 0};
+  assert!{port.underflowed()};
+}
+#[test]
+fn saturate_overflow() {
+  let mut port = Port::default();
+  for v in 1..=7 {
+    assert!{port.push(v).is_ok()};
+  }
+  assert!{port.overflowed()};
+  assert_eq!{port.to_vec(), vec![3, 4, 5, 6, 7]};
+}
+#[test]
+fn fault_underflow() {
+  let mut port = Port::default().set_mode(PortMode::Fault);
+  assert_eq!{port.pop(), Err(PortError::Underflow)};
+  assert!{!port.underflowed()};
+}
+#[test]
+fn fault_overflow() {
+  let mut port = Port::from([1, 2, 3, 4, 5]).set_mode(PortMode::Fault);
+  assert_eq!{port.push(6), Err(PortError::Overflow(6))};
+  assert_eq!{port.to_vec(), vec![1, 2, 3, 4, 5]};
+}
+#[test]
+fn invalid_push_val() {
+  let mut port = Port::default();
+  assert_eq!{port.push(0x10), Err(PortError::ValueTooLarge(0x10))};
+}
+#[test]
+fn equality_ignores_layout() {
+  let mut a = Port::default();
+  let mut b = Port::default();
+  assert!{a.push(9).is_ok()};
+  assert!{a.pop().is_ok()};
+  assert!{a.push(1).is_ok()};
+  assert!{b.push(1).is_ok()};
+  assert_eq!{a, b};
+}
@@ -1,18 +1,23 @@
+use std::error::Error;
 use std::fmt;
+use serde::{Serialize, Deserialize};
 
 pub type RegisterResult<T> = Result<T, RegisterError>;
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
 pub enum RegisterError {
   ValueTooLarge(u8),
-  MathError(usize),
 }
-impl fmt::Debug for RegisterError {
+impl fmt::Display for RegisterError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    let _ = write!{f, "Register Error: "};
     match *self {
       RegisterError::ValueTooLarge(v) =>
         write!{f, "Value too large for register: {}", v},
-      RegisterError::MathError(v) =>
-        write!{f, "Math error from: {}", v},
     }
   }
-}
\ No newline at end of file
+}
+impl fmt::Debug for RegisterError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Register Error: {}", self}
+  }
+}
+impl Error for RegisterError {}
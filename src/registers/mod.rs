@@ -18,29 +18,15 @@ pub const HF_BIT: usize = 3;
 
 pub type RegisterInner = [u8; REGISTER_SIZE];
 
-#[derive(Clone,PartialEq)]
+#[derive(Clone,PartialEq,Default)]
 pub struct Registers {
   inst_ptr:    u8,
   loop_idx:    u8,
   flags:       u8,
   accumulator: u8,
 }
-impl Default for Registers {
-  fn default() -> Self {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::default()"};
-    Registers {
-      inst_ptr:    0,
-      loop_idx:    0,
-      flags:       0,
-      accumulator: 0,
-    }
-  }
-}
 impl From<RegisterInner> for Registers {
   fn from(slice: RegisterInner) -> Self {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::from::<slice>()"};
     Registers {
       inst_ptr: slice.get_bits(4..8),
       loop_idx: slice.get_bits(0..4),
@@ -49,23 +35,19 @@ impl From<RegisterInner> for Registers {
     }
   }
 }
-impl Into<RegisterInner> for Registers {
-  fn into(self) -> RegisterInner {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::into::<slice>()"};
+impl From<Registers> for RegisterInner {
+  fn from(reg: Registers) -> RegisterInner {
     let mut slice = [0u8; REGISTER_SIZE];
     // invert endianess
-    slice.set_bits(0..4, self.loop_idx);
-    slice.set_bits(4..8, self.inst_ptr);
-    slice.set_bits(8..12, self.accumulator);
-    slice.set_bits(12..16, self.flags);
+    slice.set_bits(0..4, reg.loop_idx);
+    slice.set_bits(4..8, reg.inst_ptr);
+    slice.set_bits(8..12, reg.accumulator);
+    slice.set_bits(12..16, reg.flags);
     slice
   }
 }
 impl fmt::Debug for Registers {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::debug()"};
     write!{f,
       "Registers: IP={:#X}, LI={:#X}, FR={:#X}, AC={:#X}\nFlags: CF={} ZF={} OF={} HF={}",
       self.inst_ptr, self.loop_idx, self.flags, self.accumulator,
@@ -75,28 +57,18 @@ impl fmt::Debug for Registers {
 }
 impl Registers {
   pub fn get_ip(&self) -> u8 {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_ip()"};
     self.inst_ptr
   }
   pub fn get_li(&self) -> u8 {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_li()"};
     self.loop_idx
   }
   pub fn get_fr(&self) -> u8 {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_fr()"};
     self.flags
   }
   pub fn get_ac(&self) -> u8 {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_ac()"};
     self.accumulator
   }
   pub fn set_ip(&mut self, ip: u8) -> RegRes<()> {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_ip()"};
     if crate::MAX_VALUE < ip {
       return Err(RegErr::ValueTooLarge(ip))
     }
@@ -104,8 +76,6 @@ impl Registers {
     Ok(())
   }
   pub fn set_li(&mut self, li: u8) -> RegRes<()> {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_li()"};
     if crate::MAX_VALUE < li {
       return Err(RegErr::ValueTooLarge(li))
     }
@@ -115,8 +85,6 @@ impl Registers {
     Ok(())
   }
   pub fn set_fr(&mut self, fr: u8) -> RegRes<()> {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_fr()"};
     if crate::MAX_VALUE < fr {
       return Err(RegErr::ValueTooLarge(fr))
     }
@@ -124,8 +92,6 @@ impl Registers {
     Ok(())
   }
   pub fn set_ac(&mut self, ac: u8) -> RegRes<()> {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_ac()"};
     if crate::MAX_VALUE < ac {
       return Err(RegErr::ValueTooLarge(ac))
     }
@@ -135,24 +101,18 @@ impl Registers {
     Ok(())
   }
   pub fn inc_ip(&mut self) { // NO ZF
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::inc_ip()"};
     match self.inst_ptr {
       0b1111u8 => self.inst_ptr = 0,
       _ => self.inst_ptr += 1,
     };
   }
   pub fn dec_ip(&mut self) { // NO ZF
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::dec_ip()"};
     match self.inst_ptr {
       0 => self.inst_ptr = 0b1111u8,
       _ => self.inst_ptr -= 1,
     };
   }
   pub fn inc_li(&mut self) { // sets ZF
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::inc_li()"};
     match self.loop_idx {
       0b1111u8 => self.loop_idx = 0,
       _ => self.loop_idx += 1,
@@ -161,8 +121,6 @@ impl Registers {
     self.set_zf(zf)
   }
   pub fn dec_li(&mut self) { // sets ZF
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::dec_li()"};
     match self.loop_idx {
       0 => self.loop_idx = 0b1111u8,
       _ => self.loop_idx -= 1,
@@ -171,8 +129,6 @@ impl Registers {
     self.set_zf(zf);
   }
   pub fn inc_ac(&mut self) { // sets ZF
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::inc_ac()"};
     match self.accumulator {
       0b1111u8 => self.accumulator = 0,
       _ => self.accumulator += 1,
@@ -181,8 +137,6 @@ impl Registers {
     self.set_zf(zf)
   }
   pub fn dec_ac(&mut self) { // sets ZF
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::dec_ac()"};
     match self.accumulator {
       0 => self.accumulator = 0b1111u8,
       _ => self.accumulator -= 1,
@@ -191,43 +145,27 @@ impl Registers {
     self.set_zf(zf);
   }
   pub fn set_cf(&mut self, bit: bool) {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_cf()"};
     self.flags.set_bit(CF_BIT, bit);
   }
   pub fn set_zf(&mut self, bit: bool) {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_zf()"};
     self.flags.set_bit(ZF_BIT, bit);
   }
   pub fn set_of(&mut self, bit: bool) {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_of()"};
     self.flags.set_bit(OF_BIT, bit);
   }
   pub fn set_hf(&mut self, bit: bool) {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::set_hf()"};
     self.flags.set_bit(HF_BIT, bit);
   }
   pub fn get_cf(&self) -> bool {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_cf()"};
     self.flags.get_bit(CF_BIT)
   }
   pub fn get_zf(&self) -> bool {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_zf()"};
     self.flags.get_bit(ZF_BIT)
   }
   pub fn get_of(&self) -> bool {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_of()"};
     self.flags.get_bit(OF_BIT)
   }
   pub fn get_hf(&self) -> bool {
-    #[cfg(not(feature = "lvl3"))]
-    trace!{"Registers::get_hf()"};
     self.flags.get_bit(HF_BIT)
   }
 }
@@ -1,4 +1,5 @@
-use registers::*;
+use proptest::prelude::*;
+use crate::registers::*;
 
 #[test]
 fn default(){
@@ -7,10 +8,10 @@ fn default(){
   assert_eq!{reg.get_li(), 0};
   assert_eq!{reg.get_fr(), 0};
   assert_eq!{reg.get_ac(), 0};
-  assert_eq!{reg.get_cf(), false};
-  assert_eq!{reg.get_zf(), false};
-  assert_eq!{reg.get_of(), false};
-  assert_eq!{reg.get_hf(), false};
+  assert!{!reg.get_cf()};
+  assert!{!reg.get_zf()};
+  assert!{!reg.get_of()};
+  assert!{!reg.get_hf()};
 }
 #[test]
 fn set_ip(){
@@ -29,10 +30,10 @@ fn set_fr(){
   let mut reg = Registers::default();
   assert!{reg.set_fr(0xF).is_ok()};
   assert_eq!{reg.get_fr(), 0xF};
-  assert_eq!{reg.get_cf(), true};
-  assert_eq!{reg.get_zf(), true};
-  assert_eq!{reg.get_of(), true};
-  assert_eq!{reg.get_hf(), true};
+  assert!{reg.get_cf()};
+  assert!{reg.get_zf()};
+  assert!{reg.get_of()};
+  assert!{reg.get_hf()};
 }
 #[test]
 fn set_ac(){
@@ -45,7 +46,7 @@ fn inc_ip(){
   let mut reg = Registers::default();
   reg.inc_ip();
   assert_eq!{reg.get_ip(), 1};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn dec_ip(){
@@ -53,7 +54,7 @@ fn dec_ip(){
   let _ = reg.set_ip(2);
   reg.dec_ip();
   assert_eq!{reg.get_ip(), 1};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn inc_overflow_ip(){
@@ -61,21 +62,21 @@ fn inc_overflow_ip(){
   let _ = reg.set_ip(0xF);
   reg.inc_ip();
   assert_eq!{reg.get_ip(), 0};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn dec_underflow_ip(){
   let mut reg = Registers::default();
   reg.dec_ip();
   assert_eq!{reg.get_ip(), 0xF};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn inc_li(){
   let mut reg = Registers::default();
   reg.inc_li();
   assert_eq!{reg.get_li(), 1};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn dec_li(){
@@ -83,7 +84,7 @@ fn dec_li(){
   let _ = reg.set_li(2);
   reg.dec_li();
   assert_eq!{reg.get_li(), 1};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn inc_overflow_li(){
@@ -91,14 +92,14 @@ fn inc_overflow_li(){
   let _ = reg.set_li(0xF);
   reg.inc_li();
   assert_eq!{reg.get_li(), 0};
-  assert_eq!{reg.get_zf(), true};
+  assert!{reg.get_zf()};
 }
 #[test]
 fn dec_underflow_li(){
   let mut reg = Registers::default();
   reg.dec_li();
   assert_eq!{reg.get_li(), 0xF};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 
 #[test]
@@ -106,7 +107,7 @@ fn inc_ac(){
   let mut reg = Registers::default();
   reg.inc_ac();
   assert_eq!{reg.get_ac(), 1};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn dec_ac(){
@@ -114,7 +115,7 @@ fn dec_ac(){
   let _ = reg.set_ac(2);
   reg.dec_ac();
   assert_eq!{reg.get_ac(), 1};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn inc_overflow_ac(){
@@ -122,14 +123,14 @@ fn inc_overflow_ac(){
   let _ = reg.set_ac(0xF);
   reg.inc_ac();
   assert_eq!{reg.get_ac(), 0};
-  assert_eq!{reg.get_zf(), true};
+  assert!{reg.get_zf()};
 }
 #[test]
 fn dec_underflow_ac(){
   let mut reg = Registers::default();
   reg.dec_ac();
   assert_eq!{reg.get_ac(), 0xF};
-  assert_eq!{reg.get_zf(), false};
+  assert!{!reg.get_zf()};
 }
 #[test]
 fn into_slice() {
@@ -149,4 +150,23 @@ fn from_slice() {
   assert_eq!{reg.get_li(), 0x7};
   assert_eq!{reg.get_fr(), 0x2};
   assert_eq!{reg.get_ac(), 0x3};
-}
\ No newline at end of file
+}
+
+proptest!{
+  #[test]
+  fn inner_round_trip(inner in any::<RegisterInner>()) {
+    let reg = Registers::from(inner);
+    prop_assert_eq!{RegisterInner::from(reg), inner};
+  }
+  #[test]
+  fn setters_round_trip(ip in 0..16u8, li in 0..16u8, fr in 0..16u8, ac in 0..16u8) {
+    let mut reg = Registers::default();
+    reg.set_ip(ip).unwrap();
+    reg.set_li(li).unwrap();
+    reg.set_ac(ac).unwrap();
+    reg.set_fr(fr).unwrap();
+    let back = Registers::from(RegisterInner::from(reg.clone()));
+    prop_assert_eq!{(ip, li, fr, ac), (back.get_ip(), back.get_li(), back.get_fr(), back.get_ac())};
+    prop_assert_eq!{back, reg};
+  }
+}
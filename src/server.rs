@@ -42,7 +42,7 @@ fn main() {
 
       #[cfg(not(feature="lvl3"))] debug!{"Accepted socket; addr={:?}", socket.peer_addr().unwrap()};
       let id = client_count.fetch_add(1, Ordering::SeqCst);
-      let game = Game::start(socket, id)
+      let game = Game::start(socket, id, TIMEOUT)
         .map_err(|err| error!{"Client error = {:?}", err})
         .map(|p| {
           //println!{"game.map()"}
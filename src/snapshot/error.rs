@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::instructions::InstructionError as InstErr;
+
+pub type SnapshotResult<T> = Result<T, SnapshotError>;
+#[derive(Clone,PartialEq)]
+pub enum SnapshotError {
+  BadMagic,
+  UnsupportedVersion(u8),
+  Truncated(usize),
+  BadValue(usize),     // unknown code or trailing data at an offset
+  Invalid(InstErr),    // state the machine could not hold
+  Json(String),
+  Io(String),
+}
+impl fmt::Display for SnapshotError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      SnapshotError::BadMagic =>
+        write!{f, "Not a snapshot"},
+      SnapshotError::UnsupportedVersion(v) =>
+        write!{f, "Unsupported snapshot version: {}", v},
+      SnapshotError::Truncated(o) =>
+        write!{f, "Snapshot ends early at offset: {}", o},
+      SnapshotError::BadValue(o) =>
+        write!{f, "Unexpected byte at offset: {}", o},
+      SnapshotError::Invalid(_) =>
+        write!{f, "State the machine cannot hold"},
+      SnapshotError::Json(ref e) =>
+        write!{f, "{}", e},
+      SnapshotError::Io(ref e) =>
+        write!{f, "{}", e},
+    }
+  }
+}
+impl fmt::Debug for SnapshotError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      SnapshotError::Invalid(ref e) => write!{f, "Snapshot Error: {:?}", e},
+      _ => write!{f, "Snapshot Error: {}", self},
+    }
+  }
+}
+impl Error for SnapshotError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match *self {
+      SnapshotError::Invalid(ref e) => Some(e),
+      _ => None,
+    }
+  }
+}
+impl From<InstErr> for SnapshotError {
+  fn from(err: InstErr) -> SnapshotError {
+    SnapshotError::Invalid(err)
+  }
+}
+impl From<serde_json::Error> for SnapshotError {
+  fn from(err: serde_json::Error) -> SnapshotError {
+    SnapshotError::Json(err.to_string())
+  }
+}
+impl From<io::Error> for SnapshotError {
+  fn from(err: io::Error) -> SnapshotError {
+    SnapshotError::Io(err.to_string())
+  }
+}
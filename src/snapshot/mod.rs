@@ -0,0 +1,318 @@
+#[cfg(test)] mod test;
+mod error;
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use serde::{Serialize, Deserialize};
+use crate::machine::{Machine, MachineInner, Fault, MACHINE_SIZE};
+use crate::level::{Level, Ruleset, Endian};
+use crate::instructions::{Instruction, Isa, InstructionError};
+use crate::registers::RegisterError;
+use crate::memory::MemoryError;
+use crate::stack::StackError;
+use crate::interrupts::InterruptError;
+use crate::ports::PortError;
+use crate::ports::PortMode;
+use crate::faults::{FaultPolicy, FaultKind, FaultAction};
+use self::error::SnapshotError as SnpErr;
+use self::error::SnapshotResult as SnpRes;
+
+pub use self::error::SnapshotError;
+pub use self::error::SnapshotResult;
+pub const SNAPSHOT_MAGIC: &[u8; 4] = b"TMSN";
+pub const SNAPSHOT_VERSION: u8 = 3;
+const IRQ_SIZE: usize = 6;
+
+#[derive(Clone,PartialEq,Debug,Serialize,Deserialize)]
+pub struct PortState {
+  pub mode: PortMode,
+  pub values: Vec<u8>, // oldest first
+  pub underflow: bool,
+  pub overflow: bool,
+}
+
+// Everything Machine::restore needs to continue a run where it left off.
+// Attached devices are not saved, loading attaches the level's bus again.
+//
+// Binary layout, version 3:
+//   "TMSN" version image[10] level isa endian calls[8] cycles[8] budget[8]
+//   faults[6] stack_len stack[..] irq[6] inp outp error
+// where a port is mode flags len values[..], flags bit0 underflow and bit1
+// overflow, and error is 0 or a fault: code value[8] ip step[8] then 0, or
+// 1 and the instruction's encoded byte and next nibble. Wide values are
+// little endian. faults holds one action per FaultKind: 0 halt, 1 raise,
+// 2 nop or 0x10 | handler for a trap. Version 1 had no fault context,
+// version 2 no budget or fault policy.
+#[derive(Clone,PartialEq,Debug,Serialize,Deserialize)]
+pub struct Snapshot {
+  pub image: MachineInner,
+  pub ruleset: Ruleset,
+  pub calls: usize,
+  pub cycles: usize,
+  pub budget: usize,
+  pub faults: FaultPolicy,
+  pub stack: Vec<u8>,             // bottom first
+  pub irq: [u8; IRQ_SIZE],        // enabled mask pending timer reload vector
+  pub inp: PortState,
+  pub outp: PortState,
+  pub error: Option<Fault>,
+}
+
+// JSON carries the version beside the snapshot fields
+#[derive(Serialize)]
+struct Versioned<'a> {
+  version: u8,
+  #[serde(flatten)]
+  snapshot: &'a Snapshot,
+}
+
+impl Snapshot {
+  // machine on the snapshot's level, devices from seed
+  pub fn load(&self, seed: u32) -> SnpRes<Machine> {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Snapshot::load()"};
+    let mut machine = self.ruleset.level.load(self.image, seed);
+    machine.restore(self)?;
+    Ok(machine)
+  }
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(64);
+    bytes.extend_from_slice(SNAPSHOT_MAGIC);
+    bytes.push(SNAPSHOT_VERSION);
+    bytes.extend_from_slice(&self.image);
+    bytes.push(level_code(self.ruleset.level));
+    bytes.push(self.ruleset.isa as u8);
+    bytes.push(self.ruleset.endian as u8);
+    bytes.extend_from_slice(&(self.calls as u64).to_le_bytes());
+    bytes.extend_from_slice(&(self.cycles as u64).to_le_bytes());
+    bytes.extend_from_slice(&(self.budget as u64).to_le_bytes());
+    for kind in FaultKind::ALL.iter() {
+      bytes.push(action_code(self.faults.get(*kind)));
+    }
+    bytes.push(self.stack.len() as u8);
+    bytes.extend_from_slice(&self.stack);
+    bytes.extend_from_slice(&self.irq);
+    for port in [&self.inp, &self.outp] {
+      bytes.push(port.mode as u8);
+      bytes.push(port.underflow as u8 | (port.overflow as u8) << 1);
+      bytes.push(port.values.len() as u8);
+      bytes.extend_from_slice(&port.values);
+    }
+    match self.error {
+      None => bytes.push(0),
+      Some(ref fault) => {
+        let (code, value) = error_code(&fault.error);
+        bytes.push(code);
+        bytes.extend_from_slice(&value.to_le_bytes());
+        bytes.push(fault.ip);
+        bytes.extend_from_slice(&(fault.step as u64).to_le_bytes());
+        match fault.instruction {
+          None => bytes.push(0),
+          Some(ref inst) => {
+            let (byte, next) = inst.encode();
+            bytes.extend_from_slice(&[1, byte, next]);
+          },
+        };
+      },
+    };
+    bytes
+  }
+  pub fn from_bytes(bytes: &[u8]) -> SnpRes<Snapshot> {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Snapshot::from_bytes()"};
+    if bytes.len() < SNAPSHOT_MAGIC.len() || &bytes[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+      return Err(SnpErr::BadMagic)
+    }
+    let mut rd = Reader { bytes, offset: SNAPSHOT_MAGIC.len() };
+    let version = rd.byte()?;
+    if version != SNAPSHOT_VERSION {
+      return Err(SnpErr::UnsupportedVersion(version))
+    }
+    let mut image: MachineInner = [0; MACHINE_SIZE];
+    image.copy_from_slice(rd.take(MACHINE_SIZE)?);
+    let ruleset = Ruleset {
+      level: rd.code(|c| match c {
+        0 => Some(Level::Open),
+        1 => Some(Level::One),
+        2 => Some(Level::Two),
+        3 => Some(Level::Three),
+        _ => None,
+      })?,
+      isa: rd.code(|c| match c {
+        0 => Some(Isa::Standard),
+        1 => Some(Isa::Extended),
+        _ => None,
+      })?,
+      endian: rd.code(|c| match c {
+        0 => Some(Endian::Big),
+        1 => Some(Endian::Little),
+        _ => None,
+      })?,
+    };
+    let calls = rd.wide()? as usize;
+    let cycles = rd.wide()? as usize;
+    let budget = rd.wide()? as usize;
+    let mut faults = FaultPolicy::default();
+    for kind in FaultKind::ALL.iter() {
+      faults = faults.set(*kind, rd.code(action_from)?);
+    }
+    let len = rd.byte()? as usize;
+    let stack = rd.take(len)?.to_vec();
+    let mut irq = [0; IRQ_SIZE];
+    irq.copy_from_slice(rd.take(IRQ_SIZE)?);
+    let mut ports = Vec::with_capacity(2);
+    for _ in 0..2 {
+      let mode = rd.code(|c| match c {
+        0 => Some(PortMode::Saturate),
+        1 => Some(PortMode::Fault),
+        _ => None,
+      })?;
+      let flags = rd.code(|c| if c < 4 { Some(c) } else { None })?;
+      let len = rd.byte()? as usize;
+      ports.push(PortState {
+        mode,
+        values: rd.take(len)?.to_vec(),
+        underflow: flags & 1 != 0,
+        overflow: flags & 2 != 0,
+      });
+    }
+    let error = match rd.byte()? {
+      0 => None,
+      code => {
+        let at = rd.offset - 1;
+        let value = rd.wide()?;
+        let error = error_from(code, value).ok_or(SnpErr::BadValue(at))?;
+        let ip = rd.code(|c| if c < 16 { Some(c) } else { None })?;
+        let step = rd.wide()? as usize;
+        let instruction = match rd.code(|c| if c < 2 { Some(c) } else { None })? {
+          0 => None,
+          _ => {
+            let byte = rd.byte()?;
+            let next = rd.code(|c| if c < 16 { Some(c) } else { None })?;
+            Some(Instruction::decode(byte, next, ruleset.isa))
+          },
+        };
+        Some(Fault { ip, instruction, step, error })
+      },
+    };
+    if rd.offset != bytes.len() {
+      return Err(SnpErr::BadValue(rd.offset))
+    }
+    let outp = ports.pop().unwrap();
+    let inp = ports.pop().unwrap();
+    Ok(Snapshot { image, ruleset, calls, cycles, budget, faults, stack, irq, inp, outp, error })
+  }
+  pub fn to_json(&self) -> String {
+    let versioned = Versioned { version: SNAPSHOT_VERSION, snapshot: self };
+    serde_json::to_string_pretty(&versioned).unwrap()
+  }
+  pub fn from_json(text: &str) -> SnpRes<Snapshot> {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Snapshot::from_json()"};
+    let value: serde_json::Value = serde_json::from_str(text)?;
+    match value.get("version").and_then(|v| v.as_u64()) {
+      Some(v) if v == SNAPSHOT_VERSION as u64 => {},
+      Some(v) => return Err(SnpErr::UnsupportedVersion(v.min(u8::MAX as u64) as u8)),
+      None => return Err(SnpErr::Json("missing version".to_string())),
+    };
+    Ok(serde_json::from_value(value)?)
+  }
+  pub fn write_to<W: Write>(&self, writer: &mut W) -> SnpRes<()> {
+    writer.write_all(&self.to_bytes())?;
+    Ok(())
+  }
+  pub fn read_from<R: Read>(reader: &mut R) -> SnpRes<Snapshot> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    Snapshot::from_bytes(&bytes)
+  }
+}
+
+fn level_code(level: Level) -> u8 {
+  match level {
+    Level::Open => 0,
+    Level::One => 1,
+    Level::Two => 2,
+    Level::Three => 3,
+  }
+}
+
+fn action_code(action: FaultAction) -> u8 {
+  match action {
+    FaultAction::Halt => 0,
+    FaultAction::Raise => 1,
+    FaultAction::Nop => 2,
+    FaultAction::Trap(addr) => 0x10 | addr,
+  }
+}
+
+fn action_from(code: u8) -> Option<FaultAction> {
+  match code {
+    0 => Some(FaultAction::Halt),
+    1 => Some(FaultAction::Raise),
+    2 => Some(FaultAction::Nop),
+    0x10..=0x1F => Some(FaultAction::Trap(code & 0xF)),
+    _ => None,
+  }
+}
+
+fn error_code(err: &InstructionError) -> (u8, u64) {
+  match *err {
+    InstructionError::InvalidInstruction(v) => (2, v as u64),
+    InstructionError::Register(RegisterError::ValueTooLarge(v)) => (3, v as u64),
+    InstructionError::Memory(MemoryError::OutOfBounds(v)) => (4, v as u64),
+    InstructionError::Memory(MemoryError::ValueTooLarge(v)) => (5, v as u64),
+    InstructionError::Stack(StackError::Overflow(v)) => (6, v as u64),
+    InstructionError::Stack(StackError::Underflow) => (7, 0),
+    InstructionError::Stack(StackError::ValueTooLarge(v)) => (8, v as u64),
+    InstructionError::Interrupt(InterruptError::ValueTooLarge(v)) => (9, v as u64),
+    InstructionError::Port(PortError::Underflow) => (10, 0),
+    InstructionError::Port(PortError::Overflow(v)) => (11, v as u64),
+    InstructionError::Port(PortError::ValueTooLarge(v)) => (12, v as u64),
+  }
+}
+
+fn error_from(code: u8, value: u64) -> Option<InstructionError> {
+  let byte = u8::try_from(value).ok();
+  match code {
+    2 => byte.map(InstructionError::InvalidInstruction),
+    3 => byte.map(|v| RegisterError::ValueTooLarge(v).into()),
+    4 => Some(MemoryError::OutOfBounds(value as usize).into()),
+    5 => byte.map(|v| MemoryError::ValueTooLarge(v).into()),
+    6 => byte.map(|v| StackError::Overflow(v).into()),
+    7 => Some(StackError::Underflow.into()),
+    8 => byte.map(|v| StackError::ValueTooLarge(v).into()),
+    9 => byte.map(|v| InterruptError::ValueTooLarge(v).into()),
+    10 => Some(PortError::Underflow.into()),
+    11 => byte.map(|v| PortError::Overflow(v).into()),
+    12 => byte.map(|v| PortError::ValueTooLarge(v).into()),
+    _ => None,
+  }
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+}
+impl<'a> Reader<'a> {
+  fn take(&mut self, n: usize) -> SnpRes<&'a [u8]> {
+    let chunk = self.bytes.get(self.offset..self.offset + n).ok_or(SnpErr::Truncated(self.offset))?;
+    self.offset += n;
+    Ok(chunk)
+  }
+  fn byte(&mut self) -> SnpRes<u8> {
+    self.take(1).map(|b| b[0])
+  }
+  fn wide(&mut self) -> SnpRes<u64> {
+    let mut wide = [0; 8];
+    wide.copy_from_slice(self.take(8)?);
+    Ok(u64::from_le_bytes(wide))
+  }
+  // a byte that must decode to one of a few values
+  fn code<T, F>(&mut self, decode: F) -> SnpRes<T>
+    where F: Fn(u8) -> Option<T>
+  {
+    let at = self.offset;
+    decode(self.byte()?).ok_or(SnpErr::BadValue(at))
+  }
+}
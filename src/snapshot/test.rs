@@ -0,0 +1,125 @@
+use crate::snapshot::*;
+use crate::machine::*;
+use crate::level::{Level, Ruleset, Endian};
+use crate::instructions::{Instruction, Isa};
+use crate::stack::StackError;
+use crate::interrupts::InterruptError;
+use crate::faults::{FaultPolicy, FaultKind, FaultAction};
+use crate::ports::PortMode;
+
+// del; jnz 0; hlt counting AC down from 3
+const COUNTDOWN: MachineInner = [0x00, 0x03, 0xD3, 0x00, 0, 0, 0, 0, 0, 0];
+
+#[test]
+fn round_trip() {
+  let ruleset = Ruleset { level: Level::Three, isa: Isa::Standard, endian: Endian::Little };
+  let mut machine = ruleset.load(COUNTDOWN, 0);
+  machine.step().unwrap();
+  machine.step().unwrap();
+  let snap = machine.snapshot();
+  assert_eq!{snap.ruleset, ruleset};
+  assert_eq!{(snap.calls, snap.stack.len()), (2, 0)};
+  assert_eq!{snap.inp.values, vec![1, 2, 3, 4, 5]};
+  assert_eq!{snap.inp.mode, PortMode::Saturate};
+  assert_eq!{snap.error, None};
+  let bytes = snap.to_bytes();
+  assert!{bytes.starts_with(b"TMSN\x03")};
+  assert_eq!{Snapshot::from_bytes(&bytes), Ok(snap.clone())};
+  let json = snap.to_json();
+  assert!{json.contains("\"version\": 3")};
+  assert_eq!{Snapshot::from_json(&json), Ok(snap.clone())};
+  assert_eq!{snap.load(0).map(|m| m.snapshot()), Ok(snap)};
+}
+#[test]
+fn resume() {
+  let mut machine = Level::Two.load(COUNTDOWN, 0);
+  machine.step().unwrap();
+  let snap = machine.snapshot();
+  let mut resumed = Snapshot::from_bytes(&snap.to_bytes()).unwrap().load(0).unwrap();
+  assert_eq!{machine.exec(), resumed.exec()};
+  assert_eq!{MachineInner::from(&machine), MachineInner::from(&resumed)};
+  assert_eq!{machine.get_cycles(), resumed.get_cycles()};
+  // restoring over a running machine puts it back
+  resumed.restore(&snap).unwrap();
+  assert_eq!{resumed.get_cc(), 1};
+  assert!{!resumed.is_stopped()};
+}
+#[test]
+fn error_state() {
+  // ret with an empty stack
+  let mut machine = Machine::from([0, 0, 0x01, 0, 0, 0, 0, 0, 0, 0]).set_isa(Isa::Extended);
+  assert!{machine.exec().is_err()};
+  let fault = Fault { ip: 0, instruction: Some(Instruction::RET), step: 0, error: StackError::Underflow.into() };
+  assert_eq!{machine.get_error(), Some(&fault)};
+  let snap = machine.snapshot();
+  assert_eq!{snap.error, Some(fault.clone())};
+  assert_eq!{Snapshot::from_bytes(&snap.to_bytes()), Ok(snap.clone())};
+  let restored = Snapshot::from_json(&snap.to_json()).unwrap().load(0).unwrap();
+  assert_eq!{restored.get_error(), Some(&fault)};
+  assert!{restored.is_stopped()};
+  assert_eq!{restored.get_isa(), Isa::Extended};
+}
+#[test]
+fn bad_snapshots() {
+  assert_eq!{Snapshot::from_bytes(b"TMS"), Err(SnapshotError::BadMagic)};
+  assert_eq!{Snapshot::from_bytes(b"TMSN\x09"), Err(SnapshotError::UnsupportedVersion(9))};
+  let bytes = Machine::default().snapshot().to_bytes();
+  assert_eq!{Snapshot::from_bytes(&bytes[..20]), Err(SnapshotError::Truncated(18))};
+  let mut long = bytes.clone();
+  long.push(0);
+  assert_eq!{Snapshot::from_bytes(&long), Err(SnapshotError::BadValue(bytes.len()))};
+  let mut level = bytes.clone();
+  level[15] = 7;
+  assert_eq!{Snapshot::from_bytes(&level), Err(SnapshotError::BadValue(15))};
+  let json = Machine::default().snapshot().to_json().replace("\"version\": 3", "\"version\": 2");
+  assert_eq!{Snapshot::from_json(&json), Err(SnapshotError::UnsupportedVersion(2))};
+  assert!{matches!{Snapshot::from_json("{}"), Err(SnapshotError::Json(_))}};
+  // more than the stack holds
+  let mut snap = Machine::default().snapshot();
+  snap.stack = vec![1, 2, 3, 4, 5];
+  assert_eq!{snap.load(0).err(), Some(SnapshotError::Invalid(StackError::Overflow(5).into()))};
+}
+#[test]
+fn budget_and_faults() {
+  // jmp 0 forever, stopped by a budget the level would not give
+  let faults = FaultPolicy::default()
+    .set(FaultKind::Stack, FaultAction::Trap(0x9))
+    .set(FaultKind::InvalidOpcode, FaultAction::Nop);
+  let machine = Level::Two.load([0x00, 0x00, 0x10, 0, 0, 0, 0, 0, 0, 0], 0)
+    .set_budget(5)
+    .set_faults(faults);
+  let snap = machine.snapshot();
+  assert_eq!{(snap.budget, snap.faults), (5, faults)};
+  let bytes = snap.to_bytes();
+  assert_eq!{Snapshot::from_bytes(&bytes), Ok(snap.clone())};
+  assert_eq!{Snapshot::from_json(&snap.to_json()), Ok(snap.clone())};
+  let mut loaded = Snapshot::from_bytes(&bytes).unwrap().load(0).unwrap();
+  assert_eq!{(loaded.get_budget(), loaded.get_faults()), (5, faults)};
+  assert_eq!{loaded.run(), HaltReason::Budget};
+  assert_eq!{loaded.get_cc(), 5};
+  // restoring into a machine of another level takes the snapshot's
+  let one = Level::One.load([0; MACHINE_SIZE], 0).snapshot();
+  let mut other = Level::Two.load([0; MACHINE_SIZE], 0);
+  other.restore(&one).unwrap();
+  assert_eq!{other.get_faults(), Level::One.faults()};
+  assert_eq!{other.get_budget(), Level::One.budget()};
+  // an action code no policy has
+  let mut bad = bytes.clone();
+  bad[42] = 3;
+  assert_eq!{Snapshot::from_bytes(&bad), Err(SnapshotError::BadValue(42))};
+}
+#[test]
+fn bad_timer() {
+  let mut snap = Machine::default().snapshot();
+  // timer at 0 while reload 5 runs it
+  snap.irq = [0, 0, 0, 0, 5, 0];
+  let read = Snapshot::from_bytes(&snap.to_bytes()).unwrap();
+  assert_eq!{read.load(0).err(), Some(SnapshotError::Invalid(InterruptError::ValueTooLarge(5).into()))};
+  let mut machine = Machine::default();
+  assert!{machine.restore(&read).is_err()};
+  // more left than the timer reloads with
+  snap.irq = [0, 0, 0, 6, 5, 0];
+  assert_eq!{snap.load(0).err(), Some(SnapshotError::Invalid(InterruptError::ValueTooLarge(6).into()))};
+  snap.irq = [0, 0, 0, 5, 5, 0];
+  assert!{snap.load(0).is_ok()};
+}
@@ -0,0 +1,300 @@
+#[cfg(test)] mod test;
+
+use std::collections::HashSet;
+use bit_field::*;
+use crate::machine::{Machine, MachineInner, IMAGE_NIBBLES, MEMORY_OFFSET, get_nibble, set_nibble};
+use crate::devices::Random;
+use crate::level::Level;
+
+pub const PRUNE_DEPTH: usize = 4;   // most untouched nibbles folded into one run
+pub const POPULATION: usize = 32;
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Objective {
+  Any,
+  MinCalls,
+  MinChanges, // fewest nibbles changed from the base image
+}
+
+#[derive(Clone,PartialEq,Debug)]
+pub struct Solution {
+  pub image: MachineInner,
+  pub wildcard: u32, // image nibbles that may hold any value
+  pub calls: usize,
+  pub changes: usize,
+}
+impl Solution {
+  // wildcard nibbles print as '?'
+  pub fn to_hex(&self) -> String {
+    (0..IMAGE_NIBBLES).map(|i| match self.wildcard.get_bit(i) {
+      true => '?',
+      false => format!{"{:X}", get_nibble(&self.image, i)}.remove(0),
+    }).collect()
+  }
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct Report {
+  pub solutions: Vec<Solution>,
+  pub runs: usize,      // images executed
+  pub complete: bool,   // every candidate was covered
+}
+
+// Searches images derived from a base by changing only the free nibbles
+pub struct Solver {
+  level: Level,
+  base: MachineInner,
+  free: Vec<usize>,
+  budget: usize,
+  max_solutions: usize,
+  objective: Objective,
+  seed: u32,
+}
+impl Solver {
+  pub fn new(level: Level, base: MachineInner) -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Solver::new()"};
+    Solver {
+      level,
+      base,
+      free: (0..IMAGE_NIBBLES).collect(),
+      budget: 1 << 20,
+      max_solutions: 64,
+      objective: Objective::Any,
+      seed: 1,
+    }
+  }
+  pub fn set_free(mut self, free: Vec<usize>) -> Self {
+    self.free = free.into_iter().filter(|i| *i < IMAGE_NIBBLES).collect();
+    self
+  }
+  pub fn set_budget(mut self, budget: usize) -> Self {
+    self.budget = budget;
+    self
+  }
+  pub fn set_max_solutions(mut self, max: usize) -> Self {
+    self.max_solutions = max;
+    self
+  }
+  pub fn set_objective(mut self, objective: Objective) -> Self {
+    self.objective = objective;
+    self
+  }
+  pub fn set_seed(mut self, seed: u32) -> Self {
+    self.seed = seed;
+    self
+  }
+  // Odometer over the free nibbles, first free nibble turning fastest.
+  // When the fastest nibbles are unread by a run every value of them shares
+  // that run, so they are checked against the end state instead of re-run.
+  pub fn brute_force(&self) -> Report {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Solver::brute_force()"};
+    let mut search = Search::new(self);
+    let mut digits = vec![0u8; self.free.len()];
+    loop {
+      if self.budget <= search.report.runs || search.is_full() {
+        break;
+      }
+      let image = self.image(&digits);
+      let (machine, ok) = self.run(image);
+      search.report.runs += 1;
+      let reads = machine.get_mem().get_reads();
+      let folded = digits.iter().zip(self.free.iter())
+        .take(PRUNE_DEPTH)
+        .take_while(|(d, i)| **d == 0 && !is_read(reads, **i))
+        .count();
+      if ok {
+        search.fold(&machine, image, &self.free[..folded]);
+      }
+      // step the first digit above the folded ones
+      let mut idx = folded;
+      loop {
+        if idx == digits.len() {
+          search.report.complete = true;
+          return search.finish();
+        }
+        digits[idx] = (digits[idx] + 1) % 16;
+        if digits[idx] != 0 {
+          break;
+        }
+        idx += 1;
+      }
+    }
+    search.finish()
+  }
+  pub fn random(&self) -> Report {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Solver::random()"};
+    let rng = Random::new(self.seed);
+    let mut search = Search::new(self);
+    while search.report.runs < self.budget && !search.is_full() {
+      let digits: Vec<u8> = self.free.iter().map(|_| (rng.next_u32() % 16) as u8).collect();
+      let image = self.image(&digits);
+      let (machine, ok) = self.run(image);
+      search.report.runs += 1;
+      if ok {
+        search.fold(&machine, image, &[]);
+      }
+    }
+    search.finish()
+  }
+  // Tournament selection, uniform crossover and per nibble mutation, scored
+  // by how many level conditions the end state misses.
+  pub fn genetic(&self) -> Report {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Solver::genetic()"};
+    let rng = Random::new(self.seed);
+    let mut search = Search::new(self);
+    let width = self.free.len().max(1) as u32;
+    let mut population: Vec<(Vec<u8>, (usize, usize))> = Vec::with_capacity(POPULATION);
+    while population.len() < POPULATION && search.report.runs < self.budget {
+      let digits: Vec<u8> = self.free.iter().map(|_| (rng.next_u32() % 16) as u8).collect();
+      let score = self.score(&mut search, &digits);
+      population.push((digits, score));
+    }
+    while search.report.runs < self.budget && !search.is_full() && !population.is_empty() {
+      let mut next = Vec::with_capacity(POPULATION);
+      // keep the best individual as is
+      let best = population.iter().min_by_key(|(_, s)| *s).cloned().unwrap();
+      next.push(best);
+      while next.len() < POPULATION && search.report.runs < self.budget {
+        let a = tournament(&rng, &population);
+        let b = tournament(&rng, &population);
+        let child: Vec<u8> = a.iter().zip(b.iter()).map(|(x, y)| {
+          let gene = if rng.next_u32().is_multiple_of(2) { *x } else { *y };
+          match rng.next_u32() % width {
+            0 => (rng.next_u32() % 16) as u8,
+            _ => gene,
+          }
+        }).collect();
+        let score = self.score(&mut search, &child);
+        next.push((child, score));
+      }
+      population = next;
+    }
+    search.finish()
+  }
+  fn score(&self, search: &mut Search, digits: &[u8]) -> (usize, usize) {
+    let image = self.image(digits);
+    let (machine, ok) = self.run(image);
+    search.report.runs += 1;
+    if ok {
+      search.fold(&machine, image, &[]);
+    }
+    let unmet = self.level.unmet(&machine) + !ok as usize;
+    (unmet, self.cost(&machine, image, 0))
+  }
+  fn image(&self, digits: &[u8]) -> MachineInner {
+    let mut image = self.base;
+    for (idx, val) in self.free.iter().zip(digits.iter()) {
+      set_nibble(&mut image, *idx, *val);
+    }
+    image
+  }
+  fn run(&self, image: MachineInner) -> (Machine, bool) {
+    let mut machine = self.level.load(image, self.seed);
+    let ok = machine.exec().is_ok();
+    (machine, ok)
+  }
+  fn cost(&self, machine: &Machine, image: MachineInner, wildcard: u32) -> usize {
+    match self.objective {
+      Objective::Any => 0,
+      Objective::MinCalls => machine.get_cc(),
+      Objective::MinChanges => changes(&self.base, &image, wildcard),
+    }
+  }
+}
+
+// Solutions found so far, deduplicated
+struct Search<'s> {
+  solver: &'s Solver,
+  seen: HashSet<(MachineInner, u32)>,
+  report: Report,
+}
+impl<'s> Search<'s> {
+  fn new(solver: &'s Solver) -> Self {
+    Search {
+      solver,
+      seen: HashSet::new(),
+      report: Report::default(),
+    }
+  }
+  fn is_full(&self) -> bool {
+    self.solver.max_solutions <= self.report.solutions.len()
+  }
+  // Validate every value of the folded nibbles against the end state of one
+  // run, folded nibbles are unread so only unwritten ones carry into it.
+  fn fold(&mut self, machine: &Machine, image: MachineInner, folded: &[usize]) {
+    let level = self.solver.level;
+    let writes = machine.get_mem().get_writes();
+    let combos = 1usize << (4 * folded.len());
+    let mut valid = Vec::new();
+    for combo in 0..combos {
+      let mut image = image;
+      let mut patched = machine.clone();
+      let mut inner = *patched.get_mem().get_all();
+      for (n, idx) in folded.iter().enumerate() {
+        let val = (combo >> (4 * n)) as u8 & 0xF;
+        set_nibble(&mut image, *idx, val);
+        let offset = *idx - MEMORY_OFFSET;
+        if !writes.get_bit(offset) {
+          set_nibble(&mut inner, offset, val);
+        }
+      }
+      let mem = patched.get_mem().clone().set_all(inner);
+      patched = patched.set_mem(mem);
+      if level.is_valid(&patched) {
+        valid.push(image);
+      }
+    }
+    if valid.len() == combos && !folded.is_empty() {
+      let wildcard = folded.iter().fold(0u32, |mut w, i| { w.set_bit(*i, true); w });
+      let mut image = image;
+      for idx in folded.iter() {
+        set_nibble(&mut image, *idx, 0);
+      }
+      self.push(machine, image, wildcard);
+    } else {
+      for image in valid {
+        self.push(machine, image, 0);
+      }
+    }
+  }
+  fn push(&mut self, machine: &Machine, image: MachineInner, wildcard: u32) {
+    if self.is_full() || !self.seen.insert((image, wildcard)) {
+      return;
+    }
+    self.report.solutions.push(Solution {
+      image,
+      wildcard,
+      calls: machine.get_cc(),
+      changes: changes(&self.solver.base, &image, wildcard),
+    });
+  }
+  fn finish(mut self) -> Report {
+    let objective = self.solver.objective;
+    self.report.solutions.sort_by_key(|s| match objective {
+      Objective::Any => 0,
+      Objective::MinCalls => s.calls,
+      Objective::MinChanges => s.changes,
+    });
+    self.report
+  }
+}
+
+fn is_read(reads: u16, idx: usize) -> bool {
+  // register nibbles are always in use
+  idx < MEMORY_OFFSET || reads.get_bit(idx - MEMORY_OFFSET)
+}
+fn changes(base: &MachineInner, image: &MachineInner, wildcard: u32) -> usize {
+  (0..IMAGE_NIBBLES)
+    .filter(|i| !wildcard.get_bit(*i))
+    .filter(|i| get_nibble(base, *i) != get_nibble(image, *i))
+    .count()
+}
+fn tournament<'p>(rng: &Random, population: &'p [(Vec<u8>, (usize, usize))]) -> &'p [u8] {
+  let a = &population[rng.next_u32() as usize % population.len()];
+  let b = &population[rng.next_u32() as usize % population.len()];
+  if a.1 <= b.1 { &a.0 } else { &b.0 }
+}
@@ -0,0 +1,84 @@
+use crate::solver::*;
+use crate::machine::*;
+use crate::level::Level;
+
+#[test]
+fn nibbles() {
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  set_nibble(&mut image, 0, 0xA);
+  set_nibble(&mut image, 3, 0x5);
+  assert_eq!{image[0], 0xA0};
+  assert_eq!{image[1], 0x05};
+  assert_eq!{get_nibble(&image, 0), 0xA};
+  assert_eq!{get_nibble(&image, 3), 0x5};
+}
+#[test]
+fn solution_hex() {
+  let sol = Solution {
+    image: [0x12, 0x34, 0, 0, 0, 0, 0, 0, 0, 0xFF],
+    wildcard: 0b10,
+    calls: 0,
+    changes: 0,
+  };
+  assert_eq!{sol.to_hex(), "1?3400000000000000FF"};
+}
+#[test]
+fn brute_force_level_two() {
+  // lda 0x3; hlt with the loaded nibble free
+  let base: MachineInner = [0x00, 0x00, 0x43, 0x00, 0, 0, 0, 0, 0, 0];
+  let report = Solver::new(Level::Two, base)
+    .set_free(vec![MEMORY_OFFSET + 3])
+    .brute_force();
+  assert!{report.complete};
+  assert_eq!{report.runs, 16};
+  assert_eq!{report.solutions.len(), 1};
+  assert_eq!{report.solutions[0].image[3], 0x05};
+}
+#[test]
+fn brute_force_folds_unread() {
+  // hlt; the remaining nibbles are never read and do not affect lvl2
+  let base: MachineInner = [0x00, 0x05, 0, 0, 0, 0, 0, 0, 0, 0];
+  let free: Vec<usize> = (MEMORY_OFFSET + 2..MEMORY_OFFSET + 6).collect();
+  let report = Solver::new(Level::Two, base)
+    .set_free(free)
+    .brute_force();
+  assert!{report.complete};
+  assert_eq!{report.runs, 1};
+  assert_eq!{report.solutions.len(), 1};
+  assert_eq!{report.solutions[0].wildcard.count_ones(), 4};
+}
+#[test]
+fn brute_force_budget() {
+  let base: MachineInner = [0x00, 0x00, 0x43, 0x00, 0, 0, 0, 0, 0, 0];
+  let report = Solver::new(Level::Two, base)
+    .set_free(vec![MEMORY_OFFSET + 3, 1])
+    .set_budget(10)
+    .brute_force();
+  assert!{!report.complete};
+  assert_eq!{report.runs, 10};
+}
+#[test]
+fn minimize_changes() {
+  // ac starts at 5, any single nibble change in memory still wins
+  let base: MachineInner = [0x00, 0x05, 0, 0, 0, 0, 0, 0, 0, 0];
+  let report = Solver::new(Level::Two, base)
+    .set_free(vec![3, MEMORY_OFFSET + 1])
+    .set_objective(Objective::MinChanges)
+    .brute_force();
+  assert!{report.complete};
+  assert_eq!{report.solutions[0].changes, 0};
+}
+#[test]
+fn random_and_genetic_level_two() {
+  let base: MachineInner = [0x00, 0x00, 0x43, 0x00, 0, 0, 0, 0, 0, 0];
+  let solver = Solver::new(Level::Two, base)
+    .set_free(vec![MEMORY_OFFSET + 3])
+    .set_budget(2000)
+    .set_max_solutions(1)
+    .set_seed(7);
+  let report = solver.random();
+  assert_eq!{report.solutions.len(), 1};
+  let report = solver.genetic();
+  assert_eq!{report.solutions.len(), 1};
+  assert_eq!{report.solutions[0].image[3], 0x05};
+}
@@ -0,0 +1,29 @@
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+pub type StackResult<S> = Result<S, StackError>;
+#[derive(Clone,PartialEq,Serialize,Deserialize)]
+pub enum StackError {
+  Overflow(u8),
+  Underflow,
+  ValueTooLarge(u8),
+}
+impl fmt::Display for StackError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      StackError::Overflow(v) =>
+        write!{f, "Stack is full, unable to push: {:X}", v},
+      StackError::Underflow =>
+        write!{f, "Stack is empty, unable to pop"},
+      StackError::ValueTooLarge(v) =>
+        write!{f, "Value provided is above 4 bits: {:X}", v},
+    }
+  }
+}
+impl fmt::Debug for StackError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Stack Error: {}", self}
+  }
+}
+impl Error for StackError {}
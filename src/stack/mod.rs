@@ -0,0 +1,63 @@
+#[cfg(test)] mod test;
+mod error;
+
+use std::fmt;
+use self::error::StackError as StkErr;
+use self::error::StackResult as StkRes;
+
+pub use self::error::StackError;
+pub use self::error::StackResult;
+pub const STACK_SIZE: usize = 4; // nibbles
+pub type StackInner = [u8; STACK_SIZE];
+
+// hardware stack shared by CALL/RET and PUSH/POP, grows upward
+#[derive(Clone,PartialEq)]
+pub struct Stack {
+  inner: StackInner,
+  len: usize,
+}
+impl Default for Stack {
+  fn default() -> Self {
+    Stack {
+      inner: [0; STACK_SIZE],
+      len: 0,
+    }
+  }
+}
+impl fmt::Debug for Stack {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Stack: {:?}", self.get_all()}
+  }
+}
+impl Stack {
+  pub fn get_all(&self) -> &[u8] {
+    &self.inner[..self.len]
+  }
+  pub fn len(&self) -> usize {
+    self.len
+  }
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+  pub fn is_full(&self) -> bool {
+    self.len == STACK_SIZE
+  }
+  pub fn push(&mut self, value: u8) -> StkRes<()> {
+    if crate::MAX_VALUE < value {
+      return Err(StkErr::ValueTooLarge(value))
+    }
+    if self.is_full() {
+      return Err(StkErr::Overflow(value))
+    }
+    self.inner[self.len] = value;
+    self.len += 1;
+    Ok(())
+  }
+  pub fn pop(&mut self) -> StkRes<u8> {
+    if self.is_empty() {
+      return Err(StkErr::Underflow)
+    }
+    self.len -= 1;
+    Ok(self.inner[self.len])
+  }
+}
@@ -0,0 +1,40 @@
+use crate::stack::*;
+
+#[test]
+fn default() {
+  let stk = Stack::default();
+  assert!{stk.is_empty()};
+  assert_eq!{stk.len(), 0};
+  assert_eq!{stk.get_all(), &[] as &[u8]};
+}
+#[test]
+fn push_pop() {
+  let mut stk = Stack::default();
+  assert!{stk.push(0x3).is_ok()};
+  assert!{stk.push(0xA).is_ok()};
+  assert_eq!{stk.get_all(), &[0x3, 0xA]};
+  assert_eq!{stk.pop().unwrap(), 0xA};
+  assert_eq!{stk.pop().unwrap(), 0x3};
+  assert!{stk.is_empty()};
+}
+#[test]
+fn overflow() {
+  let mut stk = Stack::default();
+  for v in 0..STACK_SIZE {
+    assert!{stk.push(v as u8).is_ok()};
+  }
+  assert!{stk.is_full()};
+  assert_eq!{stk.push(0x1), Err(StackError::Overflow(0x1))};
+  assert_eq!{stk.len(), STACK_SIZE};
+}
+#[test]
+fn underflow() {
+  let mut stk = Stack::default();
+  assert_eq!{stk.pop(), Err(StackError::Underflow)};
+}
+#[test]
+fn invalid_push_val() {
+  let mut stk = Stack::default();
+  assert_eq!{stk.push(0x10), Err(StackError::ValueTooLarge(0x10))};
+  assert!{stk.is_empty()};
+}
@@ -0,0 +1,83 @@
+use std::io;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use futures::{Async, Poll};
+use futures::task::{self, Task};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+// what both ends of an in-memory connection share
+#[derive(Default)]
+struct Shared {
+  to_server: VecDeque<u8>,
+  to_client: Vec<u8>,
+  closed: bool,         // the peer sends nothing more
+  reader: Option<Task>, // server waiting on a read
+}
+
+// Server end of an in-memory connection, a stand in for a TcpStream.
+// Only usable from inside a task, an empty read parks the current one.
+pub struct Duplex {
+  shared: Arc<Mutex<Shared>>,
+}
+// Client end, usable from any thread while the server runs
+#[derive(Clone)]
+pub struct Peer {
+  shared: Arc<Mutex<Shared>>,
+}
+
+pub fn duplex() -> (Duplex, Peer) {
+  let shared = Arc::new(Mutex::new(Shared::default()));
+  (Duplex { shared: shared.clone() }, Peer { shared })
+}
+
+impl Peer {
+  pub fn send(&self, bytes: &[u8]) {
+    let mut shared = self.shared.lock().unwrap();
+    shared.to_server.extend(bytes);
+    if let Some(task) = shared.reader.take() {
+      task.notify();
+    }
+  }
+  // reads after the sent bytes return 0
+  pub fn close(&self) {
+    let mut shared = self.shared.lock().unwrap();
+    shared.closed = true;
+    if let Some(task) = shared.reader.take() {
+      task.notify();
+    }
+  }
+  // everything the server wrote so far
+  pub fn received(&self) -> Vec<u8> {
+    self.shared.lock().unwrap().to_client.clone()
+  }
+}
+
+impl io::Read for Duplex {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let mut shared = self.shared.lock().unwrap();
+    if shared.to_server.is_empty() && !shared.closed {
+      shared.reader = Some(task::current());
+      return Err(io::ErrorKind::WouldBlock.into())
+    }
+    let size = buf.len().min(shared.to_server.len());
+    for (b, v) in buf.iter_mut().zip(shared.to_server.drain(..size)) {
+      *b = v;
+    }
+    Ok(size)
+  }
+}
+impl io::Write for Duplex {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.shared.lock().unwrap().to_client.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+impl AsyncRead for Duplex {}
+impl AsyncWrite for Duplex {
+  fn shutdown(&mut self) -> Poll<(), io::Error> {
+    Ok(Async::Ready(()))
+  }
+}
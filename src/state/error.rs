@@ -1,43 +1,50 @@
+use std::error::Error;
 use std::fmt;
 use std::io::Error as IoErr;
+use tokio::timer::Error as TimerErr;
 
-use crate::instructions::InstructionError as InstErr;
-use crate::registers::RegisterError as RegErr;
-use crate::memory::MemoryError as MemErr;
 use crate::machine::MachineError as MacErr;
 
 pub type GameResult<M> = Result<M, GameError>;
 pub enum GameError {
-  InstructionError(InstErr),
-  RegisterError(RegErr),
-  MemoryError(MemErr),
   MachineError(MacErr),
   IoError(IoErr),
+  TimerError(TimerErr),
   Incorrect(usize),
 }
-impl fmt::Debug for GameError {
+impl fmt::Display for GameError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    let _ = write!{f, "Game Error: "};
     match *self {
+      GameError::MachineError(_) =>
+        write!{f, "Client machine failed"},
+      GameError::IoError(_) =>
+        write!{f, "Connection failed"},
+      GameError::TimerError(_) =>
+        write!{f, "Session timer failed"},
       GameError::Incorrect(ref p) =>
         write!{f, "Provided incorrect response: {}", p},
-      ref e @ _ => write!{f, "{:?}", *e},
     }
   }
 }
-impl From<InstErr> for GameError {
-  fn from(err: InstErr) -> GameError {
-    GameError::InstructionError(err)
-  }
-}
-impl From<RegErr> for GameError {
-  fn from(err: RegErr) -> GameError {
-    GameError::RegisterError(err)
+impl fmt::Debug for GameError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let _ = write!{f, "Game Error: "};
+    match *self {
+      GameError::MachineError(ref e) => write!{f, "{:?}", e},
+      GameError::IoError(ref e) => write!{f, "{:?}", e},
+      GameError::TimerError(ref e) => write!{f, "{:?}", e},
+      GameError::Incorrect(_) => write!{f, "{}", self},
+    }
   }
 }
-impl From<MemErr> for GameError {
-  fn from(err: MemErr) -> GameError {
-    GameError::MemoryError(err)
+impl Error for GameError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match *self {
+      GameError::MachineError(ref e) => Some(e),
+      GameError::IoError(ref e) => Some(e),
+      GameError::TimerError(ref e) => Some(e),
+      GameError::Incorrect(_) => None,
+    }
   }
 }
 impl From<MacErr> for GameError {
@@ -50,3 +57,8 @@ impl From<IoErr> for GameError {
     GameError::IoError(err)
   }
 }
+impl From<TimerErr> for GameError {
+  fn from(err: TimerErr) -> GameError {
+    GameError::TimerError(err)
+  }
+}
@@ -1,61 +1,68 @@
+#[cfg(test)] mod test;
 pub mod error;
+#[cfg(any(test, feature = "testing"))] mod duplex;
 
 use std::fmt;
-use std::time::{Instant, Duration};
-use mio::Ready;
-use tokio::net::TcpStream;
+use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
 use tokio::prelude::*;
-use futures::Poll;
+use futures::{Async, Poll};
+use tokio::timer::Delay;
 use state_machine_future::RentToOwn;
 
 
 use self::error::*;
+#[cfg(any(test, feature = "testing"))]
+pub use self::duplex::{Duplex, Peer, duplex};
 use crate::machine::*;
+use crate::level::Level;
 
 
 #[cfg(all(feature="real_flag", feature="lvl1"))] static FLAG: &str = "40ByteCTF{H0w_m4ny_0pc0des_t0_4_tinyM4chine?}";
 #[cfg(all(feature="real_flag", feature="lvl2"))] static FLAG: &str = "40ByteCTF{B3_0n3_w1th_th3_bs1d3s!}";
 #[cfg(all(feature="real_flag", feature="lvl3"))] static FLAG: &str = "40ByteCTF{4r3nt_n3w_4rch1t3cur3s_fun?!}";
 #[cfg(not(feature="real_flag"))] static FLAG: &str = "40ByteCTF{Not actually the real flag}";
-static TIMEOUT: u64 = 10;
+pub const TIMEOUT: Duration = Duration::from_secs(10); // for the image to arrive
+pub static GREETING: &str = "Submit your TinyMachine here:\n";
 
+// One client's session over any stream, a TcpStream on the server
 #[derive(StateMachineFuture)]
-pub enum Game {
+pub enum Game<S>
+  where S: AsyncRead + AsyncWrite
+{
   #[state_machine_future(start, transitions(Read))]
   Start {
-    stream: TcpStream,
+    stream: S,
     id: usize,
+    limit: Duration, // until the image has to be read
   },
   #[state_machine_future(transitions(Execute, Incorrect))]
   Read {
-    stream: TcpStream,
+    stream: S,
     id: usize,
-    time: Instant,
+    timeout: Delay, // fires at the deadline if nothing was read
   },
   #[state_machine_future(transitions(Validate,Incorrect))]
   Execute {
-    stream: TcpStream,
+    stream: S,
     id: usize,
-    time: Instant,
     machine: Machine,
   },
   #[state_machine_future(transitions(Correct,Incorrect))]
   Validate {
-    stream: TcpStream,
+    stream: S,
     id: usize,
-    time: Instant,
     machine: Machine,
   },
   #[state_machine_future(transitions(Finished))]
   Incorrect {
-    stream: TcpStream,
+    stream: S,
     id: usize,
     reason: Reason,
     message: String,
   },
   #[state_machine_future(transitions(Finished))]
   Correct {
-    stream: TcpStream,
+    stream: S,
     id: usize,
     message: String,
   },
@@ -65,166 +72,230 @@ pub enum Game {
   Error(GameError),
 }
 
-impl PollGame for Game {
-  fn poll_start<'a>(sess: &'a mut RentToOwn<'a, Start>)
-    -> Poll<AfterStart, GameError>
+impl<S> PollGame<S> for Game<S>
+  where S: AsyncRead + AsyncWrite
+{
+  fn poll_start<'a>(sess: &'a mut RentToOwn<'a, Start<S>>)
+    -> Poll<AfterStart<S>, GameError>
   {
     #[cfg(not(feature = "lvl3"))]
     trace!{"Game::poll_start({})", sess.id};
-    try_ready!{sess.stream.poll_write_ready()};
-    let msg = "Submit your TinyMachine here:\n";
-    try_ready!{sess.stream.poll_write(msg.as_bytes())};
+    try_ready!{sess.stream.poll_write(GREETING.as_bytes())};
     let sess = sess.take();
     let sess = Read {
       stream: sess.stream,
       id: sess.id,
-      time: Instant::now(),
+      timeout: Delay::new(Instant::now() + sess.limit),
     };
     transition!{sess}
   }
 
-  fn poll_read<'a>(sess: &'a mut RentToOwn<'a, Read>)
-    -> Poll<AfterRead, GameError>
+  fn poll_read<'a>(sess: &'a mut RentToOwn<'a, Read<S>>)
+    -> Poll<AfterRead<S>, GameError>
   {
     #[cfg(not(feature = "lvl3"))]
     trace!{"Game::poll_read({})", sess.id};
-    let state = Ready::readable();
-    try_ready!{sess.stream.poll_read_ready(state)};
     let mut buf = Vec::with_capacity(MACHINE_SIZE+1);
-    let _size = try_ready!{sess.stream.read_buf(&mut buf)};
-    #[cfg(not(feature = "lvl3"))]
-    debug!{"Client sent {:?} bytes: {}", _size, sess.id};
+    let res = match AsyncRead::read_buf(&mut sess.stream, &mut buf)? {
+      Async::Ready(_size) => {
+        #[cfg(not(feature = "lvl3"))]
+        debug!{"Client sent {:?} bytes: {}", _size, sess.id};
+        check_read(&buf, Delay::deadline(&sess.timeout), sess.id)
+      },
+      // nothing yet, wait on whichever of the stream and the timer is first
+      Async::NotReady => {
+        try_ready!{sess.timeout.poll()};
+        #[cfg(not(feature = "lvl3"))]
+        error!{"Client timedout: {}", sess.id};
+        Err(Rejected::new(Reason::Timeout, String::new()))
+      },
+    };
     let sess = sess.take();
-    if buf.len() < MACHINE_SIZE {
-      #[cfg(not(feature = "lvl3"))]
-      error!{"Client sent too few bytes: {}", sess.id};
-      let sess = Incorrect {
-        stream: sess.stream,
-        id: sess.id,
-        reason: Reason::ReadTooSmall,
-        message: String::new(),
-      };
-      transition!{sess}
-    } else if timeout(sess.time) {
-      #[cfg(not(feature = "lvl3"))]
-      error!{"Client timedout: {}", sess.id};
-      let sess = Incorrect {
-        stream: sess.stream,
-        id: sess.id,
-        reason: Reason::Timeout,
-        message: String::new(),
-      };
-      transition!{sess}
-    } else {
-      let machine = Machine::from(buf);
-      #[cfg(not(feature = "lvl3"))]
-      info!{"Client created machine: {}\n{:?}", sess.id, machine};
-      let sess = Execute {
-        stream: sess.stream,
-        id: sess.id,
-        time: sess.time,
-        machine: machine,
-      };
-      transition!{sess}
+    match res {
+      Ok(machine) => {
+        let sess = Execute {
+          stream: sess.stream,
+          id: sess.id,
+          machine,
+        };
+        transition!{sess}
+      },
+      Err(rejected) => {
+        let sess = Incorrect {
+          stream: sess.stream,
+          id: sess.id,
+          reason: rejected.reason,
+          message: rejected.message,
+        };
+        transition!{sess}
+      },
     }
   }
 
-  fn poll_execute<'a>(sess: &'a mut RentToOwn<'a, Execute>)
-    -> Poll<AfterExecute, GameError>
+  fn poll_execute<'a>(sess: &'a mut RentToOwn<'a, Execute<S>>)
+    -> Poll<AfterExecute<S>, GameError>
   {
     #[cfg(not(feature = "lvl3"))]
     trace!{"Game::poll_execute({})", sess.id};
     let mut sess = sess.take();
-    let res = sess.machine.exec();
-    if let Ok(_) = res {
-      #[cfg(not(feature = "lvl3"))]
-      info!{"Client machine executed correctly: {}", sess.id};
-      let sess = Validate {
-        stream: sess.stream,
-        id: sess.id,
-        time: sess.time,
-        machine: sess.machine,
-      };
-      transition!{sess}
-    } else {
-      #[cfg(not(feature = "lvl3"))]
-      warn!{"Failed execution: {:?}", res};
-      let res = match res {
-        Err(e @ _) => e,
-        Ok(_) => MachineError::UnknownError,
-      };
-      let sess = Incorrect {
-        stream: sess.stream,
-        id: sess.id,
-        reason: Reason::BadExecution,
-        message: format!{"{:?}", res},
-      };
-      transition!{sess}
+    match check_execute(&mut sess.machine, sess.id) {
+      Ok(()) => {
+        let sess = Validate {
+          stream: sess.stream,
+          id: sess.id,
+          machine: sess.machine,
+        };
+        transition!{sess}
+      },
+      Err(rejected) => {
+        let sess = Incorrect {
+          stream: sess.stream,
+          id: sess.id,
+          reason: rejected.reason,
+          message: rejected.message,
+        };
+        transition!{sess}
+      },
     }
   }
 
-  fn poll_validate<'a>(sess: &'a mut RentToOwn<'a, Validate>)
-    -> Poll<AfterValidate, GameError>
+  fn poll_validate<'a>(sess: &'a mut RentToOwn<'a, Validate<S>>)
+    -> Poll<AfterValidate<S>, GameError>
   {
     #[cfg(not(feature = "lvl3"))]
     trace!{"Game::poll_validate({})", sess.id};
-    #[cfg(not(feature = "lvl3"))]
-    info!{"Clients end state: {:?}", sess.machine};
     let sess = sess.take();
-    if sess.machine.is_valid() {
-      #[cfg(not(feature = "lvl3"))]
-      debug!{"Client provided a valid machine!: {}", sess.id};
-      let sess = Correct {
-        stream: sess.stream,
-        id: sess.id,
-        message: format!{"Executed {} instructions", sess.machine.get_cc()},
-      };
-      transition!{sess}
-    } else {
-      #[cfg(not(feature = "lvl3"))]
-      debug!{"Client provided incorrect machine: {}", sess.id}
-      let sess = Incorrect {
-        stream: sess.stream,
-        id: sess.id,
-        reason: Reason::WrongAnswer,
-        message: format!{"Executed {} instructions", sess.machine.get_cc()},
-      };
-      transition!{sess}
+    match check_validate(&sess.machine, sess.id) {
+      Ok(message) => {
+        let sess = Correct {
+          stream: sess.stream,
+          id: sess.id,
+          message,
+        };
+        transition!{sess}
+      },
+      Err(rejected) => {
+        let sess = Incorrect {
+          stream: sess.stream,
+          id: sess.id,
+          reason: rejected.reason,
+          message: rejected.message,
+        };
+        transition!{sess}
+      },
     }
   }
 
-  fn poll_incorrect<'a>(sess: &'a mut RentToOwn<'a, Incorrect>)
+  fn poll_incorrect<'a>(sess: &'a mut RentToOwn<'a, Incorrect<S>>)
     -> Poll<AfterIncorrect, GameError>
   {
     #[cfg(not(feature = "lvl3"))]
     trace!{"Game::poll_incorrect({})", sess.id};
-    try_ready!{sess.stream.poll_write_ready()};
-    let msg = format!{"{}{}", sess.message, sess.reason};
+    let msg = incorrect_reply(&sess.message, &sess.reason);
     try_ready!{sess.stream.poll_write(msg.as_bytes())};
     transition!{Finished(sess.id)}
   }
 
-  fn poll_correct<'a>(sess: &'a mut RentToOwn<'a, Correct>)
+  fn poll_correct<'a>(sess: &'a mut RentToOwn<'a, Correct<S>>)
     -> Poll<AfterCorrect, GameError>
   {
     #[cfg(not(feature = "lvl3"))]
     trace!{"Game::poll_correct({})", sess.id};
-    try_ready!{sess.stream.poll_write_ready()};
-    let msg = "You provided the correct machine!\nFlag: ";
-    let msg = format!{"{}\n{}{}", sess.message, msg, FLAG};
+    let msg = correct_reply(&sess.message);
     try_ready!{sess.stream.poll_write(msg.as_bytes())};
     transition!{Finished(sess.id)}
   }
 }
 
-fn timeout(start: Instant) -> bool {
-  let timeout = Duration::new(TIMEOUT, 0);
-  let taken = Instant::now() - start;
-  timeout < taken
+// Why a session ends without the flag, and what the client is told first
+pub struct Rejected {
+  pub reason: Reason,
+  pub message: String,
+}
+impl Rejected {
+  fn new(reason: Reason, message: String) -> Self {
+    Rejected { reason, message }
+  }
+}
+
+// the machine a client's first read describes
+pub fn check_read(buf: &[u8], deadline: Instant, id: usize) -> Result<Machine, Rejected> {
+  if buf.len() < MACHINE_SIZE {
+    #[cfg(not(feature = "lvl3"))]
+    error!{"Client sent too few bytes: {}", id};
+    return Err(Rejected::new(Reason::ReadTooSmall, String::new()))
+  }
+  if deadline <= Instant::now() {
+    #[cfg(not(feature = "lvl3"))]
+    error!{"Client timedout: {}", id};
+    return Err(Rejected::new(Reason::Timeout, String::new()))
+  }
+  match Level::current().try_load(&buf[..MACHINE_SIZE], seed(id)) {
+    Ok(machine) => {
+      #[cfg(not(feature = "lvl3"))]
+      info!{"Client created machine: {}\n{:?}", id, machine};
+      Ok(machine)
+    },
+    Err(e) => {
+      #[cfg(not(feature = "lvl3"))]
+      error!{"Client sent an invalid machine: {}", id};
+      Err(Rejected::new(Reason::InvalidImage, format!{"{:?}", e}))
+    },
+  }
+}
+// runs the machine until it stops, faults are rejected
+pub fn check_execute(machine: &mut Machine, _id: usize) -> Result<(), Rejected> {
+  match machine.run() {
+    HaltReason::Fault(f) => {
+      let e = MachineError::Fault(f);
+      #[cfg(not(feature = "lvl3"))]
+      warn!{"Failed execution: {:?}", e};
+      Err(Rejected::new(Reason::BadExecution, crate::error_chain(&e)))
+    },
+    _reason => {
+      #[cfg(not(feature = "lvl3"))]
+      info!{"Client machine executed correctly, {}: {}", _reason, _id};
+      Ok(())
+    },
+  }
+}
+// the run's summary when the end state wins
+pub fn check_validate(machine: &Machine, _id: usize) -> Result<String, Rejected> {
+  #[cfg(not(feature = "lvl3"))]
+  info!{"Clients end state: {:?}", machine};
+  if machine.is_valid() {
+    #[cfg(not(feature = "lvl3"))]
+    debug!{"Client provided a valid machine!: {}", _id};
+    Ok(summary(machine))
+  } else {
+    #[cfg(not(feature = "lvl3"))]
+    debug!{"Client provided incorrect machine: {}", _id};
+    Err(Rejected::new(Reason::WrongAnswer, summary(machine)))
+  }
+}
+fn correct_reply(message: &str) -> String {
+  format!{"{}\nYou provided the correct machine!\nFlag: {}", message, FLAG}
+}
+fn incorrect_reply(message: &str, reason: &Reason) -> String {
+  format!{"{}{}", message, reason}
+}
+
+// calls, cycles and why the run stopped
+fn summary(machine: &Machine) -> String {
+  let reason = machine.get_halt_reason().map_or("running".to_string(), |r| r.to_string());
+  format!{"Executed {} instructions in {} cycles, {}", machine.get_cc(), machine.get_cycles(), reason}
+}
+
+fn seed(id: usize) -> u32 {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH)
+    .map(|d| d.subsec_nanos())
+    .unwrap_or(0);
+  now ^ (id as u32).rotate_left(16)
 }
 pub enum Reason {
   Timeout,
   ReadTooSmall,
+  InvalidImage,
   BadExecution,
   WrongAnswer,
 }
@@ -232,10 +303,11 @@ impl fmt::Display for Reason {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     let _ = write!(f, "\nFailed to complete TinyMachine\n");
     match *self {
-      Reason::Timeout => write!(f, "Too slow, timeout\n"),
-      Reason::ReadTooSmall => write!(f, "Not enough bytes sent\n"),
-      Reason::BadExecution => write!(f, "Your machine did something wrong\n"),
-      Reason::WrongAnswer => write!(f, "Sorry, incorrect answer.\n"),
+      Reason::Timeout => writeln!(f, "Too slow, timeout"),
+      Reason::ReadTooSmall => writeln!(f, "Not enough bytes sent"),
+      Reason::InvalidImage => writeln!(f, "That is not a machine that can run"),
+      Reason::BadExecution => writeln!(f, "Your machine did something wrong"),
+      Reason::WrongAnswer => writeln!(f, "Sorry, incorrect answer."),
     }
   }
 }
@@ -0,0 +1,128 @@
+use std::time::Duration;
+use tokio::runtime::current_thread::block_on_all;
+use crate::state::*;
+use crate::state::error::GameResult;
+use crate::machine::MachineInner;
+
+// also in fuzz/corpus as seeds
+#[cfg(not(any(feature="lvl1", feature="lvl2", feature="lvl3")))]
+const WINNING: MachineInner = [0x00, 0x00, 0, 0, 0, 0, 0, 0, 0, 0];
+// hlt by the invalid opcode at 0x6 with the flag text in memory
+#[cfg(feature="lvl1")]
+const WINNING: MachineInner = [0x60, 0x04, b'0', b'B', b'y', b't', b'e', b'C', b'T', b'F'];
+// AC already 5, hlt at 0
+#[cfg(feature="lvl2")]
+const WINNING: MachineInner = [0x00, 0x05, 0, 0, 0, 0, 0, 0, 0, 0];
+// LI=5: get; put; del; jnz 0, then del; jnz 5 sixteen times and hlt
+#[cfg(feature="lvl3")]
+const WINNING: MachineInner = [0x05, 0x00, 0x67, 0xD3, 0x0D, 0x35, 0, 0, 0, 0];
+
+#[test]
+fn check_states() {
+  let mut machine = check_read(&WINNING, std::time::Instant::now() + TIMEOUT, 0).ok().unwrap();
+  assert!{check_execute(&mut machine, 0).is_ok()};
+  assert!{check_validate(&machine, 0).is_ok()};
+  // extended pop on an empty stack faults under every level's policy
+  let mut machine = check_read(&[0; 10], std::time::Instant::now() + TIMEOUT, 0).ok().unwrap()
+    .set_isa(crate::instructions::Isa::Extended);
+  machine.get_mut_mem().set_loc(1, 3).unwrap();
+  let rejected = check_execute(&mut machine, 0).err().unwrap();
+  assert!{matches!{rejected.reason, Reason::BadExecution}};
+  assert!{rejected.message.contains("Stack")};
+}
+
+// the whole session on an in-memory stream, what the client got back
+fn game(peer_sends: &[u8], limit: Duration) -> (GameResult<usize>, String) {
+  let (stream, peer) = duplex();
+  peer.send(peer_sends);
+  peer.close();
+  let res = block_on_all(Game::start(stream, 7, limit));
+  (res, String::from_utf8(peer.received()).unwrap())
+}
+
+#[test]
+fn game_winning() {
+  let (res, reply) = game(&WINNING, TIMEOUT);
+  assert_eq!{res.ok(), Some(7)};
+  assert!{reply.starts_with(GREETING)};
+  assert!{reply.contains("Executed "), "{}", reply};
+  assert!{reply.contains("You provided the correct machine!\nFlag: "), "{}", reply};
+}
+#[cfg(any(feature="lvl1", feature="lvl2", feature="lvl3"))]
+#[test]
+fn game_wrong_answer() {
+  // hlt straight away
+  let (res, reply) = game(&[0; 10], TIMEOUT);
+  assert_eq!{res.ok(), Some(7)};
+  assert!{reply.contains("Executed 1 instructions"), "{}", reply};
+  assert!{reply.ends_with("Sorry, incorrect answer.\n"), "{}", reply};
+}
+// GET faults below level 3, level 1 halts on it instead
+#[cfg(not(any(feature="lvl1", feature="lvl3")))]
+#[test]
+fn game_bad_execution() {
+  let (res, reply) = game(&[0, 0, 0x60, 0, 0, 0, 0, 0, 0, 0], TIMEOUT);
+  assert_eq!{res.ok(), Some(7)};
+  assert!{reply.contains("Invalid instruction: 6"), "{}", reply};
+  assert!{reply.ends_with("Your machine did something wrong\n"), "{}", reply};
+}
+#[test]
+fn game_closed_early() {
+  let (res, reply) = game(&[], TIMEOUT);
+  assert_eq!{res.ok(), Some(7)};
+  assert!{reply.ends_with("Not enough bytes sent\n"), "{}", reply};
+}
+#[test]
+fn game_short_read() {
+  // the image is judged on the first read, the rest arrives too late
+  let (stream, peer) = duplex();
+  peer.send(&WINNING[..4]);
+  let late = peer.clone();
+  let sender = std::thread::spawn(move || {
+    std::thread::sleep(Duration::from_millis(50));
+    late.send(&WINNING[4..]);
+    late.close();
+  });
+  let res = block_on_all(Game::start(stream, 7, TIMEOUT));
+  sender.join().unwrap();
+  assert_eq!{res.ok(), Some(7)};
+  let reply = String::from_utf8(peer.received()).unwrap();
+  assert!{reply.ends_with("Not enough bytes sent\n"), "{}", reply};
+}
+#[test]
+fn game_waits_for_image() {
+  let (stream, peer) = duplex();
+  let client = peer.clone();
+  let sender = std::thread::spawn(move || {
+    std::thread::sleep(Duration::from_millis(20));
+    client.send(&WINNING);
+  });
+  let res = block_on_all(Game::start(stream, 7, TIMEOUT));
+  sender.join().unwrap();
+  assert_eq!{res.ok(), Some(7)};
+  let reply = String::from_utf8(peer.received()).unwrap();
+  assert!{reply.contains("Flag: "), "{}", reply};
+}
+#[test]
+fn game_timeout() {
+  let (stream, peer) = duplex();
+  let client = peer.clone();
+  let sender = std::thread::spawn(move || {
+    std::thread::sleep(Duration::from_millis(60));
+    client.send(&WINNING);
+  });
+  let res = block_on_all(Game::start(stream, 7, Duration::from_millis(20)));
+  sender.join().unwrap();
+  assert_eq!{res.ok(), Some(7)};
+  let reply = String::from_utf8(peer.received()).unwrap();
+  assert!{reply.ends_with("Too slow, timeout\n"), "{}", reply};
+}
+#[test]
+fn game_silent_client() {
+  // connected, never sends and never closes
+  let (stream, peer) = duplex();
+  let res = block_on_all(Game::start(stream, 7, Duration::from_millis(20)));
+  assert_eq!{res.ok(), Some(7)};
+  let reply = String::from_utf8(peer.received()).unwrap();
+  assert!{reply.ends_with("Too slow, timeout\n"), "{}", reply};
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+use std::rc::Rc;
+use std::collections::HashMap;
+
+pub type Sym = Rc<Term>;
+pub type Model = Vec<Option<u8>>;
+
+// Values are u8 with wrapping arithmetic, masking to a nibble is explicit
+#[derive(Clone,PartialEq,Debug)]
+pub enum Expr {
+  Const(u8),
+  Var(usize),
+  Add(Sym, Sym),
+  And(Sym, Sym),
+  Or(Sym, Sym),
+  Xor(Sym, Sym),
+  Shl(Sym, u8),
+  Shr(Sym, u8),
+  Eq(Sym, Sym), // 1 when equal, else 0
+  Lt(Sym, Sym), // 1 when less, else 0
+}
+
+// Expression node with its variables and upper bound cached. Terms share
+// children, so anything walking them must not expand the DAG into a tree.
+#[derive(Clone,PartialEq,Debug)]
+pub struct Term {
+  expr: Expr,
+  vars: u64,
+  max: u8,
+}
+impl fmt::Display for Term {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.expr {
+      Expr::Const(c) => write!{f, "{:#X}", c},
+      Expr::Var(v) => write!{f, "v{}", v},
+      Expr::Add(ref a, ref b) => write!{f, "({} + {})", a, b},
+      Expr::And(ref a, ref b) => write!{f, "({} & {})", a, b},
+      Expr::Or(ref a, ref b) => write!{f, "({} | {})", a, b},
+      Expr::Xor(ref a, ref b) => write!{f, "({} ^ {})", a, b},
+      Expr::Shl(ref a, n) => write!{f, "({} << {})", a, n},
+      Expr::Shr(ref a, n) => write!{f, "({} >> {})", a, n},
+      Expr::Eq(ref a, ref b) => write!{f, "({} == {})", a, b},
+      Expr::Lt(ref a, ref b) => write!{f, "({} < {})", a, b},
+    }
+  }
+}
+impl Term {
+  fn new(expr: Expr) -> Sym {
+    let (vars, max) = match expr {
+      Expr::Const(c) => (0, c),
+      Expr::Var(v) => (1 << v, crate::MAX_VALUE),
+      Expr::Add(ref a, ref b) => (a.vars | b.vars, a.max.saturating_add(b.max)),
+      Expr::And(ref a, ref b) => (a.vars | b.vars, a.max.min(b.max)),
+      Expr::Or(ref a, ref b) | Expr::Xor(ref a, ref b) =>
+        (a.vars | b.vars, fill(a.max.max(b.max))),
+      Expr::Shl(ref a, n) => (a.vars, ((a.max as u16) << n).min(0xFF) as u8),
+      Expr::Shr(ref a, n) => (a.vars, a.max >> n),
+      Expr::Eq(ref a, ref b) | Expr::Lt(ref a, ref b) => (a.vars | b.vars, 1),
+    };
+    Rc::new(Term { expr, vars, max })
+  }
+  pub fn get_expr(&self) -> &Expr {
+    &self.expr
+  }
+  pub fn get_const(&self) -> Option<u8> {
+    match self.expr {
+      Expr::Const(c) => Some(c),
+      _ => None,
+    }
+  }
+  // bitset of the variables referenced
+  pub fn vars(&self) -> u64 {
+    self.vars
+  }
+  // largest value the expression can take, variables are nibbles
+  pub fn max(&self) -> u8 {
+    self.max
+  }
+  // None while any referenced variable is unassigned
+  pub fn eval(&self, model: &[Option<u8>]) -> Option<u8> {
+    self.eval_shared(model, &mut HashMap::new())
+  }
+  // each shared node is evaluated once
+  fn eval_shared(&self, model: &[Option<u8>], memo: &mut Memo) -> Option<u8> {
+    let key = self as *const Term;
+    if let Some(v) = memo.get(&key) {
+      return *v;
+    }
+    let mut get = |t: &Sym| t.eval_shared(model, memo);
+    let v = match self.expr {
+      Expr::Const(c) => Some(c),
+      Expr::Var(v) => model.get(v).cloned().unwrap_or(None),
+      Expr::Add(ref a, ref b) => get(a).and_then(|x| Some(x.wrapping_add(get(b)?))),
+      Expr::And(ref a, ref b) => get(a).and_then(|x| Some(x & get(b)?)),
+      Expr::Or(ref a, ref b) => get(a).and_then(|x| Some(x | get(b)?)),
+      Expr::Xor(ref a, ref b) => get(a).and_then(|x| Some(x ^ get(b)?)),
+      Expr::Shl(ref a, n) => get(a).map(|x| ((x as u16) << n) as u8),
+      Expr::Shr(ref a, n) => get(a).map(|x| x >> n),
+      Expr::Eq(ref a, ref b) => get(a).and_then(|x| Some((x == get(b)?) as u8)),
+      Expr::Lt(ref a, ref b) => get(a).and_then(|x| Some((x < get(b)?) as u8)),
+    };
+    memo.insert(key, v);
+    v
+  }
+}
+
+type Memo = HashMap<*const Term, Option<u8>>;
+
+// smallest all ones mask covering value
+fn fill(value: u8) -> u8 {
+  ((value as u16 + 1).next_power_of_two() - 1) as u8
+}
+
+// Constructors fold constants so concrete execution stays concrete
+pub fn konst(value: u8) -> Sym {
+  Term::new(Expr::Const(value))
+}
+pub fn var(idx: usize) -> Sym {
+  Term::new(Expr::Var(idx))
+}
+pub fn add(a: &Sym, b: &Sym) -> Sym {
+  match (a.get_const(), b.get_const()) {
+    (Some(x), Some(y)) => konst(x.wrapping_add(y)),
+    (Some(0), _) => b.clone(),
+    (_, Some(0)) => a.clone(),
+    _ => Term::new(Expr::Add(a.clone(), b.clone())),
+  }
+}
+pub fn and(a: &Sym, b: &Sym) -> Sym {
+  match (a.get_const(), b.get_const()) {
+    (Some(x), Some(y)) => konst(x & y),
+    (Some(0), _) | (_, Some(0)) => konst(0),
+    // masks covering every possible bit are no-ops
+    (Some(m), _) if m == fill(m) && b.max() <= m => b.clone(),
+    (_, Some(m)) if m == fill(m) && a.max() <= m => a.clone(),
+    _ => Term::new(Expr::And(a.clone(), b.clone())),
+  }
+}
+pub fn or(a: &Sym, b: &Sym) -> Sym {
+  match (a.get_const(), b.get_const()) {
+    (Some(x), Some(y)) => konst(x | y),
+    (Some(0), _) => b.clone(),
+    (_, Some(0)) => a.clone(),
+    _ => Term::new(Expr::Or(a.clone(), b.clone())),
+  }
+}
+pub fn xor(a: &Sym, b: &Sym) -> Sym {
+  match (a.get_const(), b.get_const()) {
+    (Some(x), Some(y)) => konst(x ^ y),
+    (Some(0), _) => b.clone(),
+    (_, Some(0)) => a.clone(),
+    _ => Term::new(Expr::Xor(a.clone(), b.clone())),
+  }
+}
+pub fn shl(a: &Sym, n: u8) -> Sym {
+  match a.get_const() {
+    Some(x) => konst(((x as u16) << n) as u8),
+    None if n == 0 => a.clone(),
+    None => Term::new(Expr::Shl(a.clone(), n)),
+  }
+}
+pub fn shr(a: &Sym, n: u8) -> Sym {
+  match a.get_const() {
+    Some(x) => konst(x >> n),
+    None if n == 0 => a.clone(),
+    None if a.max() >> n == 0 => konst(0),
+    None => Term::new(Expr::Shr(a.clone(), n)),
+  }
+}
+pub fn eq(a: &Sym, b: &Sym) -> Sym {
+  match (a.get_const(), b.get_const()) {
+    (Some(x), Some(y)) => konst((x == y) as u8),
+    (_, Some(y)) if a.max() < y => konst(0),
+    (Some(x), _) if b.max() < x => konst(0),
+    _ if Rc::ptr_eq(a, b) => konst(1),
+    _ => Term::new(Expr::Eq(a.clone(), b.clone())),
+  }
+}
+pub fn lt(a: &Sym, b: &Sym) -> Sym {
+  match (a.get_const(), b.get_const()) {
+    (Some(x), Some(y)) => konst((x < y) as u8),
+    (_, Some(y)) if a.max() < y => konst(1),
+    (_, Some(0)) => konst(0),
+    _ => Term::new(Expr::Lt(a.clone(), b.clone())),
+  }
+}
+// single bit n of a as 0 or 1
+pub fn bit(a: &Sym, n: u8) -> Sym {
+  and(&shr(a, n), &konst(1))
+}
+// logical not of a 0 or 1 value
+pub fn not(a: &Sym) -> Sym {
+  xor(a, &konst(1))
+}
+pub fn all(conds: &[Sym]) -> Sym {
+  conds.iter().fold(konst(1), |acc, c| match acc.get_const() {
+    Some(1) => c.clone(),
+    _ => and(&acc, c),
+  })
+}
+
+#[derive(Clone,PartialEq,Debug)]
+pub enum Satisfy {
+  Sat(Model), // every variable referenced by the constraints is assigned
+  Unsat,
+  Unknown,    // gave up after the node limit
+}
+
+// Backtracking search over nibble variables. Each constraint must evaluate
+// non zero and is checked as soon as its last variable is assigned.
+pub fn satisfy(constraints: &[Sym], limit: usize) -> Satisfy {
+  #[cfg(not(feature = "lvl3"))]
+  trace!{"symbolic::satisfy()"};
+  let mut sorted: Vec<&Sym> = Vec::with_capacity(constraints.len());
+  for c in constraints.iter() {
+    match c.get_const() {
+      Some(0) => return Satisfy::Unsat,
+      Some(_) => {},
+      None => sorted.push(c),
+    }
+  }
+  // constraints on fewer variables first, so they prune near the root
+  sorted.sort_by_key(|c| c.vars().count_ones());
+  let mut order: Vec<usize> = Vec::new();
+  let mut checks: Vec<Vec<&Sym>> = Vec::new();
+  let mut seen = 0u64;
+  for c in sorted {
+    let mut fresh = c.vars() & !seen;
+    while fresh != 0 {
+      let v = fresh.trailing_zeros() as usize;
+      fresh &= fresh - 1;
+      order.push(v);
+      checks.push(Vec::new());
+    }
+    seen |= c.vars();
+    // order is non empty, a constraint with variables added at least one
+    checks.last_mut().unwrap().push(c);
+  }
+  let size = order.iter().max().map(|v| v + 1).unwrap_or(0);
+  let mut model: Model = vec![None; size];
+  let mut nodes = 0;
+  match assign(&order, &checks, &mut model, 0, &mut nodes, limit) {
+    Some(true) => Satisfy::Sat(model),
+    Some(false) => Satisfy::Unsat,
+    None => Satisfy::Unknown,
+  }
+}
+
+fn assign(order: &[usize], checks: &[Vec<&Sym>], model: &mut Model,
+          depth: usize, nodes: &mut usize, limit: usize) -> Option<bool> {
+  if depth == order.len() {
+    return Some(true);
+  }
+  for value in 0..=crate::MAX_VALUE {
+    *nodes += 1;
+    if limit < *nodes {
+      return None;
+    }
+    model[order[depth]] = Some(value);
+    let ok = checks[depth].iter().all(|c| c.eval(model).unwrap_or(0) != 0);
+    if ok && assign(order, checks, model, depth + 1, nodes, limit)? {
+      return Some(true);
+    }
+  }
+  model[order[depth]] = None;
+  Some(false)
+}
@@ -0,0 +1,465 @@
+#[cfg(test)] mod test;
+pub mod expr;
+
+use std::collections::HashSet;
+use crate::instructions::{Instruction, InstructionError, ADDR_BITS, VALUE_BITS};
+use crate::machine::{MachineInner, MACHINE_SIZE};
+use crate::memory::MEMORY_SIZE;
+use crate::ports::{PortMode, PortError, PORT_SIZE};
+use crate::registers::{CF_BIT, ZF_BIT, OF_BIT, HF_BIT};
+use crate::machine::{get_nibble, set_nibble, IMAGE_NIBBLES, MEMORY_OFFSET};
+use crate::level::Level;
+use crate::faults::{FaultKind, FaultAction};
+
+use self::expr::*;
+
+pub use self::expr::{Expr, Sym, Model, Satisfy, satisfy};
+
+pub const VAR_COUNT: usize = IMAGE_NIBBLES + PORT_SIZE; // image nibbles then input values
+pub const SOLVER_LIMIT: usize = 1 << 16; // nodes per satisfy call
+const MEMORY_NIBBLES: usize = MEMORY_SIZE * 2;
+
+// variable standing for input value idx
+pub fn input_var(idx: usize) -> usize {
+  IMAGE_NIBBLES + idx
+}
+fn mask(bits: &std::ops::Range<usize>) -> u8 {
+  ((1u16 << bits.end) - (1u16 << bits.start)) as u8
+}
+
+#[derive(Clone,PartialEq,Debug)]
+pub enum End {
+  Halted,                   // HLT, or a fault the level halts on
+  Budget,                   // the level's call budget reached
+  Fault(InstructionError),
+}
+
+// Machine state where every nibble is an expression over image and input
+// variables. Devices, interrupts and the extended ISA are not modelled, so
+// on Level::Open the random nibble reads as the image rather than the bus.
+#[derive(Clone,Debug)]
+pub struct SymMachine {
+  ip: Sym,
+  li: Sym,
+  fr: Sym,
+  ac: Sym,
+  mem: Vec<Sym>,
+  inp: Vec<Sym>,
+  outp: Vec<Sym>,
+  mode: PortMode,
+  invalid: FaultAction, // the level's policy for invalid opcodes
+  budget: usize,
+  calls: usize,
+  path: Vec<Sym>, // conditions that hold on every image reaching this state
+  end: Option<End>,
+}
+impl SymMachine {
+  fn new(base: &MachineInner, symbolic: u32, inp: Vec<Sym>, mode: PortMode, invalid: FaultAction, budget: usize) -> Self {
+    let nibble = |idx: usize| match (symbolic >> idx) & 1 {
+      1 => var(idx),
+      _ => konst(get_nibble(base, idx)),
+    };
+    // register nibble order matches RegisterInner, IP|LI then FR|AC
+    SymMachine {
+      ip: nibble(0),
+      li: nibble(1),
+      fr: nibble(2),
+      ac: nibble(3),
+      mem: (0..MEMORY_NIBBLES).map(|i| nibble(MEMORY_OFFSET + i)).collect(),
+      inp,
+      outp: Vec::new(),
+      mode,
+      invalid,
+      budget,
+      calls: 0,
+      path: Vec::new(),
+      end: None,
+    }
+  }
+  pub fn get_ip(&self) -> &Sym { &self.ip }
+  pub fn get_li(&self) -> &Sym { &self.li }
+  pub fn get_fr(&self) -> &Sym { &self.fr }
+  pub fn get_ac(&self) -> &Sym { &self.ac }
+  pub fn get_cf(&self) -> Sym { bit(&self.fr, CF_BIT as u8) }
+  pub fn get_zf(&self) -> Sym { bit(&self.fr, ZF_BIT as u8) }
+  pub fn get_of(&self) -> Sym { bit(&self.fr, OF_BIT as u8) }
+  pub fn get_hf(&self) -> Sym { bit(&self.fr, HF_BIT as u8) }
+  pub fn get_mem(&self, addr: usize) -> &Sym { &self.mem[addr % MEMORY_NIBBLES] }
+  pub fn get_inp(&self) -> &[Sym] { &self.inp }
+  pub fn get_outp(&self) -> &[Sym] { &self.outp }
+  pub fn get_cc(&self) -> usize { self.calls }
+  pub fn get_path(&self) -> &[Sym] { &self.path }
+  pub fn get_end(&self) -> Option<&End> { self.end.as_ref() }
+
+  fn set_flag(&mut self, flag: usize, value: &Sym) {
+    let cleared = and(&self.fr, &konst(!(1u8 << flag) & crate::MAX_VALUE));
+    self.fr = or(&cleared, &shl(value, flag as u8));
+  }
+  // like Registers::set_ac/set_li, zf follows the new value
+  fn set_ac(&mut self, value: Sym) {
+    let zf = eq(&value, &konst(0));
+    self.ac = value;
+    self.set_flag(ZF_BIT, &zf);
+  }
+  fn set_li(&mut self, value: Sym) {
+    let zf = eq(&value, &konst(0));
+    self.li = value;
+    self.set_flag(ZF_BIT, &zf);
+  }
+  fn inc_ip(&mut self, by: u8) {
+    let ip = self.ip.get_const().unwrap_or(0);
+    self.ip = konst((ip + by) % (crate::MAX_VALUE + 1));
+  }
+  fn fault(&mut self, err: InstructionError) {
+    self.set_flag(HF_BIT, &konst(1));
+    self.end = Some(End::Fault(err));
+  }
+  fn halt(&mut self) {
+    self.set_flag(HF_BIT, &konst(1));
+    self.calls += 1;
+    self.end = Some(End::Halted);
+  }
+  fn feasible(&self, limit: usize) -> bool {
+    satisfy(&self.path, limit) != Satisfy::Unsat
+  }
+  // one state per feasible concrete value of value, with that value
+  fn concretize(&self, value: &Sym, limit: usize) -> Vec<(SymMachine, u8)> {
+    if let Some(c) = value.get_const() {
+      return vec![(self.clone(), c)];
+    }
+    (0..=value.max()).filter_map(|c| {
+      let mut state = self.clone();
+      state.path.push(eq(value, &konst(c)));
+      match state.feasible(limit) {
+        true => Some((state, c)),
+        false => None,
+      }
+    }).collect()
+  }
+  // follows Machine::exec for a single instruction, forking where the
+  // next state depends on a symbolic value
+  fn step(&self, limit: usize) -> Vec<SymMachine> {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"SymMachine::step()"};
+    // like Machine::run, HF already set stops before the next fetch
+    let hf = self.get_hf();
+    let mut next = Vec::new();
+    for (mut state, hf) in self.concretize(&hf, limit) {
+      match hf {
+        1 => {
+          state.end = Some(End::Halted);
+          next.push(state);
+        },
+        _ => next.extend(state.fetch(limit)),
+      };
+    }
+    next
+  }
+  fn fetch(&self, limit: usize) -> Vec<SymMachine> {
+    if self.calls >= self.budget {
+      let mut state = self.clone();
+      state.end = Some(End::Budget);
+      return vec![state];
+    }
+    let mut next = Vec::new();
+    for (mut state, ip) in self.concretize(&self.ip, limit) {
+      state.ip = konst(ip);
+      let ip = ip as usize;
+      let op = state.mem[ip].clone();
+      for (state, op) in state.concretize(&op, limit) {
+        let inst = Instruction::from(op << 4);
+        if inst.size() == 1 {
+          next.extend(state.call(inst, limit));
+          continue;
+        }
+        // only the ADDR_BITS of the second nibble are decoded
+        let addr = and(&state.mem[(ip + 1) % MEMORY_NIBBLES], &konst(mask(&ADDR_BITS)));
+        for (state, addr) in state.concretize(&addr, limit) {
+          next.extend(state.call(Instruction::from(op << 4 | addr), limit));
+        }
+      }
+    }
+    next
+  }
+  fn call(mut self, inst: Instruction, limit: usize) -> Vec<SymMachine> {
+    let value = konst(mask(&VALUE_BITS));
+    match inst {
+      Instruction::HLT => {
+        self.halt();
+        return vec![self];
+      },
+      Instruction::JMP(addr) => self.ip = konst(addr),
+      Instruction::JZE(addr) | Instruction::JNZ(addr) => {
+        let want = match inst {
+          Instruction::JZE(_) => 1,
+          _ => 0,
+        };
+        let zf = self.get_zf();
+        return self.concretize(&zf, limit).into_iter().map(|(mut state, zf)| {
+          match zf == want {
+            true => state.ip = konst(addr),
+            false => state.inc_ip(2),
+          }
+          state.calls += 1;
+          state
+        }).collect();
+      },
+      Instruction::LDA(addr) => {
+        self.inc_ip(2);
+        let ac = self.mem[addr as usize].clone();
+        self.set_ac(ac);
+      },
+      Instruction::STA(addr) => {
+        self.inc_ip(2);
+        self.mem[addr as usize] = self.ac.clone();
+      },
+      Instruction::GET | Instruction::PUT => {
+        if !cfg!(feature = "lvl3") {
+          // nop and trap policies are not modelled
+          match self.invalid {
+            FaultAction::Halt => self.halt(),
+            _ => self.fault(InstructionError::InvalidInstruction(inst.opcode())),
+          };
+          return vec![self];
+        }
+        self.inc_ip(1);
+        if inst == Instruction::GET {
+          match (self.inp.is_empty(), self.mode) {
+            (true, PortMode::Fault) => self.fault(PortError::Underflow.into()),
+            (true, PortMode::Saturate) => self.set_ac(konst(0)),
+            (false, _) => {
+              let ac = self.inp.remove(0);
+              self.set_ac(ac);
+            },
+          };
+        } else {
+          match (self.outp.len() == PORT_SIZE, self.mode) {
+            (true, PortMode::Fault) => {
+              let ac = self.ac.clone();
+              return self.concretize(&ac, limit).into_iter().map(|(mut state, ac)| {
+                state.fault(PortError::Overflow(ac).into());
+                state
+              }).collect();
+            },
+            (true, PortMode::Saturate) => {
+              self.outp.remove(0);
+              self.outp.push(self.ac.clone());
+            },
+            (false, _) => self.outp.push(self.ac.clone()),
+          };
+        }
+        if self.end.is_some() {
+          return vec![self];
+        }
+      },
+      // u8 rotate of a nibble, the carry enters at bit 0 and leaves from bit 4
+      Instruction::ROL => {
+        self.inc_ip(1);
+        let cf = self.get_cf();
+        let rot = or(&shl(&self.ac, 1), &cf);
+        let cf2 = bit(&rot, 4);
+        self.set_ac(and(&rot, &value));
+        self.set_flag(CF_BIT, &cf2);
+        self.set_flag(OF_BIT, &eq(&cf, &cf2));
+        let zf = eq(&self.ac, &konst(0));
+        self.set_flag(ZF_BIT, &zf);
+      },
+      // the carry enters at bit 4 and leaves from bit 7
+      Instruction::ROR => {
+        self.inc_ip(1);
+        let cf = self.get_cf();
+        let rot = or(&or(&shr(&self.ac, 1), &shl(&bit(&self.ac, 0), 7)), &shl(&cf, 4));
+        let cf2 = bit(&rot, 7);
+        self.set_ac(and(&rot, &value));
+        self.set_flag(CF_BIT, &cf2);
+        self.set_flag(OF_BIT, &eq(&cf, &cf2));
+        let zf = eq(&self.ac, &konst(0));
+        self.set_flag(ZF_BIT, &zf);
+      },
+      Instruction::ADC(addr) => {
+        self.inc_ip(2);
+        let cf = self.get_cf();
+        let sum = add(&add(&self.ac, &self.mem[addr as usize]), &cf);
+        self.set_ac(and(&sum, &value));
+        let cf2 = bit(&sum, 4);
+        self.set_flag(CF_BIT, &cf2);
+        self.set_flag(OF_BIT, &eq(&cf, &cf2));
+        let zf = eq(&self.ac, &konst(0));
+        self.set_flag(ZF_BIT, &zf);
+      },
+      Instruction::CCF => {
+        self.inc_ip(1);
+        self.set_flag(CF_BIT, &konst(0));
+      },
+      Instruction::SCF => {
+        self.inc_ip(1);
+        self.set_flag(CF_BIT, &konst(1));
+      },
+      Instruction::DEL => {
+        self.inc_ip(1);
+        let li = and(&add(&self.li, &konst(crate::MAX_VALUE)), &konst(crate::MAX_VALUE));
+        self.set_li(li);
+      },
+      Instruction::LDL(addr) => {
+        self.inc_ip(2);
+        let li = self.mem[addr as usize].clone();
+        self.set_li(li);
+      },
+      Instruction::FLA => {
+        self.inc_ip(1);
+        let ac = and(&xor(&self.ac, &value), &value);
+        self.set_ac(ac);
+      },
+      // standard decoding never yields the extended instructions
+      inst => {
+        self.fault(InstructionError::InvalidInstruction(inst.opcode()));
+        return vec![self];
+      },
+    };
+    self.calls += 1;
+    vec![self]
+  }
+}
+
+#[derive(Clone,PartialEq,Debug)]
+pub struct Found {
+  pub image: MachineInner,
+  pub input: Vec<u8>, // values for the symbolic inputs
+  pub calls: usize,
+  pub end: End,
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct SymReport {
+  pub found: Vec<Found>,
+  pub paths: usize,     // finished paths considered
+  pub complete: bool,   // every path was explored and decided
+}
+
+// Explores every path from an image whose selected nibbles are unknown
+pub struct Executor {
+  level: Level,
+  base: MachineInner,
+  symbolic: u32,
+  inputs: Option<usize>,
+  max_paths: usize,
+  max_solutions: usize,
+  limit: usize,
+}
+impl Executor {
+  pub fn new(level: Level, base: MachineInner) -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Executor::new()"};
+    Executor {
+      level,
+      base,
+      symbolic: 0,
+      inputs: None,
+      max_paths: 1 << 12,
+      max_solutions: 64,
+      limit: SOLVER_LIMIT,
+    }
+  }
+  // image nibble indexes treated as variables
+  pub fn set_symbolic(mut self, nibbles: Vec<usize>) -> Self {
+    self.symbolic = nibbles.into_iter()
+      .filter(|i| *i < IMAGE_NIBBLES)
+      .fold(0, |acc, i| acc | 1 << i);
+    self
+  }
+  // replaces the level's input with count symbolic values
+  pub fn set_symbolic_input(mut self, count: usize) -> Self {
+    self.inputs = Some(count.min(PORT_SIZE));
+    self
+  }
+  pub fn set_max_paths(mut self, max: usize) -> Self {
+    self.max_paths = max;
+    self
+  }
+  pub fn set_max_solutions(mut self, max: usize) -> Self {
+    self.max_solutions = max;
+    self
+  }
+  pub fn set_limit(mut self, limit: usize) -> Self {
+    self.limit = limit;
+    self
+  }
+  pub fn start(&self) -> SymMachine {
+    let inp = match self.inputs {
+      Some(count) => (0..count).map(|i| var(input_var(i))).collect(),
+      None => self.level.input().iter().map(konst).collect(),
+    };
+    let invalid = self.level.faults().get(FaultKind::InvalidOpcode);
+    SymMachine::new(&self.base, self.symbolic, inp, self.level.port_mode(), invalid, self.level.budget())
+  }
+  // finished states, depth first, and whether every path was reached
+  pub fn explore(&self) -> (Vec<SymMachine>, bool) {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Executor::explore()"};
+    let mut done = Vec::new();
+    let mut work = vec![self.start()];
+    while let Some(state) = work.pop() {
+      if self.max_paths <= done.len() {
+        return (done, false);
+      }
+      for next in state.step(self.limit).into_iter().rev() {
+        match next.end {
+          Some(_) => done.push(next),
+          None => work.push(next),
+        }
+      }
+    }
+    (done, true)
+  }
+  // concrete images whose run ends in a state satisfying target
+  pub fn solve<F>(&self, target: F) -> SymReport
+    where F: Fn(&SymMachine) -> Sym
+  {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Executor::solve()"};
+    let (states, mut complete) = self.explore();
+    let mut report = SymReport { paths: states.len(), ..Default::default() };
+    let mut seen = HashSet::new();
+    for state in states.iter() {
+      if self.max_solutions <= report.found.len() {
+        complete = false;
+        break;
+      }
+      let mut constraints = state.path.clone();
+      constraints.push(target(state));
+      let model = match satisfy(&constraints, self.limit) {
+        Satisfy::Sat(model) => model,
+        Satisfy::Unsat => continue,
+        Satisfy::Unknown => {
+          complete = false;
+          continue;
+        },
+      };
+      let found = self.concrete(state, &model);
+      if seen.insert((found.image, found.input.clone())) {
+        report.found.push(found);
+      }
+    }
+    report.complete = complete;
+    report
+  }
+  // unconstrained variables keep the base image value, or 0 for inputs
+  fn concrete(&self, state: &SymMachine, model: &[Option<u8>]) -> Found {
+    let mut image: MachineInner = [0; MACHINE_SIZE];
+    image.copy_from_slice(&self.base);
+    for idx in (0..IMAGE_NIBBLES).filter(|i| (self.symbolic >> i) & 1 == 1) {
+      if let Some(Some(v)) = model.get(idx) {
+        set_nibble(&mut image, idx, *v);
+      }
+    }
+    let input = (0..self.inputs.unwrap_or(0))
+      .map(|i| model.get(input_var(i)).cloned().unwrap_or(None).unwrap_or(0))
+      .collect();
+    Found {
+      image,
+      input,
+      calls: state.calls,
+      end: state.end.clone().unwrap_or(End::Budget),
+    }
+  }
+}
@@ -0,0 +1,142 @@
+use crate::symbolic::*;
+use crate::symbolic::expr::*;
+use crate::machine::*;
+use crate::devices::Random;
+use crate::machine::{get_nibble, MEMORY_OFFSET};
+use crate::level::Level;
+
+// concrete end state of a found image, for comparing against the path
+fn replay(found: &Found) -> (End, usize, Machine) {
+  let mut machine = Level::Two.load(found.image, 0);
+  for v in found.input.iter() {
+    machine.push_inp(*v).unwrap();
+  }
+  let end = match machine.exec() {
+    Ok(_) if machine.get_reg().get_hf() => End::Halted,
+    Ok(_) => End::Budget,
+    Err(MachineError::Fault(f)) => End::Fault(f.error),
+    Err(e) => panic!{"unexpected {:?}", e},
+  };
+  let calls = machine.get_cc();
+  (end, calls, machine)
+}
+
+#[test]
+fn expr_folding() {
+  assert_eq!{eq(&konst(3), &konst(3)).get_const(), Some(1)};
+  assert_eq!{and(&var(0), &konst(0xF)), var(0)};
+  assert_eq!{shr(&var(0), 4).get_const(), Some(0)};
+  assert_eq!{eq(&var(0), &konst(0x10)).get_const(), Some(0)};
+  let e = add(&var(0), &konst(1));
+  assert_eq!{e.eval(&[Some(0xF)]), Some(0x10)};
+  assert_eq!{e.eval(&[None]), None};
+}
+#[test]
+fn satisfy_nibbles() {
+  let sum = eq(&add(&var(0), &var(1)), &konst(0x13));
+  let small = lt(&var(0), &konst(5));
+  match satisfy(&[sum.clone(), small], 1 << 12) {
+    Satisfy::Sat(model) => {
+      let (x, y) = (model[0].unwrap(), model[1].unwrap());
+      assert_eq!{x + y, 0x13};
+      assert!{x < 5};
+    },
+    s => panic!{"expected a model, got {:?}", s},
+  };
+  let clash = [eq(&var(2), &konst(3)), eq(&var(2), &konst(4))];
+  assert_eq!{satisfy(&clash, 1 << 12), Satisfy::Unsat};
+  assert_eq!{satisfy(&[sum], 2), Satisfy::Unknown};
+}
+#[test]
+fn branch_paths() {
+  // lda 7; jze 5; hlt; scf; hlt; with the loaded nibble symbolic
+  let base: MachineInner = [0x00, 0x00, 0x47, 0x25, 0x0C, 0x00, 0, 0, 0, 0];
+  let exec = Executor::new(Level::Two, base).set_symbolic(vec![MEMORY_OFFSET + 7]);
+  let (states, complete) = exec.explore();
+  assert!{complete};
+  assert_eq!{states.len(), 2};
+  let report = exec.solve(|s| s.get_cf());
+  assert!{report.complete};
+  assert_eq!{report.found.len(), 1};
+  let found = &report.found[0];
+  assert_eq!{get_nibble(&found.image, MEMORY_OFFSET + 7), 0};
+  assert_eq!{(found.end.clone(), found.calls), (End::Halted, 4)};
+  let (end, calls, machine) = replay(found);
+  assert_eq!{(end, calls), (End::Halted, 4)};
+  assert!{machine.get_reg().get_cf()};
+}
+#[test]
+fn symbolic_opcode() {
+  // ? 7; hlt ...; 5 at nibble 7
+  let base: MachineInner = [0x00, 0x00, 0x07, 0x00, 0x00, 0x05, 0, 0, 0, 0];
+  let report = Executor::new(Level::Two, base)
+    .set_symbolic(vec![MEMORY_OFFSET])
+    .solve(|s| eq(s.get_ac(), &konst(5)));
+  assert!{report.complete};
+  let mut ops: Vec<u8> = report.found.iter()
+    .map(|f| get_nibble(&f.image, MEMORY_OFFSET))
+    .collect();
+  ops.sort();
+  // lda 7 or adc 7
+  assert_eq!{ops, vec![0x4, 0xA]};
+  for found in report.found.iter() {
+    let (_, _, machine) = replay(found);
+    assert_eq!{machine.get_reg().get_ac(), 5};
+  }
+}
+#[test]
+fn budget_path() {
+  // jmp 0
+  let base: MachineInner = [0x00, 0x00, 0x10, 0, 0, 0, 0, 0, 0, 0];
+  let (states, complete) = Executor::new(Level::Two, base).explore();
+  assert!{complete};
+  assert_eq!{states.len(), 1};
+  assert_eq!{states[0].get_end(), Some(&End::Budget)};
+  assert_eq!{states[0].get_cc(), MAX_CALLS};
+}
+#[test]
+fn hf_preset() {
+  // HF set, lda 7; hlt never runs
+  let base: MachineInner = [0x00, 0x80, 0x47, 0x00, 0, 0, 0, 0, 0, 0x05];
+  let (states, complete) = Executor::new(Level::Two, base).explore();
+  assert!{complete};
+  assert_eq!{states.len(), 1};
+  assert_eq!{(states[0].get_end(), states[0].get_cc()), (Some(&End::Halted), 0)};
+  // FR symbolic, one path stops at once and the other runs
+  let report = Executor::new(Level::Two, base)
+    .set_symbolic(vec![2])
+    .solve(|_| konst(1));
+  assert!{report.complete};
+  assert_eq!{report.found.len(), 2};
+  for found in report.found.iter() {
+    let (end, calls, _) = replay(found);
+    assert_eq!{(end, calls), (found.end.clone(), found.calls)};
+  }
+  let mut calls: Vec<usize> = report.found.iter().map(|f| f.calls).collect();
+  calls.sort();
+  assert_eq!{calls, vec![0, 2]};
+}
+#[test]
+fn differential_random_images() {
+  let rng = Random::new(0x5eed);
+  for _ in 0..16 {
+    let mut base: MachineInner = [0; MACHINE_SIZE];
+    for b in base.iter_mut().skip(2) {
+      *b = rng.next_u32() as u8;
+    }
+    let free = vec![
+      MEMORY_OFFSET + rng.next_u32() as usize % 16,
+      MEMORY_OFFSET + rng.next_u32() as usize % 16,
+    ];
+    let report = Executor::new(Level::Two, base)
+      .set_symbolic(free)
+      .set_max_solutions(256)
+      .solve(|_| konst(1));
+    assert!{report.complete};
+    assert_eq!{report.found.len(), report.paths};
+    for found in report.found.iter() {
+      let (end, calls, _) = replay(found);
+      assert_eq!{(end, calls), (found.end.clone(), found.calls)};
+    }
+  }
+}
@@ -0,0 +1,350 @@
+#[macro_use] extern crate log;
+extern crate pretty_env_logger;
+extern crate clap;
+
+extern crate tiny_machine;
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use tiny_machine::prelude::*;
+
+fn main() {
+  pretty_env_logger::init();
+
+  let matches = App::new("tm_tools")
+    .about("Offline tooling for TinyMachine images")
+    .setting(AppSettings::SubcommandRequiredElseHelp)
+    .subcommand(SubCommand::with_name("solve")
+      .about("Search for images that win the level this was built for")
+      .arg(Arg::with_name("template")
+        .help("Hex image, '?' marks a nibble the search may change")
+        .required(true))
+      .arg(Arg::with_name("search")
+        .long("search")
+        .takes_value(true)
+        .possible_values(&["brute", "random", "genetic"])
+        .default_value("brute"))
+      .arg(Arg::with_name("budget")
+        .long("budget")
+        .help("Most images to execute")
+        .takes_value(true)
+        .default_value("1048576"))
+      .arg(Arg::with_name("max")
+        .long("max")
+        .help("Most solutions to report")
+        .takes_value(true)
+        .default_value("64"))
+      .arg(Arg::with_name("minimize")
+        .long("minimize")
+        .takes_value(true)
+        .possible_values(&["calls", "changes"]))
+      .arg(Arg::with_name("seed")
+        .long("seed")
+        .takes_value(true)
+        .default_value("1"))
+      .arg(Arg::with_name("format")
+        .long("format")
+        .help("How to print solutions, hex keeps '?' for unused nibbles")
+        .takes_value(true)
+        .possible_values(&["hex", "ihex", "text"])
+        .default_value("hex")))
+    .subcommand(SubCommand::with_name("paths")
+      .about("Symbolically explore every path, '?' nibbles are unknown")
+      .arg(Arg::with_name("template")
+        .help("Hex image, '?' marks a symbolic nibble")
+        .required(true))
+      .arg(Arg::with_name("inputs")
+        .long("inputs")
+        .help("Replace the level input with this many symbolic values")
+        .takes_value(true))
+      .arg(Arg::with_name("max")
+        .long("max")
+        .help("Most paths to explore")
+        .takes_value(true)
+        .default_value("4096")))
+    .subcommand(SubCommand::with_name("dot")
+      .about("Print the control flow graph from IP as Graphviz DOT")
+      .arg(Arg::with_name("image")
+        .help("Hex image or image file")
+        .required(true))
+      .arg(Arg::with_name("extended")
+        .long("extended")
+        .help("Decode with the extended instruction set")))
+    .subcommand(SubCommand::with_name("trace")
+      .about("Run an image and print every step, or print a saved trace")
+      .arg(Arg::with_name("image")
+        .help("Hex image or image file, run under the level this was built for")
+        .required_unless("load"))
+      .arg(Arg::with_name("save")
+        .long("save")
+        .help("Also write the binary trace to this file")
+        .takes_value(true))
+      .arg(Arg::with_name("load")
+        .long("load")
+        .help("Binary trace file to print")
+        .takes_value(true)
+        .conflicts_with("image")))
+    .subcommand(SubCommand::with_name("diff")
+      .about("Run two images in lockstep and show where they first diverge")
+      .arg(Arg::with_name("left")
+        .help("Hex image or image file")
+        .required(true))
+      .arg(Arg::with_name("right")
+        .help("Hex image or image file, the left one again when omitted"))
+      .arg(Arg::with_name("left-isa")
+        .long("left-isa")
+        .takes_value(true)
+        .possible_values(&["standard", "extended"])
+        .default_value("standard"))
+      .arg(Arg::with_name("right-isa")
+        .long("right-isa")
+        .takes_value(true)
+        .possible_values(&["standard", "extended"])
+        .default_value("standard"))
+      .arg(Arg::with_name("left-endian")
+        .long("left-endian")
+        .takes_value(true)
+        .possible_values(&["big", "little"])
+        .default_value("big"))
+      .arg(Arg::with_name("right-endian")
+        .long("right-endian")
+        .takes_value(true)
+        .possible_values(&["big", "little"])
+        .default_value("big"))
+      .arg(Arg::with_name("context")
+        .long("context")
+        .help("Steps shown before the divergence")
+        .takes_value(true)
+        .default_value("3")))
+    .subcommand(SubCommand::with_name("debug")
+      .about("Run to the first breakpoint or stop, optionally step back, and print the state")
+      .arg(Arg::with_name("image")
+        .help("Hex image or image file")
+        .required(true))
+      .arg(Arg::with_name("break")
+        .long("break")
+        .help("IP to stop at, may be repeated")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1))
+      .arg(Arg::with_name("back")
+        .long("back")
+        .help("Steps to undo after stopping")
+        .takes_value(true)
+        .default_value("0"))
+      .arg(Arg::with_name("format")
+        .long("format")
+        .takes_value(true)
+        .possible_values(&["raw", "hex", "ihex", "text"])
+        .default_value("text"))
+      .arg(Arg::with_name("save")
+        .long("save")
+        .help("Write the state to this file instead of printing it")
+        .takes_value(true)))
+    .get_matches();
+
+  let res = match matches.subcommand() {
+    ("solve", Some(args)) => solve(args),
+    ("paths", Some(args)) => paths(args),
+    ("dot", Some(args)) => dot(args),
+    ("trace", Some(args)) => record(args),
+    ("diff", Some(args)) => diff(args),
+    ("debug", Some(args)) => debug(args),
+    _ => unreachable!{},
+  };
+  if let Err(e) = res {
+    eprintln!{"{}", e};
+    process::exit(1);
+  }
+}
+
+fn solve(args: &ArgMatches) -> Result<(), String> {
+  let (base, free) = parse_template(args.value_of("template").unwrap())?;
+  let objective = match args.value_of("minimize") {
+    Some("calls") => Objective::MinCalls,
+    Some("changes") => Objective::MinChanges,
+    _ => Objective::Any,
+  };
+  let seed = u32::try_from(parse_num(args, "seed")?)
+    .map_err(|_| format!{"invalid seed: {}", args.value_of("seed").unwrap()})?;
+  let level = Level::current();
+  debug!{"Solving {:?} with {} free nibbles", level, free.len()};
+  let solver = Solver::new(level, base)
+    .set_free(free)
+    .set_budget(parse_num(args, "budget")?)
+    .set_max_solutions(parse_num(args, "max")?)
+    .set_objective(objective)
+    .set_seed(seed);
+  let report = match args.value_of("search") {
+    Some("random") => solver.random(),
+    Some("genetic") => solver.genetic(),
+    _ => solver.brute_force(),
+  };
+  let format: Format = args.value_of("format").unwrap().parse().map_err(|e| error_chain(&e))?;
+  for sol in report.solutions.iter() {
+    match format {
+      Format::Hex => println!{"{}  calls={} changes={}", sol.to_hex(), sol.calls, sol.changes},
+      _ => {
+        println!{"# calls={} changes={}", sol.calls, sol.changes};
+        print!{"{}", String::from_utf8_lossy(&format.render(&sol.image))};
+      },
+    };
+  }
+  println!{"{} solutions, {} runs{}", report.solutions.len(), report.runs,
+    if report.complete { ", search complete" } else { "" }};
+  Ok(())
+}
+
+fn paths(args: &ArgMatches) -> Result<(), String> {
+  let (base, symbolic) = parse_template(args.value_of("template").unwrap())?;
+  let mut exec = Executor::new(Level::current(), base)
+    .set_symbolic(symbolic)
+    .set_max_paths(parse_num(args, "max")?);
+  if args.is_present("inputs") {
+    exec = exec.set_symbolic_input(parse_num(args, "inputs")?);
+  }
+  // any image reaching the end of a path is a witness for it
+  let report = exec
+    .set_max_solutions(usize::MAX)
+    .solve(|_| expr::konst(1));
+  for found in report.found.iter() {
+    let image: String = (0..IMAGE_NIBBLES)
+      .map(|i| format!{"{:X}", get_nibble(&found.image, i)})
+      .collect();
+    println!{"{}  input={:?} calls={} end={:?}", image, found.input, found.calls, found.end};
+  }
+  println!{"{} paths{}", report.paths,
+    if report.complete { ", exploration complete" } else { "" }};
+  Ok(())
+}
+
+fn dot(args: &ArgMatches) -> Result<(), String> {
+  let image = parse_image(args.value_of("image").unwrap())?;
+  let isa = match args.is_present("extended") {
+    true => Isa::Extended,
+    false => Isa::Standard,
+  };
+  let machine = Machine::from(image).set_isa(isa);
+  print!{"{}", Cfg::from(&machine).to_dot()};
+  Ok(())
+}
+
+fn record(args: &ArgMatches) -> Result<(), String> {
+  let trace = match args.value_of("load") {
+    Some(path) => {
+      let mut file = File::open(path).map_err(|e| e.to_string())?;
+      Trace::read_from(&mut file).map_err(|e| error_chain(&e))?
+    },
+    None => {
+      let image = parse_image(args.value_of("image").unwrap())?;
+      let mut machine = load(Level::current(), &image)?.set_recording(true);
+      if let Err(e) = machine.exec() {
+        eprintln!{"{}", error_chain(&e)};
+      }
+      machine.take_trace().unwrap()
+    },
+  };
+  if let Some(path) = args.value_of("save") {
+    let mut file = File::create(path).map_err(|e| e.to_string())?;
+    trace.write_to(&mut file).map_err(|e| error_chain(&e))?;
+  }
+  print!{"{}", trace.dump()};
+  Ok(())
+}
+
+fn diff(args: &ArgMatches) -> Result<(), String> {
+  let left = parse_image(args.value_of("left").unwrap())?;
+  let right = match args.value_of("right") {
+    Some(text) => parse_image(text)?,
+    None => left,
+  };
+  let ruleset = |side: &str| Ruleset {
+    level: Level::current(),
+    isa: match args.value_of(format!{"{}-isa", side}) {
+      Some("extended") => Isa::Extended,
+      _ => Isa::Standard,
+    },
+    endian: match args.value_of(format!{"{}-endian", side}) {
+      Some("little") => Endian::Little,
+      _ => Endian::Big,
+    },
+  };
+  let load = |side: &str, image: &MachineInner| {
+    ruleset(side).try_load(image, 1).map_err(|e| format!{"{} {}", side, error_chain(&e)})
+  };
+  let diff = Diff::new(&load("left", &left)?, &load("right", &right)?);
+  print!{"{}", diff.report(parse_num(args, "context")?)};
+  Ok(())
+}
+
+fn debug(args: &ArgMatches) -> Result<(), String> {
+  let image = parse_image(args.value_of("image").unwrap())?;
+  let mut dbg = Debugger::new(load(Level::current(), &image)?);
+  for ip in args.values_of("break").into_iter().flatten() {
+    let ip = u8::from_str_radix(ip, 16).map_err(|_| format!{"invalid break: {}", ip})?;
+    dbg = dbg.set_breakpoint(ip);
+  }
+  let stop = dbg.cont();
+  eprintln!{"{:?} after {} steps", stop, dbg.get_position()};
+  let back = parse_num(args, "back")?;
+  if back > 0 {
+    let target = dbg.get_position().saturating_sub(back);
+    dbg.restore(target);
+    eprintln!{"back at step {}", target};
+  }
+  let format: Format = args.value_of("format").unwrap().parse().map_err(|e| error_chain(&e))?;
+  let state = format.render(&dbg.get_machine().into());
+  match args.value_of("save") {
+    Some(path) => File::create(path).and_then(|mut f| f.write_all(&state)),
+    None => std::io::stdout().write_all(&state),
+  }.map_err(|e| e.to_string())
+}
+
+// refuses images that cannot start a run
+fn load(level: Level, image: &MachineInner) -> Result<Machine, String> {
+  let hook = match log_enabled!{log::Level::Debug} {
+    true => Some(log_step as StepHook),
+    false => None,
+  };
+  level.try_load(image, 1).map(|m| m.set_hook(hook)).map_err(|e| error_chain(&e))
+}
+
+// hex on the command line, or a file in any image format
+fn parse_image(text: &str) -> Result<MachineInner, String> {
+  if Path::new(text).is_file() {
+    let mut file = File::open(text).map_err(|e| e.to_string())?;
+    return read_image(&mut file).map_err(|e| error_chain(&e))
+  }
+  match parse_template(text)? {
+    (image, ref free) if free.is_empty() => Ok(image),
+    _ => Err("image may not contain '?'".to_string()),
+  }
+}
+
+// "60 04 3? ..." into a base image and the free nibble indexes
+fn parse_template(text: &str) -> Result<(MachineInner, Vec<usize>), String> {
+  let nibbles: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+  if nibbles.len() != IMAGE_NIBBLES {
+    return Err(format!{"template needs {} nibbles, got {}", IMAGE_NIBBLES, nibbles.len()});
+  }
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  let mut free = Vec::new();
+  for (idx, c) in nibbles.iter().enumerate() {
+    match (*c, c.to_digit(16)) {
+      ('?', _) => free.push(idx),
+      (_, Some(v)) => set_nibble(&mut image, idx, v as u8),
+      (c, None) => return Err(format!{"invalid nibble '{}' at {}", c, idx}),
+    }
+  }
+  Ok((image, free))
+}
+
+fn parse_num(args: &ArgMatches, name: &str) -> Result<usize, String> {
+  let val = args.value_of(name).unwrap();
+  val.parse().map_err(|_| format!{"invalid {}: {}", name, val})
+}
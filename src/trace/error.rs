@@ -0,0 +1,40 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+pub type TraceResult<T> = Result<T, TraceError>;
+#[derive(Clone,PartialEq)]
+pub enum TraceError {
+  BadMagic,
+  UnsupportedVersion(u8),
+  Truncated(usize),
+  BadRecord(usize),
+  Io(String),
+}
+impl fmt::Display for TraceError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      TraceError::BadMagic =>
+        write!{f, "Not a trace file"},
+      TraceError::UnsupportedVersion(v) =>
+        write!{f, "Unsupported trace version: {}", v},
+      TraceError::Truncated(o) =>
+        write!{f, "Trace ends mid record at offset: {}", o},
+      TraceError::BadRecord(o) =>
+        write!{f, "Record has both port flags at offset: {}", o},
+      TraceError::Io(ref e) =>
+        write!{f, "{}", e},
+    }
+  }
+}
+impl fmt::Debug for TraceError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!{f, "Trace Error: {}", self}
+  }
+}
+impl Error for TraceError {}
+impl From<io::Error> for TraceError {
+  fn from(err: io::Error) -> TraceError {
+    TraceError::Io(err.to_string())
+  }
+}
@@ -0,0 +1,214 @@
+#[cfg(test)] mod test;
+mod error;
+
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+use std::io::{Read, Write};
+use crate::machine::{Machine, MachineInner, MACHINE_SIZE};
+use crate::registers::{Registers, RegisterInner, REGISTER_SIZE};
+use crate::machine::{get_nibble, set_nibble, IMAGE_NIBBLES, MEMORY_OFFSET};
+use self::error::TraceError as TrcErr;
+use self::error::TraceResult as TrcRes;
+
+pub use self::error::TraceError;
+pub use self::error::TraceResult;
+pub const TRACE_MAGIC: &[u8; 4] = b"TMTR";
+pub const TRACE_VERSION: u8 = 1;
+// low nibble of a record's first byte, IP is the high nibble
+const WRITE_FLAG: u8 = 0b0001;
+const IN_FLAG: u8 = 0b0010;
+const OUT_FLAG: u8 = 0b0100;
+const FAULT_FLAG: u8 = 0b1000;
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum PortOp {
+  In(u8),  // popped from the input
+  Out(u8), // pushed to the output
+}
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct Step {
+  pub ip: u8,                  // where the instruction was fetched
+  pub opcode: u8,              // byte at ip, as stored
+  pub regs: RegisterInner,     // register file after the step
+  pub write: Option<(u8, u8)>, // memory nibble changed and its new value
+  pub port: Option<PortOp>,
+  pub fault: bool,             // the step ended in an error
+}
+
+// Called with the machine and what it did after each step
+pub type StepHook = fn(&Machine, &Step);
+
+// hook that logs each step, the interpreter itself does not log
+pub fn log_step(_machine: &Machine, _step: &Step) {
+  #[cfg(not(feature = "lvl3"))]
+  debug!{"{}", _step};
+}
+
+// fetch, registers after, then any side effects
+impl fmt::Display for Step {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let reg = Registers::from(self.regs);
+    write!{f, "{:X}: {:02X}  IP={:X} LI={:X} FR={:X} AC={:X}",
+      self.ip, self.opcode, reg.get_ip(), reg.get_li(), reg.get_fr(), reg.get_ac()}?;
+    if let Some((addr, value)) = self.write {
+      write!{f, "  [{:X}]={:X}", addr, value}?;
+    }
+    match self.port {
+      Some(PortOp::In(v)) => write!{f, "  in {:X}", v}?,
+      Some(PortOp::Out(v)) => write!{f, "  out {:X}", v}?,
+      None => {},
+    };
+    if self.fault {
+      write!{f, "  fault"}?;
+    }
+    Ok(())
+  }
+}
+
+// Recorded run: the starting image then one record per step.
+//
+// Binary layout, version 1:
+//   "TMTR" version image[10]
+//   per step: ip<<4|flags opcode regs[2] [addr<<4|value] [port value]
+// The optional bytes are present when WRITE_FLAG / IN_FLAG|OUT_FLAG are set.
+#[derive(Clone,PartialEq,Debug)]
+pub struct Trace {
+  image: MachineInner,
+  steps: Vec<Step>,
+}
+impl Trace {
+  pub fn new(image: MachineInner) -> Self {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Trace::new()"};
+    Trace {
+      image,
+      steps: Vec::new(),
+    }
+  }
+  pub fn get_image(&self) -> &MachineInner {
+    &self.image
+  }
+  pub fn get_steps(&self) -> &[Step] {
+    &self.steps
+  }
+  pub fn len(&self) -> usize {
+    self.steps.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.steps.is_empty()
+  }
+  // called by Machine::step once the step is complete
+  pub(crate) fn record(&mut self, step: Step) {
+    self.steps.push(step);
+  }
+  // drops the last step when the machine steps back
+  pub(crate) fn unrecord(&mut self) {
+    self.steps.pop();
+  }
+  // registers and memory after n steps, the image itself for 0
+  pub fn state_at(&self, n: usize) -> MachineInner {
+    let mut state = self.image;
+    for step in self.steps.iter().take(n) {
+      state[..REGISTER_SIZE].copy_from_slice(&step.regs);
+      if let Some((addr, value)) = step.write {
+        set_nibble(&mut state, MEMORY_OFFSET + addr as usize, value);
+      }
+    }
+    state
+  }
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(15 + self.steps.len() * 6);
+    bytes.extend_from_slice(TRACE_MAGIC);
+    bytes.push(TRACE_VERSION);
+    bytes.extend_from_slice(&self.image);
+    for step in self.steps.iter() {
+      let mut head = step.ip << 4;
+      if step.write.is_some() { head |= WRITE_FLAG; }
+      match step.port {
+        Some(PortOp::In(_)) => head |= IN_FLAG,
+        Some(PortOp::Out(_)) => head |= OUT_FLAG,
+        None => {},
+      };
+      if step.fault { head |= FAULT_FLAG; }
+      bytes.push(head);
+      bytes.push(step.opcode);
+      bytes.extend_from_slice(&step.regs);
+      if let Some((addr, value)) = step.write {
+        bytes.push(addr << 4 | value);
+      }
+      if let Some(PortOp::In(v)) | Some(PortOp::Out(v)) = step.port {
+        bytes.push(v);
+      }
+    }
+    bytes
+  }
+  pub fn from_bytes(bytes: &[u8]) -> TrcRes<Trace> {
+    #[cfg(not(feature = "lvl3"))]
+    trace!{"Trace::from_bytes()"};
+    if bytes.len() < TRACE_MAGIC.len() || &bytes[..TRACE_MAGIC.len()] != TRACE_MAGIC {
+      return Err(TrcErr::BadMagic)
+    }
+    let mut offset = TRACE_MAGIC.len();
+    let version = take(bytes, &mut offset, 1)?[0];
+    if version != TRACE_VERSION {
+      return Err(TrcErr::UnsupportedVersion(version))
+    }
+    let mut image: MachineInner = [0; MACHINE_SIZE];
+    image.copy_from_slice(take(bytes, &mut offset, MACHINE_SIZE)?);
+    let mut trace = Trace::new(image);
+    while offset < bytes.len() {
+      let start = offset;
+      let fixed = take(bytes, &mut offset, 2 + REGISTER_SIZE)?;
+      let (head, opcode) = (fixed[0], fixed[1]);
+      let regs: RegisterInner = [fixed[2], fixed[3]];
+      let write = match head & WRITE_FLAG {
+        0 => None,
+        _ => take(bytes, &mut offset, 1).map(|b| Some((b[0] >> 4, b[0] & 0xF)))?,
+      };
+      let port = match head & (IN_FLAG | OUT_FLAG) {
+        0 => None,
+        IN_FLAG => Some(PortOp::In(take(bytes, &mut offset, 1)?[0])),
+        OUT_FLAG => Some(PortOp::Out(take(bytes, &mut offset, 1)?[0])),
+        _ => return Err(TrcErr::BadRecord(start)),
+      };
+      trace.steps.push(Step {
+        ip: head >> 4,
+        opcode,
+        regs,
+        write,
+        port,
+        fault: head & FAULT_FLAG != 0,
+      });
+    }
+    Ok(trace)
+  }
+  pub fn write_to<W: Write>(&self, writer: &mut W) -> TrcRes<()> {
+    writer.write_all(&self.to_bytes())?;
+    Ok(())
+  }
+  pub fn read_from<R: Read>(reader: &mut R) -> TrcRes<Trace> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    Trace::from_bytes(&bytes)
+  }
+  // header then one line per step
+  pub fn dump(&self) -> String {
+    let mut out = String::new();
+    let image: String = (0..IMAGE_NIBBLES)
+      .map(|i| format!{"{:X}", get_nibble(&self.image, i)})
+      .collect();
+    let _ = writeln!{out, "trace v{} image {} steps {}", TRACE_VERSION, image, self.len()};
+    for (n, step) in self.steps.iter().enumerate() {
+      let _ = writeln!{out, "{:4}  {}", n, step};
+    }
+    out
+  }
+}
+
+// next n bytes of a record
+fn take<'a>(bytes: &'a [u8], offset: &mut usize, n: usize) -> TrcRes<&'a [u8]> {
+  let chunk = bytes.get(*offset..*offset + n).ok_or(TrcErr::Truncated(*offset))?;
+  *offset += n;
+  Ok(chunk)
+}
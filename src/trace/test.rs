@@ -0,0 +1,100 @@
+use std::io::Cursor;
+use crate::trace::*;
+use crate::machine::*;
+use crate::instructions::Isa;
+use crate::registers::RegisterInner;
+
+fn recorded(mem: [u8; 8]) -> Machine {
+  let mut image: MachineInner = [0; MACHINE_SIZE];
+  image[2..].copy_from_slice(&mem);
+  Machine::from(image).set_recording(true)
+}
+
+#[test]
+fn record_steps() {
+  // lda 7; sta 6; hlt
+  let mut machine = recorded([0x47, 0x56, 0, 0x03, 0, 0, 0, 0]);
+  machine.exec().unwrap();
+  let trace = machine.get_trace().unwrap();
+  assert_eq!{trace.len(), 3};
+  let steps = trace.get_steps();
+  assert_eq!{(steps[0].ip, steps[0].opcode, steps[0].write), (0, 0x47, None)};
+  assert_eq!{steps[0].regs, [0x20, 0x03]};
+  assert_eq!{(steps[1].ip, steps[1].opcode, steps[1].write), (2, 0x56, Some((6, 3)))};
+  assert!{steps.iter().all(|s| s.port.is_none() && !s.fault)};
+  assert_eq!{trace.state_at(0), *trace.get_image()};
+  assert_eq!{trace.state_at(3), MachineInner::from(&machine)};
+  let dump = trace.dump();
+  assert!{dump.starts_with("trace v1 image 00004756000300000000 steps 3")};
+  assert!{dump.contains("   1  2: 56  IP=4 LI=0 FR=0 AC=3  [6]=3\n")};
+  assert!{machine.take_trace().is_some()};
+  assert!{machine.get_trace().is_none()};
+}
+#[test]
+fn bytes_round_trip() {
+  let mut machine = recorded([0x47, 0x56, 0, 0x03, 0, 0, 0, 0]);
+  machine.exec().unwrap();
+  let trace = machine.take_trace().unwrap();
+  let bytes = trace.to_bytes();
+  // header, three 4 byte records and one write byte
+  assert_eq!{bytes.len(), 4 + 1 + MACHINE_SIZE + 3 * 4 + 1};
+  assert_eq!{Trace::from_bytes(&bytes), Ok(trace.clone())};
+  let mut file = Vec::new();
+  trace.write_to(&mut file).unwrap();
+  assert_eq!{Trace::read_from(&mut Cursor::new(file)), Ok(trace)};
+}
+#[test]
+fn bad_files() {
+  assert_eq!{Trace::from_bytes(b"TMT"), Err(TraceError::BadMagic)};
+  assert_eq!{Trace::from_bytes(b"XXXX\x01"), Err(TraceError::BadMagic)};
+  assert_eq!{Trace::from_bytes(b"TMTR\x09"), Err(TraceError::UnsupportedVersion(9))};
+  assert_eq!{Trace::from_bytes(b"TMTR\x01\x00"), Err(TraceError::Truncated(5))};
+  let mut bytes = Trace::new([0; MACHINE_SIZE]).to_bytes();
+  bytes.extend_from_slice(&[0x01, 0x45, 0x00]);
+  assert_eq!{Trace::from_bytes(&bytes), Err(TraceError::Truncated(15))};
+  // a step is either input or output, never both
+  let mut bytes = Trace::new([0; MACHINE_SIZE]).to_bytes();
+  bytes.extend_from_slice(&[0x06, 0x60, 0x00, 0x00, 0x03]);
+  assert_eq!{Trace::from_bytes(&bytes), Err(TraceError::BadRecord(15))};
+}
+#[test]
+fn fault_recorded() {
+  // ret with an empty stack
+  let mut machine = recorded([0x01, 0, 0, 0, 0, 0, 0, 0]).set_isa(Isa::Extended);
+  assert!{machine.exec().is_err()};
+  let trace = machine.take_trace().unwrap();
+  assert_eq!{trace.len(), 1};
+  assert!{trace.get_steps()[0].fault};
+  assert_eq!{Trace::from_bytes(&trace.to_bytes()), Ok(trace.clone())};
+  assert!{trace.dump().ends_with("fault\n")};
+}
+#[test]
+fn step_hook() {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  static WRITES: AtomicUsize = AtomicUsize::new(0);
+  fn count(machine: &Machine, step: &Step) {
+    assert_eq!{RegisterInner::from(machine.get_reg().clone()), step.regs};
+    if step.write.is_some() {
+      WRITES.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+  // lda 7; sta 0; sta 1; hlt, without recording
+  let image: MachineInner = [0, 0, 0x47, 0x50, 0x51, 0x03, 0, 0, 0, 0];
+  let mut machine = Machine::from(image).set_hook(Some(count));
+  machine.exec().unwrap();
+  assert!{machine.get_trace().is_none()};
+  assert_eq!{WRITES.load(Ordering::SeqCst), 2};
+}
+#[cfg(feature = "lvl3")]
+#[test]
+fn port_ops() {
+  use crate::level::Level;
+  // get; put; hlt
+  let image: MachineInner = [0, 0, 0x67, 0, 0, 0, 0, 0, 0, 0];
+  let mut machine = Level::Three.load(image, 0).set_recording(true);
+  machine.exec().unwrap();
+  let trace = machine.take_trace().unwrap();
+  let ports: Vec<_> = trace.get_steps().iter().map(|s| s.port).collect();
+  assert_eq!{ports, vec![Some(PortOp::In(1)), Some(PortOp::Out(1)), None]};
+  assert_eq!{Trace::from_bytes(&trace.to_bytes()), Ok(trace.clone())};
+}